@@ -0,0 +1,141 @@
+use std::{
+    collections::HashMap,
+    io::{self, Cursor, SeekFrom},
+    path::Path,
+};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+
+use crate::{
+    blocks::{self, BlockCursor},
+    header::{DEBUG_INFO_MASK, HEADER_PREFIX_SIZE},
+    tile::{self, BlockRequest, BlockSource, INDEX_SIGNATURE_SIZE, TILE_INDEX_ENTRY_SIZE},
+    types::{AsyncMapFile, BoundingBox, MapFile, MapHeader, QueryResult, Tile, TileIndexEntry},
+    Result,
+};
+
+impl AsyncMapFile {
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = tokio::fs::File::open(path).await?;
+
+        Self::from_reader(file).await
+    }
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin> AsyncMapFile<R> {
+    // reads the header and tile index, both are decoded by the same code as in
+    // MapFile once their bytes are in memory
+    pub async fn from_reader(mut reader: R) -> Result<Self> {
+        let mut header_bytes = vec![0u8; HEADER_PREFIX_SIZE as usize];
+        reader.read_exact(&mut header_bytes).await?;
+
+        let header_size = u32::from_be_bytes(header_bytes[20..24].try_into().unwrap());
+        read_bytes(&mut reader, header_size as u64, &mut header_bytes).await?;
+
+        let header = MapHeader::read_from_file(&mut Cursor::new(header_bytes))?;
+
+        let mut tile_indices = Vec::with_capacity(header.num_zoom_intervals as usize);
+        for interval in &header.zoom_interval_configuration {
+            reader.seek(SeekFrom::Start(interval.sub_file_start)).await?;
+
+            if header.flags & DEBUG_INFO_MASK != 0 {
+                let mut sig = [0u8; INDEX_SIGNATURE_SIZE as usize];
+                reader.read_exact(&mut sig).await?;
+                tile::check_index_signature(&sig)?;
+            }
+
+            let total_tiles = MapFile::calculate_total_tiles(&header.bounding_box, interval.base_zoom_level)?;
+
+            let mut index_bytes = vec![];
+            read_bytes(&mut reader, total_tiles as u64 * TILE_INDEX_ENTRY_SIZE as u64, &mut index_bytes).await?;
+
+            let tile_index = index_bytes
+                .chunks_exact(TILE_INDEX_ENTRY_SIZE)
+                .map(|bytes| TileIndexEntry::from_bytes(bytes.try_into().unwrap(), interval.sub_file_start))
+                .collect();
+            tile_indices.push(tile_index);
+        }
+
+        Ok(AsyncMapFile {
+            header,
+            reader,
+            tile_indices,
+        })
+    }
+
+    // same as MapFile::read_tile
+    pub async fn read_tile(&mut self, zoom: u8, x: u32, y: u32) -> Result<Tile> {
+        let request = BlockRequest::for_tile(&self.header, zoom, x, y)?;
+        let mut blocks = self.fetch_blocks(&request).await?;
+
+        tile::read_tile(&mut blocks, zoom, x, y)
+    }
+
+    // same as MapFile::query
+    pub async fn query(&mut self, bbox: BoundingBox, zoom: u8) -> Result<QueryResult> {
+        let request = BlockRequest::for_query(&self.header, &bbox, zoom)?;
+        let mut blocks = self.fetch_blocks(&request).await?;
+
+        tile::query(&mut blocks, bbox, zoom)
+    }
+
+    // reads the bytes of the requested blocks, they are decoded by the same
+    // code as in MapFile once all of them are in memory
+    async fn fetch_blocks(&mut self, request: &BlockRequest) -> Result<FetchedBlocks<'_>> {
+        let mut blocks = FetchedBlocks {
+            header: &self.header,
+            tile_indices: &self.tile_indices,
+            bytes: HashMap::new(),
+        };
+
+        for &(x, y) in &request.blocks {
+            let Some((start, end)) = blocks.block_range(request.interval_index, x, y)? else {
+                continue;
+            };
+
+            self.reader.seek(SeekFrom::Start(start)).await?;
+            let mut block = vec![];
+            read_bytes(&mut self.reader, end - start, &mut block).await?;
+            blocks.bytes.insert(start, block);
+        }
+
+        Ok(blocks)
+    }
+}
+
+// blocks read ahead of decoding, by their absolute start
+struct FetchedBlocks<'a> {
+    header: &'a MapHeader,
+    tile_indices: &'a [Vec<TileIndexEntry>],
+    bytes: HashMap<u64, Vec<u8>>,
+}
+
+impl BlockSource for FetchedBlocks<'_> {
+    type Tile = Tile;
+
+    fn header(&self) -> &MapHeader {
+        self.header
+    }
+
+    fn index_entry(&mut self, interval_index: usize, index: usize) -> Result<TileIndexEntry> {
+        Ok(self.tile_indices[interval_index][index])
+    }
+
+    fn decode_block(&mut self, interval_index: usize, x: u32, y: u32, (start, _): (u64, u64), zoom: u8, tile_bitmask: Option<u16>) -> Result<Tile> {
+        let interval = &self.header.zoom_interval_configuration[interval_index];
+        let mut cursor = BlockCursor::new(&self.bytes[&start], start);
+
+        blocks::process_poi_way_block(&mut cursor, self.header, interval, x, y, zoom, tile_bitmask)
+    }
+}
+
+// utils::read_bytes for async readers, appending to a buffer
+async fn read_bytes<R: AsyncRead + Unpin>(reader: &mut R, length: u64, buffer: &mut Vec<u8>) -> Result<()> {
+    let read = reader.take(length).read_to_end(buffer).await?;
+
+    if (read as u64) < length {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+
+    Ok(())
+}
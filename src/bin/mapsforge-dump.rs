@@ -0,0 +1,199 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use clap::{ArgGroup, Parser};
+use mapsforge_rs::{
+    types::{LatLong, MapFile, Tag, Tile, Way, POI},
+    MapforgeError, Result,
+};
+use serde_json::{json, Value};
+
+/// Prints the POIs and ways of one tile of a mapsforge map file
+#[derive(Parser)]
+#[command(name = "mapsforge-dump", version, about)]
+#[command(group(ArgGroup::new("tile").required(true).args(["x", "lat"])))]
+struct Args {
+    /// map file to read
+    map: PathBuf,
+
+    /// zoom level of the tile
+    #[arg(long)]
+    zoom: u8,
+
+    /// x tile number on the zoom level
+    #[arg(long, requires = "y")]
+    x: Option<u32>,
+
+    /// y tile number on the zoom level
+    #[arg(long, requires = "x")]
+    y: Option<u32>,
+
+    /// latitude of a position in the tile
+    #[arg(long, requires = "lon", allow_negative_numbers = true)]
+    lat: Option<f64>,
+
+    /// longitude of a position in the tile
+    #[arg(long, requires = "lat", allow_negative_numbers = true)]
+    lon: Option<f64>,
+
+    /// print the tile as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let (x, y, tile) = match read_tile(&args) {
+        Ok(tile) => tile,
+        Err(e) => {
+            eprintln!("mapsforge-dump: {}: {e}", args.map.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if args.json {
+        println!("{:#}", tile_json(&tile, args.zoom, x, y));
+    } else {
+        print_tile(&tile, args.zoom, x, y);
+    }
+
+    ExitCode::SUCCESS
+}
+
+// the tile numbers and the tile, either given directly or as the tile
+// containing the position
+fn read_tile(args: &Args) -> Result<(u32, u32, Tile)> {
+    let mut map_file = MapFile::open(&args.map)?;
+    if map_file.zoom_interval_index(args.zoom).is_none() {
+        return Err(MapforgeError::InvalidZoomLevel(args.zoom));
+    }
+
+    let (x, y) = match (args.x, args.y, args.lat, args.lon) {
+        (Some(x), Some(y), _, _) => (x, y),
+        (_, _, Some(lat), Some(lon)) => MapFile::get_tiles(lat, lon, args.zoom)?,
+        // clap makes sure one of both pairs is given
+        _ => unreachable!(),
+    };
+
+    let tile = map_file.read_tile(args.zoom, x, y)?;
+
+    Ok((x, y, tile))
+}
+
+fn print_tile(tile: &Tile, zoom: u8, x: u32, y: u32) {
+    println!("tile {zoom}/{x}/{y}: {} POIs, {} ways", tile.pois.len(), tile.ways.len());
+
+    for (index, poi) in tile.pois.iter().enumerate() {
+        println!();
+        println!("POI {index}");
+        println!("  position:     {}", format_position(&poi.position));
+        println!("  layer:        {}", poi.layer);
+        println!("  tags:         {}", format_tags(poi.tags.as_deref()));
+        if let Some(name) = &poi.name {
+            println!("  name:         {name}");
+        }
+        if let Some(house_number) = &poi.house_number {
+            println!("  house number: {house_number}");
+        }
+        if let Some(elevation) = poi.elevation {
+            println!("  elevation:    {elevation}");
+        }
+    }
+
+    for (index, way) in tile.ways.iter().enumerate() {
+        println!();
+        println!("way {index}");
+        println!("  layer:           {}", way.layer);
+        println!("  tags:            {}", format_tags(way.tags.as_deref()));
+        println!("  sub-tile bitmap: {:016b}", way.sub_tile_bitmap);
+        if let Some(label_position) = &way.label_position {
+            println!("  label position:  {}", format_position(label_position));
+        }
+        for (data_block_index, blocks) in way.coordinate_blocks.iter().enumerate() {
+            println!("  data block {data_block_index}:");
+            for (block_index, block) in blocks.iter().enumerate() {
+                println!("    block {block_index}: {} nodes", block.coordinates.len());
+                for node in &block.coordinates {
+                    println!("      {}", format_position(node));
+                }
+            }
+        }
+    }
+}
+
+fn format_position(position: &LatLong) -> String {
+    format!("{:.6}, {:.6}", position.latitude, position.longitude)
+}
+
+fn format_tags(tags: Option<&[Tag]>) -> String {
+    let tags: Vec<String> = tags
+        .unwrap_or_default()
+        .iter()
+        .map(|tag| format!("{}={}", tag.key, tag.value))
+        .collect();
+
+    if tags.is_empty() {
+        "-".to_string()
+    } else {
+        tags.join(", ")
+    }
+}
+
+fn tile_json(tile: &Tile, zoom: u8, x: u32, y: u32) -> Value {
+    json!({
+        "zoom": zoom,
+        "x": x,
+        "y": y,
+        "pois": tile.pois.iter().map(poi_json).collect::<Vec<_>>(),
+        "ways": tile.ways.iter().map(way_json).collect::<Vec<_>>(),
+    })
+}
+
+fn poi_json(poi: &POI) -> Value {
+    json!({
+        "position": position_json(&poi.position),
+        "layer": poi.layer,
+        "tags": tags_json(poi.tags.as_deref()),
+        "name": poi.name,
+        "house_number": poi.house_number,
+        "elevation": poi.elevation,
+    })
+}
+
+fn way_json(way: &Way) -> Value {
+    // one list of coordinate blocks per data block
+    let blocks: Vec<Vec<Vec<Value>>> = way
+        .coordinate_blocks
+        .iter()
+        .map(|blocks| {
+            blocks
+                .iter()
+                .map(|block| block.coordinates.iter().map(position_json).collect())
+                .collect()
+        })
+        .collect();
+
+    json!({
+        "layer": way.layer,
+        "tags": tags_json(way.tags.as_deref()),
+        "name": way.name,
+        "house_number": way.house_number,
+        "ref": way.reference,
+        "sub_tile_bitmap": way.sub_tile_bitmap,
+        "label_position": way.label_position.as_ref().map(position_json),
+        "coordinate_blocks": blocks,
+    })
+}
+
+// latitude first, like everywhere else in the output
+fn position_json(position: &LatLong) -> Value {
+    json!([position.latitude, position.longitude])
+}
+
+// a list rather than an object, a key may appear more than once
+fn tags_json(tags: Option<&[Tag]>) -> Value {
+    tags.unwrap_or_default()
+        .iter()
+        .map(|tag| json!([tag.key, tag.value]))
+        .collect()
+}
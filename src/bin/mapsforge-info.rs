@@ -0,0 +1,149 @@
+use std::{fs::File, io::BufReader, path::PathBuf, process::ExitCode};
+
+use clap::Parser;
+use mapsforge_rs::{header::DEBUG_INFO_MASK, types::MapFile, MapHeader, Result};
+use serde_json::{json, Value};
+
+/// Prints the header of a mapsforge map file
+#[derive(Parser)]
+#[command(name = "mapsforge-info", version, about)]
+struct Args {
+    /// map file to inspect
+    map: PathBuf,
+
+    /// print the header as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    // only the header is read, so even large maps open instantly
+    let header = match read_header(&args) {
+        Ok(header) => header,
+        Err(e) => {
+            eprintln!("mapsforge-info: {}: {e}", args.map.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if args.json {
+        println!("{:#}", header_json(&header));
+    } else {
+        print_header(&header);
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn read_header(args: &Args) -> Result<MapHeader> {
+    let mut reader = BufReader::new(File::open(&args.map)?);
+    MapHeader::read_from_file(&mut reader)
+}
+
+fn print_header(header: &MapHeader) {
+    let bbox = &header.bounding_box;
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+
+    println!("file version:        {}", header.file_version);
+    println!("file size:           {} bytes", header.file_size);
+    println!("header size:         {} bytes", header.header_size);
+    println!("creation date:       {} ({} ms)", format_date(header.creation_date), header.creation_date);
+    println!("bounding box:        {}, {}, {}, {}", bbox.min_lat, bbox.min_lon, bbox.max_lat, bbox.max_lon);
+    println!("tile size:           {}", header.tile_size);
+    println!("projection:          {}", header.projection);
+    println!("flags:               {:#04x}", header.flags);
+    println!("debug information:   {}", if header.flags & DEBUG_INFO_MASK != 0 { "yes" } else { "no" });
+    println!(
+        "start position:      {}",
+        optional(header.map_start_position.map(|position| format!("{}, {}", position.latitude, position.longitude)))
+    );
+    println!("start zoom level:    {}", optional(header.start_zoom_level.map(|zoom| zoom.to_string())));
+    println!("language preference: {}", optional(header.language_preference.clone()));
+    println!("comment:             {}", optional(header.comment.clone()));
+    println!("created by:          {}", optional(header.created_by.clone()));
+    println!("POI tags:            {}", header.poi_tags.len());
+    println!("way tags:            {}", header.way_tags.len());
+    println!();
+
+    println!("zoom intervals:      {}", header.num_zoom_intervals);
+    println!("{:>5} {:>5} {:>5} {:>5} {:>14} {:>14} {:>10}", "#", "base", "min", "max", "sub-file start", "sub-file size", "tiles");
+    for (index, interval) in header.zoom_interval_configuration.iter().enumerate() {
+        println!(
+            "{index:>5} {:>5} {:>5} {:>5} {:>14} {:>14} {:>10}",
+            interval.base_zoom_level,
+            interval.min_zoom_level,
+            interval.max_zoom_level,
+            interval.sub_file_start,
+            interval.sub_file_size,
+            // too many tiles for a tile index are shown as unknown
+            MapFile::calculate_total_tiles(bbox, interval.base_zoom_level).map_or("-".to_string(), |tiles| tiles.to_string()),
+        );
+    }
+}
+
+fn header_json(header: &MapHeader) -> Value {
+    let bbox = &header.bounding_box;
+    let zoom_intervals: Vec<Value> = header
+        .zoom_interval_configuration
+        .iter()
+        .map(|interval| {
+            json!({
+                "base_zoom_level": interval.base_zoom_level,
+                "min_zoom_level": interval.min_zoom_level,
+                "max_zoom_level": interval.max_zoom_level,
+                "sub_file_start": interval.sub_file_start,
+                "sub_file_size": interval.sub_file_size,
+                "tiles": MapFile::calculate_total_tiles(bbox, interval.base_zoom_level).ok(),
+            })
+        })
+        .collect();
+
+    json!({
+        "file_version": header.file_version,
+        "file_size": header.file_size,
+        "header_size": header.header_size,
+        "creation_date": header.creation_date,
+        "bounding_box": {
+            "min_lat": bbox.min_lat,
+            "min_lon": bbox.min_lon,
+            "max_lat": bbox.max_lat,
+            "max_lon": bbox.max_lon,
+        },
+        "tile_size": header.tile_size,
+        "projection": header.projection,
+        "flags": header.flags,
+        "debug_information": header.flags & DEBUG_INFO_MASK != 0,
+        "map_start_position": header.map_start_position.map(|position| json!({
+            "latitude": position.latitude,
+            "longitude": position.longitude,
+        })),
+        "start_zoom_level": header.start_zoom_level,
+        "language_preference": header.language_preference,
+        "comment": header.comment,
+        "created_by": header.created_by,
+        "poi_tags": header.poi_tags.len(),
+        "way_tags": header.way_tags.len(),
+        "zoom_intervals": zoom_intervals,
+    })
+}
+
+// milliseconds since the epoch as UTC date and time
+fn format_date(milliseconds: u64) -> String {
+    let seconds = milliseconds / 1000;
+    let (days, time) = ((seconds / 86400) as i64, seconds % 86400);
+
+    // civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC", time / 3600, time % 3600 / 60, time % 60)
+}
@@ -1,19 +1,242 @@
-use std::io::{BufReader, Read};
+use std::{
+    borrow::Cow,
+    fmt,
+    io::{self, Cursor, Read, Seek, SeekFrom},
+};
 
-use crate::{types::ZoomInterval, utils, Result};
+use log::debug;
 
-pub fn process_poi_way_block() {
+use crate::{
+    error::MapforgeError,
+    header::DEBUG_INFO_MASK,
+    poi,
+    types::{LatLong, MapFile, MapHeader, Tile, TileContext, TileRef, ZoomInterval},
+    utils, way, Result,
+};
 
+pub(crate) const TILE_SIGNATURE_PREFIX: &str = "###TileStart";
+
+impl TileContext {
+    pub fn new(zoom_level: u8, x: u32, y: u32, debug: bool) -> Self {
+        TileContext {
+            zoom_level,
+            x,
+            y,
+            origin: LatLong {
+                latitude: MapFile::tiley2lat(y, zoom_level),
+                longitude: MapFile::tilex2long(x, zoom_level),
+            },
+            debug,
+        }
+    }
+}
+
+// zoom/x/y of the tile, used as context in log messages
+impl fmt::Display for TileContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.zoom_level, self.x, self.y)
+    }
+}
+
+// a tile block read into memory that keeps the file offsets of its bytes, so
+// positions in errors are the same as when decoding straight from the file
+pub(crate) struct BlockCursor<'a> {
+    cursor: Cursor<&'a [u8]>,
+    start: u64,
+}
+
+impl<'a> BlockCursor<'a> {
+    pub(crate) fn new(block: &'a [u8], start: u64) -> Self {
+        BlockCursor {
+            cursor: Cursor::new(block),
+            start,
+        }
+    }
+
+    // the next length bytes of the block, without copying them
+    fn take_bytes(&mut self, length: usize) -> Result<&'a [u8]> {
+        let block: &'a [u8] = self.cursor.get_ref();
+        let position = self.cursor.position() as usize;
+
+        let end = position
+            .checked_add(length)
+            .filter(|&end| end <= block.len())
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        self.cursor.set_position(end as u64);
+
+        Ok(&block[position..end])
+    }
+}
+
+impl Read for BlockCursor<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.cursor.read(buf)
+    }
 }
 
-pub fn read_zoom_table<R: Read>(reader: &mut BufReader<R>, zoom_interval: &ZoomInterval) -> Result<Vec<(usize, usize)>> {
-    let mut zoom_table= Vec::with_capacity((zoom_interval.max_zoom_level-zoom_interval.min_zoom_level) as usize);
-    for zl in zoom_interval.min_zoom_level..zoom_interval.max_zoom_level + 1 {
+impl Seek for BlockCursor<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(offset) => SeekFrom::Start(offset.checked_sub(self.start).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a position before the block")
+            })?),
+            relative => relative,
+        };
+
+        Ok(self.cursor.seek(pos)? + self.start)
+    }
+}
+
+// where the decoders get their strings from: a block in memory lends them,
+// any other reader copies them
+pub(crate) trait BlockRead<'a>: Read + Seek {
+    fn read_str(&mut self) -> Result<Cow<'a, str>>;
+
+    fn read_debug_signature(&mut self) -> Result<Cow<'a, str>>;
+}
+
+impl<'a> BlockRead<'a> for BlockCursor<'a> {
+    // invalid UTF-8 is reported with the byte offset of the string, like
+    // utils::read_string does
+    fn read_str(&mut self) -> Result<Cow<'a, str>> {
+        let length = utils::read_vbe_u_int(self)?;
+        let offset = self.stream_position()?;
+        let bytes = self.take_bytes(length)?;
+
+        std::str::from_utf8(bytes)
+            .map(Cow::Borrowed)
+            .map_err(|_| MapforgeError::InvalidUtf8(offset))
+    }
+
+    fn read_debug_signature(&mut self) -> Result<Cow<'a, str>> {
+        let signature = match String::from_utf8_lossy(self.take_bytes(utils::DEBUG_SIGNATURE_SIZE)?) {
+            Cow::Borrowed(signature) => Cow::Borrowed(signature.trim()),
+            Cow::Owned(signature) => Cow::Owned(signature.trim().to_string()),
+        };
+
+        Ok(signature)
+    }
+}
+
+// decodes straight from a reader such as a file, strings are copied
+pub(crate) struct CopiedStrings<'r, R>(pub &'r mut R);
+
+impl<R: Read> Read for CopiedStrings<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+// forwards seek_relative and stream_position too, a BufReader keeps its
+// buffer for them
+impl<R: Seek> Seek for CopiedStrings<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.0.seek(pos)
+    }
+
+    fn seek_relative(&mut self, offset: i64) -> io::Result<()> {
+        self.0.seek_relative(offset)
+    }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        self.0.stream_position()
+    }
+}
+
+impl<'a, R: Read + Seek> BlockRead<'a> for CopiedStrings<'_, R> {
+    fn read_str(&mut self) -> Result<Cow<'a, str>> {
+        utils::read_string(self.0).map(Cow::Owned)
+    }
+
+    fn read_debug_signature(&mut self) -> Result<Cow<'a, str>> {
+        utils::read_debug_signature(self.0).map(Cow::Owned)
+    }
+}
+
+// decodes a single tile block starting at the current reader position:
+// optional debug signature, zoom table, first way offset, POIs and ways
+// x and y are the tile numbers of the block on the base zoom level, only the
+// POIs and ways visible on query_zoom_level are decoded, ways outside of the
+// sub-tiles in tile_bitmask are skipped
+pub fn process_poi_way_block<R: Read + Seek>(reader: &mut R, header: &MapHeader, zoom_interval: &ZoomInterval, x: u32, y: u32, query_zoom_level: u8, tile_bitmask: Option<u16>) -> Result<Tile> {
+    let tile = decode_block(&mut CopiedStrings(reader), header, zoom_interval, x, y, query_zoom_level, tile_bitmask)?;
+
+    Ok(tile.into_owned())
+}
+
+// process_poi_way_block with the strings borrowed from the reader if it lends
+// them, tag keys and values are borrowed from the header
+pub(crate) fn decode_block<'a>(reader: &mut impl BlockRead<'a>, header: &'a MapHeader, zoom_interval: &ZoomInterval, x: u32, y: u32, query_zoom_level: u8, tile_bitmask: Option<u16>) -> Result<TileRef<'a>> {
+    let tile = TileContext::new(zoom_interval.base_zoom_level, x, y, header.flags & DEBUG_INFO_MASK != 0);
+
+    let debug_signature = if tile.debug {
+        let signature = reader.read_debug_signature()?;
+        if !signature.starts_with(TILE_SIGNATURE_PREFIX) {
+            return Err(MapforgeError::InvalidTileSignature(x, y, signature.into_owned()));
+        }
+        Some(signature)
+    } else {
+        None
+    };
+
+    let zoom_table = read_zoom_table(reader, zoom_interval)?;
+    let first_way_offset = utils::read_vbe_u_int(reader)?;
+
+    let (number_of_pois, number_of_ways) = features_on_zoom_level(&zoom_table, zoom_interval, query_zoom_level);
+    debug!(
+        "tile {tile}: zoom table {zoom_table:?}, first way offset {first_way_offset}, reading {number_of_pois} POIs and {number_of_ways} ways for zoom level {query_zoom_level}"
+    );
+
+    // the POI section is first_way_offset bytes long, whatever is left of it
+    // after the POIs of the query zoom level is skipped
+    let poi_section_start = reader.stream_position()?;
+    let pois = poi::decode_pois(reader, number_of_pois, &header.poi_tags, &tile)?;
+    let poi_section_read = reader.stream_position()? - poi_section_start;
+    reader.seek_relative(first_way_offset as i64 - poi_section_read as i64)?;
+
+    let ways = way::decode_ways(reader, number_of_ways, &header.way_tags, &tile, tile_bitmask)?;
+
+    Ok(TileRef {
+        debug_signature,
+        zoom_table: zoom_table.iter().map(|&(p, w)| (p as u32, w as u32)).collect(),
+        first_way_offset: first_way_offset as u32,
+        pois,
+        ways,
+    })
+}
+
+// features are sorted by the lowest zoom level they appear on, the zoom table
+// holds how many start on each level so the counts add up to the query zoom level
+pub fn features_on_zoom_level(zoom_table: &[(usize, usize)], zoom_interval: &ZoomInterval, query_zoom_level: u8) -> (usize, usize) {
+    let query_zoom_level = query_zoom_level.clamp(zoom_interval.min_zoom_level, zoom_interval.max_zoom_level);
+    let rows = (query_zoom_level - zoom_interval.min_zoom_level) as usize + 1;
+
+    zoom_table
+        .iter()
+        .take(rows)
+        .fold((0, 0), |(pois, ways), &(p, w)| (pois + p, ways + w))
+}
+
+pub fn read_zoom_table<R: Read>(reader: &mut R, zoom_interval: &ZoomInterval) -> Result<Vec<(usize, usize)>> {
+    let mut zoom_table = vec![];
+    for _ in zoom_interval.min_zoom_level..=zoom_interval.max_zoom_level {
         let pois = utils::read_vbe_u_int(reader)?;
         let ways = utils::read_vbe_u_int(reader)?;
-        println!("zoomlevell {zl} has {pois} pois and {ways} ways");
         zoom_table.push((pois,ways));
     }
 
     Ok(zoom_table)
-}
\ No newline at end of file
+}
+
+impl TileRef<'_> {
+    // copies the borrowed strings into a Tile
+    pub fn into_owned(self) -> Tile {
+        Tile {
+            debug_signature: self.debug_signature.map(Cow::into_owned),
+            zoom_table: self.zoom_table,
+            first_way_offset: self.first_way_offset,
+            pois: self.pois.into_iter().map(|poi| poi.into_owned()).collect(),
+            ways: self.ways.into_iter().map(|way| way.into_owned()).collect(),
+        }
+    }
+}
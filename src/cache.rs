@@ -0,0 +1,112 @@
+use std::mem::size_of;
+
+use lru::LruCache;
+
+use crate::{
+    blocks,
+    types::{LatLong, Tag, Tile, TileCache, Way, WayCoordinateBlock, ZoomInterval, POI},
+};
+
+impl TileCache {
+    pub fn new(budget: usize) -> Self {
+        TileCache {
+            budget,
+            size: 0,
+            tiles: LruCache::unbounded(),
+        }
+    }
+
+    pub fn get(&mut self, interval_index: usize, x: u32, y: u32) -> Option<&Tile> {
+        self.tiles.get(&(interval_index, x, y))
+    }
+
+    // adds a block and drops the least recently used ones until the cache fits
+    // into its budget again, a block larger than the whole budget isn't kept
+    pub fn insert(&mut self, interval_index: usize, x: u32, y: u32, tile: Tile) {
+        let tile_size = tile.approximate_size();
+        if tile_size > self.budget {
+            return;
+        }
+
+        if let Some(previous) = self.tiles.put((interval_index, x, y), tile) {
+            self.size -= previous.approximate_size();
+        }
+        self.size += tile_size;
+
+        while self.size > self.budget {
+            match self.tiles.pop_lru() {
+                Some((_, dropped)) => self.size -= dropped.approximate_size(),
+                None => break,
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.tiles.clear();
+        self.size = 0;
+    }
+}
+
+impl Tile {
+    // bytes the tile takes in memory including its features, strings are
+    // counted by length so this is a lower bound
+    pub fn approximate_size(&self) -> usize {
+        size_of::<Tile>()
+            + self.debug_signature.as_ref().map_or(0, String::len)
+            + self.zoom_table.len() * size_of::<(u32, u32)>()
+            + self.pois.iter().map(poi_size).sum::<usize>()
+            + self.ways.iter().map(way_size).sum::<usize>()
+    }
+
+    // the features of a fully decoded block that a read at zoom would have
+    // decoded, ways outside of the sub-tiles in tile_bitmask are left out
+    pub(crate) fn features_for(&self, interval: &ZoomInterval, zoom: u8, tile_bitmask: Option<u16>) -> Tile {
+        let zoom_table: Vec<(usize, usize)> = self
+            .zoom_table
+            .iter()
+            .map(|&(pois, ways)| (pois as usize, ways as usize))
+            .collect();
+        let (pois, ways) = blocks::features_on_zoom_level(&zoom_table, interval, zoom);
+
+        Tile {
+            debug_signature: self.debug_signature.clone(),
+            zoom_table: self.zoom_table.clone(),
+            first_way_offset: self.first_way_offset,
+            pois: self.pois[..pois.min(self.pois.len())].to_vec(),
+            ways: self.ways[..ways.min(self.ways.len())]
+                .iter()
+                .filter(|way| tile_bitmask.is_none_or(|mask| way.sub_tile_bitmap & mask != 0))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+fn poi_size(poi: &POI) -> usize {
+    size_of::<POI>()
+        + poi.debug_signature.as_ref().map_or(0, String::len)
+        + poi.tag_ids.len() * size_of::<usize>()
+        + poi.tags.as_ref().map_or(0, |tags| tags.iter().map(tag_size).sum())
+        + poi.name.as_ref().map_or(0, String::len)
+        + poi.house_number.as_ref().map_or(0, String::len)
+}
+
+fn way_size(way: &Way) -> usize {
+    size_of::<Way>()
+        + way.debug_signature.as_ref().map_or(0, String::len)
+        + way.tag_ids.len() * size_of::<usize>()
+        + way.tags.as_ref().map_or(0, |tags| tags.iter().map(tag_size).sum())
+        + way.name.as_ref().map_or(0, String::len)
+        + way.house_number.as_ref().map_or(0, String::len)
+        + way.reference.as_ref().map_or(0, String::len)
+        + way
+            .coordinate_blocks
+            .iter()
+            .flatten()
+            .map(|block| size_of::<WayCoordinateBlock>() + block.coordinates.len() * size_of::<LatLong>())
+            .sum::<usize>()
+}
+
+fn tag_size(tag: &Tag) -> usize {
+    size_of::<Tag>() + tag.key.len() + tag.value.len()
+}
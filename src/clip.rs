@@ -0,0 +1,242 @@
+use crate::types::{BoundingBox, LatLong, Way, WayCoordinateBlock};
+
+// well below the microdegrees nodes are stored in
+const RING_TOLERANCE: f64 = 1e-7;
+
+// one side of a bounding box, the inside is towards the box
+#[derive(Debug, Clone, Copy)]
+enum Edge {
+    MinLat(f64),
+    MaxLat(f64),
+    MinLon(f64),
+    MaxLon(f64),
+}
+
+impl Edge {
+    fn inside(self, node: LatLong) -> bool {
+        match self {
+            Edge::MinLat(lat) => node.latitude >= lat,
+            Edge::MaxLat(lat) => node.latitude <= lat,
+            Edge::MinLon(lon) => node.longitude >= lon,
+            Edge::MaxLon(lon) => node.longitude <= lon,
+        }
+    }
+
+    // where the segment from a to b crosses the edge, only called for segments
+    // with one node on each side
+    fn intersection(self, a: LatLong, b: LatLong) -> LatLong {
+        match self {
+            Edge::MinLat(lat) | Edge::MaxLat(lat) => {
+                let t = (lat - a.latitude) / (b.latitude - a.latitude);
+                LatLong {
+                    latitude: lat,
+                    longitude: a.longitude + t * (b.longitude - a.longitude),
+                }
+            }
+            Edge::MinLon(lon) | Edge::MaxLon(lon) => {
+                let t = (lon - a.longitude) / (b.longitude - a.longitude);
+                LatLong {
+                    latitude: a.latitude + t * (b.latitude - a.latitude),
+                    longitude: lon,
+                }
+            }
+        }
+    }
+}
+
+// a data block is a polygon if its first coordinate block is a closed ring
+pub(crate) fn is_closed(nodes: &[LatLong]) -> bool {
+    match (nodes.first(), nodes.last()) {
+        (Some(first), Some(last)) => nodes.len() > 3 && same_position(first, last),
+        _ => false,
+    }
+}
+
+// the ring with its last node exactly on its first one, a node is added if
+// the ring is open
+pub(crate) fn close_ring(nodes: &[LatLong]) -> Vec<LatLong> {
+    let mut ring = nodes.to_vec();
+    match (nodes.first(), nodes.last()) {
+        (Some(&first), Some(last)) if nodes.len() > 1 && same_position(&first, last) => *ring.last_mut().unwrap() = first,
+        (Some(&first), Some(_)) => ring.push(first),
+        _ => {}
+    }
+
+    ring
+}
+
+// decoded nodes are sums of microdegree deltas, the closing node of a ring may
+// be off from the first one by rounding errors
+fn same_position(a: &LatLong, b: &LatLong) -> bool {
+    (a.latitude - b.latitude).abs() < RING_TOLERANCE && (a.longitude - b.longitude).abs() < RING_TOLERANCE
+}
+
+// the part of the way inside the bounding box, one data block per part: data
+// blocks that are polygons are clipped together with their inner rings, the
+// coordinate blocks of any other data block as lines that may fall apart,
+// None if nothing of the way is inside
+pub(crate) fn clip_way(way: &Way, bbox: &BoundingBox) -> Option<Way> {
+    let mut data_blocks = vec![];
+
+    for blocks in &way.coordinate_blocks {
+        let Some(outer) = blocks.first() else {
+            continue;
+        };
+
+        if is_closed(&outer.coordinates) {
+            let outer = clip_ring(&outer.coordinates, bbox);
+            if outer.is_empty() {
+                continue;
+            }
+
+            let inner = blocks[1..]
+                .iter()
+                .map(|block| clip_ring(&block.coordinates, bbox))
+                .filter(|ring| !ring.is_empty());
+
+            data_blocks.push(std::iter::once(outer).chain(inner).map(WayCoordinateBlock::new).collect());
+            continue;
+        }
+
+        for block in blocks {
+            data_blocks.extend(clip_line(&block.coordinates, bbox).into_iter().map(|line| vec![WayCoordinateBlock::new(line)]));
+        }
+    }
+
+    (!data_blocks.is_empty()).then(|| piece(way, data_blocks))
+}
+
+// the parts of a polyline inside the bounding box
+pub(crate) fn clip_line(nodes: &[LatLong], bbox: &BoundingBox) -> Vec<Vec<LatLong>> {
+    let mut lines = vec![];
+    let mut line: Vec<LatLong> = vec![];
+
+    for segment in nodes.windows(2) {
+        let Some((start, end, entered, left)) = clip_segment(segment[0], segment[1], bbox) else {
+            finish_line(&mut lines, &mut line);
+            continue;
+        };
+
+        if entered || line.is_empty() {
+            finish_line(&mut lines, &mut line);
+            line.push(start);
+        }
+        line.push(end);
+        if left {
+            finish_line(&mut lines, &mut line);
+        }
+    }
+    finish_line(&mut lines, &mut line);
+
+    lines
+}
+
+// clips a closed ring with Sutherland-Hodgman, the result is closed again or
+// empty if nothing of the ring is inside the bounding box
+pub(crate) fn clip_ring(ring: &[LatLong], bbox: &BoundingBox) -> Vec<LatLong> {
+    let edges = [
+        Edge::MinLat(bbox.min_lat),
+        Edge::MaxLat(bbox.max_lat),
+        Edge::MinLon(bbox.min_lon),
+        Edge::MaxLon(bbox.max_lon),
+    ];
+
+    // the ring without its closing node
+    let mut nodes = ring[..ring.len().saturating_sub(1)].to_vec();
+    for edge in edges {
+        let Some(&last) = nodes.last() else {
+            break;
+        };
+
+        let mut clipped = Vec::with_capacity(nodes.len());
+        let mut previous = last;
+        for &node in &nodes {
+            match (edge.inside(previous), edge.inside(node)) {
+                (true, true) => clipped.push(node),
+                (true, false) => clipped.push(edge.intersection(previous, node)),
+                (false, true) => {
+                    clipped.push(edge.intersection(previous, node));
+                    clipped.push(node);
+                }
+                (false, false) => {}
+            }
+            previous = node;
+        }
+        nodes = clipped;
+    }
+
+    if nodes.len() < 3 {
+        return vec![];
+    }
+    nodes.push(nodes[0]);
+
+    nodes
+}
+
+// Liang-Barsky, the part of the segment inside the bounding box and whether
+// the segment enters or leaves the box on the way
+fn clip_segment(a: LatLong, b: LatLong, bbox: &BoundingBox) -> Option<(LatLong, LatLong, bool, bool)> {
+    let d_lat = b.latitude - a.latitude;
+    let d_lon = b.longitude - a.longitude;
+
+    let mut t_start: f64 = 0.0;
+    let mut t_end: f64 = 1.0;
+    let boundaries = [
+        (-d_lon, a.longitude - bbox.min_lon),
+        (d_lon, bbox.max_lon - a.longitude),
+        (-d_lat, a.latitude - bbox.min_lat),
+        (d_lat, bbox.max_lat - a.latitude),
+    ];
+
+    for (p, q) in boundaries {
+        if p == 0.0 {
+            // parallel to the boundary and outside of it
+            if q < 0.0 {
+                return None;
+            }
+            continue;
+        }
+
+        let t = q / p;
+        if p < 0.0 {
+            t_start = t_start.max(t);
+        } else {
+            t_end = t_end.min(t);
+        }
+    }
+
+    if t_start > t_end {
+        return None;
+    }
+
+    let at = |t: f64| LatLong {
+        latitude: a.latitude + t * d_lat,
+        longitude: a.longitude + t * d_lon,
+    };
+
+    Some((at(t_start), at(t_end), t_start > 0.0, t_end < 1.0))
+}
+
+fn finish_line(lines: &mut Vec<Vec<LatLong>>, line: &mut Vec<LatLong>) {
+    let line = std::mem::take(line);
+    if line.len() > 1 {
+        lines.push(line);
+    }
+}
+
+// a copy of the way with other data blocks
+fn piece(way: &Way, coordinate_blocks: Vec<Vec<WayCoordinateBlock>>) -> Way {
+    Way {
+        debug_signature: None,
+        sub_tile_bitmap: 0,
+        layer: way.layer,
+        tag_ids: way.tag_ids.clone(),
+        tags: way.tags.clone(),
+        name: way.name.clone(),
+        house_number: way.house_number.clone(),
+        reference: way.reference.clone(),
+        label_position: way.label_position,
+        coordinate_blocks,
+        double_delta_encoding: way.double_delta_encoding,
+    }
+}
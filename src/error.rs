@@ -20,4 +20,61 @@ pub enum MapforgeError {
 
     #[error("Invalid tile index signature")]
     InvalidIndexSignature,
+
+    #[error("No zoom interval covers zoom level {0}")]
+    InvalidZoomLevel(u8),
+
+    #[error("Invalid zoom interval with base zoom level {0}, minimum zoom level {1} and maximum zoom level {2}")]
+    InvalidZoomInterval(u8, u8, u8),
+
+    #[error("The tiles of the bounding box on zoom level {0} don't fit into a tile index")]
+    TooManyTiles(u8),
+
+    #[error("Tile {1}/{2} at zoom level {0} is outside of the map")]
+    TileOutOfBounds(u8, u32, u32),
+
+    #[error("The map has no zoom interval {0}")]
+    InvalidZoomIntervalIndex(usize),
+
+    #[error("The tile index of zoom interval {0} has no entry {1}")]
+    InvalidTileIndexEntry(usize, usize),
+
+    #[error("Invalid tile signature in tile {0}/{1}: '{2}'")]
+    InvalidTileSignature(u32, u32, String),
+
+    #[error("Invalid signature of POI {2} in tile {0}/{1}: '{3}'")]
+    InvalidPoiSignature(u32, u32, usize, String),
+
+    #[error("Invalid signature of way {2} in tile {0}/{1}: '{3}'")]
+    InvalidWaySignature(u32, u32, usize, String),
+
+    #[error("Invalid tag id {0} at byte offset {1}")]
+    InvalidTagId(usize, u64),
+
+    #[error("Variable byte encoded number doesn't fit into 64 bits")]
+    VbeNumberTooLarge,
+
+    #[error("Invalid UTF-8 string at byte offset {0}")]
+    InvalidUtf8(u64),
+
+    #[error("Malformed tag '{0}' referenced at byte offset {1}, expected key=value")]
+    MalformedTag(String, u64),
+
+    #[error("Header flags announce the {0}, but it is missing")]
+    MissingHeaderField(String),
+
+    #[error("{0} tags don't fit into a tag table of at most 65535 tags")]
+    TooManyTags(usize),
+
+    #[error("{0} zoom intervals don't fit into a header of at most 255 zoom intervals")]
+    TooManyZoomIntervals(usize),
+
+    #[error("{0} tags don't fit into a POI or way of at most 15 tags")]
+    TooManyFeatureTags(usize),
+
+    #[error("Layer {0} doesn't fit into a POI or way, layers are 0 to 15")]
+    InvalidLayer(i8),
+
+    #[error("Invalid OSM PBF data: {0}")]
+    InvalidPbf(String),
 }
@@ -0,0 +1,91 @@
+use serde_json::{json, Map, Value};
+
+use crate::{
+    properties::{poi_properties, way_properties, PropertyValue},
+    types::{Geometry, LatLong, QueryResult, Tile, Way, POI},
+};
+
+impl Geometry {
+    // GeoJSON positions are longitude first
+    pub fn to_geojson(&self) -> Value {
+        match self {
+            Geometry::Point(position) => json!({ "type": "Point", "coordinates": geojson_position(position) }),
+            Geometry::LineString(line) => json!({ "type": "LineString", "coordinates": geojson_positions(line) }),
+            Geometry::MultiLineString(lines) => json!({
+                "type": "MultiLineString",
+                "coordinates": lines.iter().map(|line| geojson_positions(line)).collect::<Vec<_>>(),
+            }),
+            Geometry::Polygon(rings) => json!({ "type": "Polygon", "coordinates": geojson_rings(rings) }),
+            Geometry::MultiPolygon(polygons) => json!({
+                "type": "MultiPolygon",
+                "coordinates": polygons.iter().map(|rings| geojson_rings(rings)).collect::<Vec<_>>(),
+            }),
+        }
+    }
+}
+
+impl POI {
+    // a Point feature, its tags and fields become the properties
+    pub fn to_geojson(&self) -> Value {
+        feature(self.geometry(), poi_properties(self))
+    }
+}
+
+impl Way {
+    // a LineString, MultiLineString, Polygon or MultiPolygon feature, its tags
+    // and fields become the properties
+    pub fn to_geojson(&self) -> Value {
+        feature(self.geometry(), way_properties(self))
+    }
+}
+
+impl Tile {
+    // a FeatureCollection of the POIs followed by the ways of the tile
+    pub fn to_geojson(&self) -> Value {
+        feature_collection(&self.pois, &self.ways)
+    }
+}
+
+impl QueryResult {
+    pub fn to_geojson(&self) -> Value {
+        feature_collection(&self.pois, &self.ways)
+    }
+}
+
+fn feature(geometry: Geometry, properties: Vec<(String, PropertyValue)>) -> Value {
+    let properties: Map<String, Value> = properties
+        .into_iter()
+        .map(|(key, value)| match value {
+            PropertyValue::String(value) => (key, value.into()),
+            PropertyValue::Integer(value) => (key, value.into()),
+        })
+        .collect();
+
+    json!({
+        "type": "Feature",
+        "geometry": geometry.to_geojson(),
+        "properties": properties,
+    })
+}
+
+fn feature_collection(pois: &[POI], ways: &[Way]) -> Value {
+    let features: Vec<Value> = pois
+        .iter()
+        .map(POI::to_geojson)
+        .chain(ways.iter().map(Way::to_geojson))
+        .collect();
+
+    json!({ "type": "FeatureCollection", "features": features })
+}
+
+fn geojson_position(position: &LatLong) -> Value {
+    json!([position.longitude, position.latitude])
+}
+
+fn geojson_positions(positions: &[LatLong]) -> Vec<Value> {
+    positions.iter().map(geojson_position).collect()
+}
+
+fn geojson_rings(rings: &[Vec<LatLong>]) -> Vec<Vec<Value>> {
+    rings.iter().map(|ring| geojson_positions(ring)).collect()
+}
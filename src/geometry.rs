@@ -0,0 +1,47 @@
+use crate::{
+    clip,
+    types::{Geometry, LatLong, Way, WayCoordinateBlock, POI},
+};
+
+impl POI {
+    pub fn geometry(&self) -> Geometry {
+        Geometry::Point(self.position)
+    }
+}
+
+impl Way {
+    // every way data block is a polygon if its first coordinate block is a
+    // closed ring, the following ones being its holes, ways made of polygons
+    // only are a Polygon or MultiPolygon, any other way is a LineString or
+    // MultiLineString of all of its coordinate blocks
+    pub fn geometry(&self) -> Geometry {
+        let is_polygon = |blocks: &Vec<WayCoordinateBlock>| blocks.first().is_some_and(|outer| clip::is_closed(&outer.coordinates));
+
+        if self.coordinate_blocks.is_empty() || !self.coordinate_blocks.iter().all(is_polygon) {
+            let mut lines: Vec<Vec<LatLong>> = self
+                .coordinate_blocks
+                .iter()
+                .flatten()
+                .map(|block| block.coordinates.clone())
+                .collect();
+
+            return match lines.len() {
+                0 => Geometry::LineString(vec![]),
+                1 => Geometry::LineString(lines.remove(0)),
+                _ => Geometry::MultiLineString(lines),
+            };
+        }
+
+        let mut polygons: Vec<Vec<Vec<LatLong>>> = self
+            .coordinate_blocks
+            .iter()
+            .map(|blocks| blocks.iter().map(|block| clip::close_ring(&block.coordinates)).collect())
+            .collect();
+
+        if polygons.len() == 1 {
+            Geometry::Polygon(polygons.remove(0))
+        } else {
+            Geometry::MultiPolygon(polygons)
+        }
+    }
+}
@@ -1,26 +1,30 @@
 use crate::{
-    error::MapforgeError, types::{BoundingBox, LatLong, MapHeader, ZoomInterval}, utils, Result
+    error::MapforgeError, tile::MAX_ZOOM_LEVEL, types::{BoundingBox, LatLong, MapHeader, ZoomInterval}, utils, Result
 };
-use byteorder::{BigEndian, ReadBytesExt};
-use std::io::{BufReader, Read};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use log::debug;
+use std::io::{self, Read, Seek, SeekFrom, Write};
 
 // masgic bytes that identify a valid Mapsforge binary map file
-const MAGIC_BYTES: &str = "mapsforge binary OSM";
+pub(crate) const MAGIC_BYTES: &str = "mapsforge binary OSM";
 
 const MIN_SUPPORTED_VERSION: u32 = 3;
 
+// magic bytes and the header size field in front of the rest of the header
+pub(crate) const HEADER_PREFIX_SIZE: u64 = 24;
+
 // mask
 pub const DEBUG_INFO_MASK: u8 = 0x80;
-const MAP_START_POSITION_MASK: u8 = 0x40;
-const START_ZOOM_LEVEL_MASK: u8 = 0x20;
-const LANGUAGE_PREFERENCE_MASK: u8 = 0x10;
-const COMMENT_MASK: u8 = 0x08;
-const CREATED_BY_MASK: u8 = 0x04;
+pub const MAP_START_POSITION_MASK: u8 = 0x40;
+pub const START_ZOOM_LEVEL_MASK: u8 = 0x20;
+pub const LANGUAGE_PREFERENCE_MASK: u8 = 0x10;
+pub const COMMENT_MASK: u8 = 0x08;
+pub const CREATED_BY_MASK: u8 = 0x04;
 
 impl BoundingBox {
     // reads a bounding box from a binary buffer
     // using generic so that it can work with any type that implements Read
-    pub fn read_from_buffer<R: Read>(reader: &mut BufReader<R>) -> Result<Self> {
+    pub fn read_from_buffer<R: Read>(reader: &mut R) -> Result<Self> {
         // read and convert coordinates from microdegrees to degrees
         // divide by 1_000_000 to get degrees as all latitude and longitude coordinates are stored in microdegrees (degrees × 10^6)
         let min_lat = reader.read_i32::<BigEndian>()? as f64 / 1_000_000.0;
@@ -42,6 +46,36 @@ impl BoundingBox {
         Ok(bbox)
     }
 
+    // writes the bounding box in microdegrees, the mirror of read_from_buffer
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        for degrees in [self.min_lat, self.min_lon, self.max_lat, self.max_lon] {
+            writer.write_i32::<BigEndian>(utils::degrees_to_microdegrees(degrees) as i32)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn contains(&self, position: &LatLong) -> bool {
+        self.min_lat <= position.latitude
+            && position.latitude <= self.max_lat
+            && self.min_lon <= position.longitude
+            && position.longitude <= self.max_lon
+    }
+
+    pub fn contains_box(&self, other: &BoundingBox) -> bool {
+        self.min_lat <= other.min_lat
+            && other.max_lat <= self.max_lat
+            && self.min_lon <= other.min_lon
+            && other.max_lon <= self.max_lon
+    }
+
+    pub fn intersects(&self, other: &BoundingBox) -> bool {
+        self.min_lat <= other.max_lat
+            && other.min_lat <= self.max_lat
+            && self.min_lon <= other.max_lon
+            && other.min_lon <= self.max_lon
+    }
+
     // validate if bounding box coordinates are within valid ranges:
     // lat must be between -90 and 90 degree
     // log must be between -180 and 180 degree
@@ -62,7 +96,7 @@ impl BoundingBox {
 
 impl MapHeader {
     // reads a map header from binary buffer
-    pub fn read_from_file<R: Read>(reader: &mut BufReader<R>) -> Result<Self> {
+    pub fn read_from_file<R: Read + Seek>(reader: &mut R) -> Result<Self> {
         let mut magic_buf = [0u8; 20];
         reader.read_exact(&mut magic_buf)?;
         let magic = String::from_utf8_lossy(&magic_buf).trim().to_string();
@@ -85,60 +119,52 @@ impl MapHeader {
 
         let tile_size = reader.read_u16::<BigEndian>()?;
 
-        let projection = utils::read_vbe_u(reader)?;
+        let projection = utils::read_string(reader)?;
 
         let flags = reader.read_u8()?;
 
         if flags & DEBUG_INFO_MASK != 0 {
-            println!("DEBUG INFO EXIST");
+            debug!("map file contains debug information");
         }
 
-        let map_start_position: Option<LatLong>;
-
-        if flags & MAP_START_POSITION_MASK != 0 {
+        let map_start_position = if flags & MAP_START_POSITION_MASK != 0 {
             let latitude = reader.read_i32::<BigEndian>()? as f64 / 1_000_000.0;
             let longitude = reader.read_i32::<BigEndian>()? as f64 / 1_000_000.0;
-            map_start_position = Some(LatLong{latitude, longitude});
-        }else{ 
-            map_start_position = None
-        }
-
-        let start_zoom_level: Option<u8>;
-
-        if flags & START_ZOOM_LEVEL_MASK != 0 {
-            start_zoom_level = Some(reader.read_u8()?);
+            Some(LatLong{latitude, longitude})
         } else {
-            start_zoom_level = None
-        }
+            None
+        };
 
-        let language_preference: Option<String>;
+        let start_zoom_level = if flags & START_ZOOM_LEVEL_MASK != 0 {
+            Some(reader.read_u8()?)
+        } else {
+            None
+        };
 
-        if flags & LANGUAGE_PREFERENCE_MASK != 0 {
-            language_preference = Some(utils::read_vbe_u(reader)?);
+        let language_preference = if flags & LANGUAGE_PREFERENCE_MASK != 0 {
+            Some(utils::read_string(reader)?)
         } else {
-            language_preference = None;
-        }
+            None
+        };
 
-        let comment: Option<String>;
-        if flags & COMMENT_MASK != 0 {
-            comment = Some(utils::read_vbe_u(reader)?)
-        }else {
-            comment = None
-        }
+        let comment = if flags & COMMENT_MASK != 0 {
+            Some(utils::read_string(reader)?)
+        } else {
+            None
+        };
 
-        let created_by: Option<String>;
-        if flags & CREATED_BY_MASK != 0 {
-            created_by = Some(utils::read_vbe_u(reader)?);
-        }else{
-            created_by = None
-        }
+        let created_by = if flags & CREATED_BY_MASK != 0 {
+            Some(utils::read_string(reader)?)
+        } else {
+            None
+        };
 
 
         let num_poi_tags = reader.read_u16::<BigEndian>()?;
         let mut poi_tags: Vec<String> = vec![];
 
         for _ in 0..num_poi_tags {
-            let tag = utils::read_vbe_u(reader)?;
+            let tag = utils::read_string(reader)?;
             poi_tags.push(tag);
         }
 
@@ -146,7 +172,7 @@ impl MapHeader {
         let mut way_tags: Vec<String> = vec![];
 
         for _ in 0..num_way_tags {
-            let tag = utils::read_vbe_u(reader)?;
+            let tag = utils::read_string(reader)?;
             way_tags.push(tag);
         }
 
@@ -162,8 +188,10 @@ impl MapHeader {
             let sub_file_start = reader.read_u64::<BigEndian>()?;
             let sub_file_size = reader.read_u64::<BigEndian>()?;
 
-
-            
+            // tile numbers on deeper zoom levels don't fit into u32
+            if !(min_zoom_level <= base_zoom_level && base_zoom_level <= max_zoom_level && max_zoom_level <= MAX_ZOOM_LEVEL) {
+                return Err(MapforgeError::InvalidZoomInterval(base_zoom_level, min_zoom_level, max_zoom_level));
+            }
 
             zoom_interval_configuration.push(ZoomInterval{
                 base_zoom_level,
@@ -206,9 +234,112 @@ impl MapHeader {
         Ok(header)
     }
 
+    // writes the header in the binary format, the mirror of read_from_file
+    // the header size is computed from the fields, the stored one is ignored
+    // optional fields are written if their bit in flags is set
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        write_header(writer, &self.fields()?)
+    }
+
+    // copies the map with this header in place of its own one, e.g. to patch
+    // the comment of a map. A header that got smaller is padded to the size of
+    // the original one, for a larger one the sub-files and the file size move
+    // by the difference. The header has to be read from the same map, as the
+    // sub-file offsets are taken from it.
+    pub fn rewrite<R: Read + Seek, W: Write>(&self, map: &mut R, writer: &mut W) -> Result<()> {
+        map.rewind()?;
+        let original_size = MapHeader::read_from_file(map)?.header_size as u64;
+
+        // the offsets have a fixed size, moving them doesn't change the header size
+        let size = self.fields()?.len() as u64;
+        let shift = size.saturating_sub(original_size);
+
+        let mut header = self.clone();
+        header.file_size += shift;
+        for interval in &mut header.zoom_interval_configuration {
+            interval.sub_file_start += shift;
+        }
+
+        let mut fields = header.fields()?;
+        fields.resize(size.max(original_size) as usize, 0);
+        write_header(writer, &fields)?;
+
+        map.seek(SeekFrom::Start(HEADER_PREFIX_SIZE + original_size))?;
+        io::copy(map, writer)?;
+
+        Ok(())
+    }
+
+    // everything after the header size field
+    fn fields(&self) -> Result<Vec<u8>> {
+        let mut header: Vec<u8> = vec![];
+
+        header.write_u32::<BigEndian>(self.file_version)?;
+        header.write_u64::<BigEndian>(self.file_size)?;
+        header.write_u64::<BigEndian>(self.creation_date)?;
+        self.bounding_box.write_to(&mut header)?;
+        header.write_u16::<BigEndian>(self.tile_size)?;
+        utils::write_string(&mut header, &self.projection)?;
+        header.write_u8(self.flags)?;
+
+        if self.flags & MAP_START_POSITION_MASK != 0 {
+            let position = self.map_start_position.ok_or_else(|| missing("map start position"))?;
+            header.write_i32::<BigEndian>(utils::degrees_to_microdegrees(position.latitude) as i32)?;
+            header.write_i32::<BigEndian>(utils::degrees_to_microdegrees(position.longitude) as i32)?;
+        }
+
+        if self.flags & START_ZOOM_LEVEL_MASK != 0 {
+            header.write_u8(self.start_zoom_level.ok_or_else(|| missing("start zoom level"))?)?;
+        }
+
+        let optional_strings = [
+            (LANGUAGE_PREFERENCE_MASK, &self.language_preference, "language preference"),
+            (COMMENT_MASK, &self.comment, "comment"),
+            (CREATED_BY_MASK, &self.created_by, "created by"),
+        ];
+        for (mask, value, name) in optional_strings {
+            if self.flags & mask != 0 {
+                utils::write_string(&mut header, value.as_deref().ok_or_else(|| missing(name))?)?;
+            }
+        }
+
+        for tags in [&self.poi_tags, &self.way_tags] {
+            let count = u16::try_from(tags.len()).map_err(|_| MapforgeError::TooManyTags(tags.len()))?;
+            header.write_u16::<BigEndian>(count)?;
+            for tag in tags {
+                utils::write_string(&mut header, tag)?;
+            }
+        }
+
+        let intervals = self.zoom_interval_configuration.len();
+        header.write_u8(u8::try_from(intervals).map_err(|_| MapforgeError::TooManyZoomIntervals(intervals))?)?;
+        for interval in &self.zoom_interval_configuration {
+            header.write_u8(interval.base_zoom_level)?;
+            header.write_u8(interval.min_zoom_level)?;
+            header.write_u8(interval.max_zoom_level)?;
+            header.write_u64::<BigEndian>(interval.sub_file_start)?;
+            header.write_u64::<BigEndian>(interval.sub_file_size)?;
+        }
+
+        Ok(header)
+    }
+
     pub fn is_valid(&self) -> bool {
         self.magic.trim() == MAGIC_BYTES
             && self.header_size > 0
             && self.file_version >= MIN_SUPPORTED_VERSION
     }
 }
+
+// the magic bytes and the header size in front of the header fields
+fn write_header<W: Write>(writer: &mut W, fields: &[u8]) -> Result<()> {
+    writer.write_all(MAGIC_BYTES.as_bytes())?;
+    writer.write_u32::<BigEndian>(fields.len() as u32)?;
+    writer.write_all(fields)?;
+
+    Ok(())
+}
+
+fn missing(field: &str) -> MapforgeError {
+    MapforgeError::MissingHeaderField(field.to_string())
+}
@@ -7,6 +7,25 @@ pub mod tag;
 pub mod blocks;
 pub mod poi;
 pub mod way;
+pub mod slice;
+pub mod cache;
+pub mod shared;
+pub mod writer;
+pub mod geometry;
+pub mod wkt;
+mod clip;
+#[cfg(feature = "async")]
+pub mod async_reader;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+#[cfg(feature = "osm")]
+pub mod osm;
+#[cfg(feature = "geojson")]
+pub mod geojson;
+#[cfg(feature = "mvt")]
+pub mod mvt;
+#[cfg(any(feature = "geojson", feature = "mvt"))]
+mod properties;
 
 pub use error::MapforgeError;
 pub use types::{BoundingBox, MapHeader};
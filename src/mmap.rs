@@ -0,0 +1,21 @@
+use std::{fs::File, io::Cursor, path::Path};
+
+use memmap2::Mmap;
+
+use crate::{types::MapFile, Result};
+
+// a map file read through a memory map, seeking and reading only copy from the
+// mapped pages instead of going through read calls
+pub type MmapMapFile = MapFile<Cursor<Mmap>>;
+
+impl MapFile<Cursor<Mmap>> {
+    pub fn open_mmap(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(path)?;
+
+        // SAFETY: the map is only read, but the file must not be truncated or
+        // modified by another process while it is mapped
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        Self::from_reader(Cursor::new(mmap))
+    }
+}
@@ -0,0 +1,412 @@
+use std::{
+    collections::HashMap,
+    f64::consts::PI,
+    io::{Read, Seek},
+};
+
+use crate::{
+    clip,
+    properties::{poi_properties, way_properties, PropertyValue},
+    types::{BoundingBox, Geometry, LatLong, MapFile, MvtEncoder, MvtLayerRule, QueryResult, Tag, Tile, Way, POI},
+    Result,
+};
+
+const MVT_VERSION: u64 = 2;
+const DEFAULT_EXTENT: u32 = 4096;
+const DEFAULT_BUFFER: u32 = 64;
+
+const GEOMETRY_POINT: u64 = 1;
+const GEOMETRY_LINESTRING: u64 = 2;
+const GEOMETRY_POLYGON: u64 = 3;
+
+const COMMAND_MOVE_TO: u32 = 1;
+const COMMAND_LINE_TO: u32 = 2;
+const COMMAND_CLOSE_PATH: u32 = 7;
+
+// field numbers of the vector tile protobuf schema
+const TILE_LAYERS: u64 = 3;
+const LAYER_NAME: u64 = 1;
+const LAYER_FEATURES: u64 = 2;
+const LAYER_KEYS: u64 = 3;
+const LAYER_VALUES: u64 = 4;
+const LAYER_EXTENT: u64 = 5;
+const LAYER_VERSION: u64 = 15;
+const FEATURE_TAGS: u64 = 2;
+const FEATURE_TYPE: u64 = 3;
+const FEATURE_GEOMETRY: u64 = 4;
+const VALUE_STRING: u64 = 1;
+const VALUE_SINT: u64 = 6;
+
+impl MvtLayerRule {
+    pub fn new(key: &str, value: Option<&str>, layer: &str) -> Self {
+        MvtLayerRule {
+            key: key.to_string(),
+            value: value.map(str::to_string),
+            layer: layer.to_string(),
+        }
+    }
+
+    fn matches(&self, tags: &[Tag]) -> bool {
+        tags.iter()
+            .any(|tag| tag.key == self.key && self.value.as_ref().is_none_or(|value| *value == tag.value))
+    }
+}
+
+impl MvtEncoder {
+    pub fn new(rules: Vec<MvtLayerRule>) -> Self {
+        MvtEncoder {
+            rules,
+            default_layer: None,
+            extent: DEFAULT_EXTENT,
+            buffer: DEFAULT_BUFFER,
+        }
+    }
+
+    pub fn encode_tile(&self, tile: &Tile, zoom: u8, x: u32, y: u32) -> Vec<u8> {
+        self.encode(&tile.pois, &tile.ways, zoom, x, y)
+    }
+
+    pub fn encode_query(&self, result: &QueryResult, zoom: u8, x: u32, y: u32) -> Vec<u8> {
+        self.encode(&result.pois, &result.ways, zoom, x, y)
+    }
+
+    // encodes the features as vector tile zoom/x/y, geometries are projected to
+    // web mercator and clipped to the tile and its buffer, features outside of
+    // it or in no layer are left out
+    pub fn encode(&self, pois: &[POI], ways: &[Way], zoom: u8, x: u32, y: u32) -> Vec<u8> {
+        let features = pois
+            .iter()
+            .map(|poi| (poi.geometry(), poi.tags.as_deref(), poi_properties(poi)))
+            .chain(ways.iter().map(|way| (way.geometry(), way.tags.as_deref(), way_properties(way))));
+
+        let mut layers: Vec<LayerBuilder> = vec![];
+        for (geometry, tags, properties) in features {
+            let Some(name) = self.layer_name(tags.unwrap_or_default()) else {
+                continue;
+            };
+            let Some((geometry_type, commands)) = self.encode_geometry(&geometry, zoom, x, y) else {
+                continue;
+            };
+
+            let index = match layers.iter().position(|layer| layer.name == name) {
+                Some(index) => index,
+                None => {
+                    layers.push(LayerBuilder::new(name));
+                    layers.len() - 1
+                }
+            };
+            layers[index].add_feature(geometry_type, &commands, properties);
+        }
+
+        let mut tile = Message::default();
+        for layer in layers {
+            tile.message(TILE_LAYERS, layer.encode(self.extent));
+        }
+
+        tile.0
+    }
+
+    // the area of tile zoom/x/y grown by the buffer
+    pub fn buffered_bounds(&self, zoom: u8, x: u32, y: u32) -> BoundingBox {
+        let max_lat = MapFile::tiley2lat(y, zoom);
+        let min_lat = MapFile::tiley2lat(y + 1, zoom);
+        let min_lon = MapFile::tilex2long(x, zoom);
+        let max_lon = MapFile::tilex2long(x + 1, zoom);
+        let share = self.buffer as f64 / self.extent as f64;
+
+        BoundingBox {
+            min_lat: (min_lat - (max_lat - min_lat) * share).max(-90.0),
+            min_lon: (min_lon - (max_lon - min_lon) * share).max(-180.0),
+            max_lat: (max_lat + (max_lat - min_lat) * share).min(90.0),
+            max_lon: (max_lon + (max_lon - min_lon) * share).min(180.0),
+        }
+    }
+
+    fn layer_name(&self, tags: &[Tag]) -> Option<&str> {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(tags))
+            .map(|rule| rule.layer.as_str())
+            .or(self.default_layer.as_deref())
+    }
+
+    // the geometry type and command integers of the geometry, None if nothing
+    // of it is left after clipping
+    fn encode_geometry(&self, geometry: &Geometry, zoom: u8, x: u32, y: u32) -> Option<(u64, Vec<u32>)> {
+        // the clip functions work on any plane, tile coordinates are put into
+        // the latitude as y and the longitude as x
+        let project = |positions: &[LatLong]| -> Vec<LatLong> {
+            positions.iter().map(|position| self.project(*position, zoom, x, y)).collect()
+        };
+        let min = -(self.buffer as f64);
+        let max = (self.extent + self.buffer) as f64;
+        let clip_box = BoundingBox {
+            min_lat: min,
+            min_lon: min,
+            max_lat: max,
+            max_lon: max,
+        };
+
+        let mut encoder = GeometryEncoder::default();
+        let geometry_type = match geometry {
+            Geometry::Point(position) => {
+                let point = self.project(*position, zoom, x, y);
+                if !clip_box.contains(&point) {
+                    return None;
+                }
+                encoder.move_to(round(point));
+                GEOMETRY_POINT
+            }
+            Geometry::LineString(line) => {
+                self.encode_lines(&mut encoder, &[project(line)], &clip_box);
+                GEOMETRY_LINESTRING
+            }
+            Geometry::MultiLineString(lines) => {
+                let lines: Vec<_> = lines.iter().map(|line| project(line)).collect();
+                self.encode_lines(&mut encoder, &lines, &clip_box);
+                GEOMETRY_LINESTRING
+            }
+            Geometry::Polygon(rings) => {
+                encode_polygon(&mut encoder, &rings.iter().map(|ring| project(ring)).collect::<Vec<_>>(), &clip_box);
+                GEOMETRY_POLYGON
+            }
+            Geometry::MultiPolygon(polygons) => {
+                for rings in polygons {
+                    encode_polygon(&mut encoder, &rings.iter().map(|ring| project(ring)).collect::<Vec<_>>(), &clip_box);
+                }
+                GEOMETRY_POLYGON
+            }
+        };
+
+        (!encoder.commands.is_empty()).then_some((geometry_type, encoder.commands))
+    }
+
+    fn encode_lines(&self, encoder: &mut GeometryEncoder, lines: &[Vec<LatLong>], clip_box: &BoundingBox) {
+        for line in lines {
+            for piece in clip::clip_line(line, clip_box) {
+                let points = rounded(&piece);
+                if points.len() > 1 {
+                    encoder.move_to(points[0]);
+                    encoder.line_to(&points[1..]);
+                }
+            }
+        }
+    }
+
+    // web mercator position in tile coordinates, y grows downwards
+    fn project(&self, position: LatLong, zoom: u8, x: u32, y: u32) -> LatLong {
+        let tiles = (1u64 << zoom) as f64;
+        let latitude = position.latitude.to_radians();
+        let world_x = (position.longitude + 180.0) / 360.0 * tiles;
+        let world_y = (1.0 - (latitude.tan() + 1.0 / latitude.cos()).ln() / PI) / 2.0 * tiles;
+
+        LatLong {
+            latitude: (world_y - y as f64) * self.extent as f64,
+            longitude: (world_x - x as f64) * self.extent as f64,
+        }
+    }
+}
+
+impl<R: Read + Seek> MapFile<R> {
+    // reads the features of tile zoom/x/y and the buffer around it and encodes
+    // them as vector tile
+    pub fn read_mvt(&mut self, encoder: &MvtEncoder, zoom: u8, x: u32, y: u32) -> Result<Vec<u8>> {
+        let result = self.query(encoder.buffered_bounds(zoom, x, y), zoom)?;
+
+        Ok(encoder.encode_query(&result, zoom, x, y))
+    }
+}
+
+// the outer ring must have a positive area by the surveyor's formula in tile
+// coordinates, the inner rings a negative one, the rings are turned around
+// where they don't, polygons whose outer ring is clipped away are left out
+fn encode_polygon(encoder: &mut GeometryEncoder, rings: &[Vec<LatLong>], clip_box: &BoundingBox) {
+    for (index, ring) in rings.iter().enumerate() {
+        let mut points = rounded(&clip::clip_ring(ring, clip_box));
+        // the closing point is implied by the close path command
+        if points.len() > 1 && points[0] == points[points.len() - 1] {
+            points.pop();
+        }
+
+        let area = signed_area(&points);
+        if points.len() < 3 || area == 0 {
+            if index == 0 {
+                return;
+            }
+            continue;
+        }
+        if (index == 0) != (area > 0) {
+            points.reverse();
+        }
+
+        encoder.move_to(points[0]);
+        encoder.line_to(&points[1..]);
+        encoder.close_path();
+    }
+}
+
+// twice the area of the ring
+fn signed_area(points: &[(i32, i32)]) -> i64 {
+    (0..points.len())
+        .map(|index| {
+            let (x1, y1) = points[index];
+            let (x2, y2) = points[(index + 1) % points.len()];
+            x1 as i64 * y2 as i64 - x2 as i64 * y1 as i64
+        })
+        .sum()
+}
+
+fn round(point: LatLong) -> (i32, i32) {
+    (point.longitude.round() as i32, point.latitude.round() as i32)
+}
+
+// integer tile coordinates without points repeated by rounding
+fn rounded(points: &[LatLong]) -> Vec<(i32, i32)> {
+    let mut rounded: Vec<(i32, i32)> = points.iter().map(|&point| round(point)).collect();
+    rounded.dedup();
+    rounded
+}
+
+// command integers of a feature geometry, positions are relative to the end
+// of the previous command, even across the parts of a feature
+#[derive(Default)]
+struct GeometryEncoder {
+    commands: Vec<u32>,
+    cursor: (i32, i32),
+}
+
+impl GeometryEncoder {
+    fn move_to(&mut self, point: (i32, i32)) {
+        self.commands.push(command(COMMAND_MOVE_TO, 1));
+        self.parameters(&[point]);
+    }
+
+    fn line_to(&mut self, points: &[(i32, i32)]) {
+        self.commands.push(command(COMMAND_LINE_TO, points.len()));
+        self.parameters(points);
+    }
+
+    fn close_path(&mut self) {
+        self.commands.push(command(COMMAND_CLOSE_PATH, 1));
+    }
+
+    fn parameters(&mut self, points: &[(i32, i32)]) {
+        for &(x, y) in points {
+            self.commands.push(zigzag(x - self.cursor.0));
+            self.commands.push(zigzag(y - self.cursor.1));
+            self.cursor = (x, y);
+        }
+    }
+}
+
+fn command(id: u32, count: usize) -> u32 {
+    id | (count as u32) << 3
+}
+
+fn zigzag(value: i32) -> u32 {
+    ((value << 1) ^ (value >> 31)) as u32
+}
+
+// a layer with its key and value tables, features refer to both by index
+struct LayerBuilder {
+    name: String,
+    features: Vec<Message>,
+    keys: Vec<String>,
+    key_indices: HashMap<String, u32>,
+    values: Vec<PropertyValue>,
+    value_indices: HashMap<PropertyValue, u32>,
+}
+
+impl LayerBuilder {
+    fn new(name: &str) -> Self {
+        LayerBuilder {
+            name: name.to_string(),
+            features: vec![],
+            keys: vec![],
+            key_indices: HashMap::new(),
+            values: vec![],
+            value_indices: HashMap::new(),
+        }
+    }
+
+    fn add_feature(&mut self, geometry_type: u64, commands: &[u32], properties: Vec<(String, PropertyValue)>) {
+        let mut tags = Vec::with_capacity(properties.len() * 2);
+        for (key, value) in properties {
+            let key_index = *self.key_indices.entry(key.clone()).or_insert_with(|| {
+                self.keys.push(key);
+                self.keys.len() as u32 - 1
+            });
+            let value_index = *self.value_indices.entry(value.clone()).or_insert_with(|| {
+                self.values.push(value);
+                self.values.len() as u32 - 1
+            });
+            tags.extend([key_index, value_index]);
+        }
+
+        let mut feature = Message::default();
+        feature.packed(FEATURE_TAGS, &tags);
+        feature.varint(FEATURE_TYPE, geometry_type);
+        feature.packed(FEATURE_GEOMETRY, commands);
+        self.features.push(feature);
+    }
+
+    fn encode(self, extent: u32) -> Message {
+        let mut layer = Message::default();
+        layer.varint(LAYER_VERSION, MVT_VERSION);
+        layer.bytes(LAYER_NAME, self.name.as_bytes());
+        for feature in self.features {
+            layer.message(LAYER_FEATURES, feature);
+        }
+        for key in &self.keys {
+            layer.bytes(LAYER_KEYS, key.as_bytes());
+        }
+        for value in self.values {
+            let mut encoded = Message::default();
+            match value {
+                PropertyValue::String(value) => encoded.bytes(VALUE_STRING, value.as_bytes()),
+                PropertyValue::Integer(value) => encoded.varint(VALUE_SINT, ((value << 1) ^ (value >> 63)) as u64),
+            }
+            layer.message(LAYER_VALUES, encoded);
+        }
+        layer.varint(LAYER_EXTENT, extent as u64);
+
+        layer
+    }
+}
+
+// an encoded protobuf message
+#[derive(Default)]
+struct Message(Vec<u8>);
+
+impl Message {
+    fn varint(&mut self, field: u64, value: u64) {
+        self.raw_varint(field << 3);
+        self.raw_varint(value);
+    }
+
+    fn bytes(&mut self, field: u64, bytes: &[u8]) {
+        self.raw_varint(field << 3 | 2);
+        self.raw_varint(bytes.len() as u64);
+        self.0.extend(bytes);
+    }
+
+    fn message(&mut self, field: u64, message: Message) {
+        self.bytes(field, &message.0);
+    }
+
+    fn packed(&mut self, field: u64, values: &[u32]) {
+        let mut packed = Message::default();
+        for &value in values {
+            packed.raw_varint(value as u64);
+        }
+        self.bytes(field, &packed.0);
+    }
+
+    fn raw_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+}
@@ -0,0 +1,294 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
+
+use log::warn;
+
+use crate::{
+    clip,
+    error::MapforgeError,
+    tag::{KEY_VALUE_SEPERATOR, TAG_KEY_HOUSE_NUMBER, TAG_KEY_NAME, TAG_KEY_REF},
+    types::{BoundingBox, LatLong, MapWriter, OsmConverter, Tag, Way, WayCoordinateBlock, ZoomInterval, POI},
+    Result,
+};
+
+pub mod pbf;
+
+use pbf::{MemberType, OsmData, OsmWay};
+
+// base, min and max zoom level of the intervals mapsforge's own writer uses
+const DEFAULT_ZOOM_INTERVALS: [(u8, u8, u8); 3] = [(5, 0, 7), (10, 8, 11), (14, 12, 21)];
+const MAX_FEATURE_TAGS: usize = 15;
+const ANY_VALUE: &str = "*";
+
+const TAG_KEY_LAYER: &str = "layer";
+const TAG_KEY_ELEVATION: &str = "ele";
+const TAG_KEY_TYPE: &str = "type";
+const MULTIPOLYGON: &str = "multipolygon";
+const ROLE_INNER: &str = "inner";
+
+// mapsforge keeps the layer in 4 bits, OSM layers from -5 to 10 are stored
+// shifted to 0 to 15
+const LAYER_OFFSET: i8 = 5;
+const MAX_LAYER: i8 = 10;
+
+impl OsmConverter {
+    pub fn new(poi_tags: HashMap<String, u8>, way_tags: HashMap<String, u8>) -> Self {
+        OsmConverter {
+            poi_tags,
+            way_tags,
+            zoom_intervals: DEFAULT_ZOOM_INTERVALS
+                .iter()
+                .map(|&(base, min, max)| ZoomInterval::new(base, min, max))
+                .collect(),
+            bounding_box: None,
+        }
+    }
+
+    pub fn convert_file(&self, input: impl AsRef<Path>, output: impl AsRef<Path>) -> Result<()> {
+        self.convert(BufReader::new(File::open(input)?))?.write_file(output)
+    }
+
+    // the returned writer can still be changed, e.g. to add header fields,
+    // before it is written
+    pub fn convert<R: Read>(&self, reader: R) -> Result<MapWriter> {
+        self.convert_data(&pbf::read_pbf(reader)?)
+    }
+
+    // tagged nodes become POIs, tagged ways lines or, if they are closed,
+    // areas, multipolygon relations areas with holes, ways are clipped to the
+    // tiles they are written to
+    pub fn convert_data(&self, data: &OsmData) -> Result<MapWriter> {
+        let mut pois = vec![];
+        let mut ways = vec![];
+
+        // sorted by id, so the same extract always gives the same map
+        let mut nodes: Vec<_> = data.nodes.iter().filter(|(_, node)| !node.tags.is_empty()).collect();
+        nodes.sort_by_key(|&(id, _)| *id);
+        for (_, node) in nodes {
+            if let Some((tags, zoom_level)) = whitelisted(&node.tags, &self.poi_tags) {
+                pois.push((poi(&node.tags, tags, node.position), zoom_level));
+            }
+        }
+
+        for osm_way in &data.ways {
+            let Some((tags, zoom_level)) = whitelisted(&osm_way.tags, &self.way_tags) else {
+                continue;
+            };
+
+            let nodes = positions(&osm_way.refs, data);
+            if nodes.len() > 1 {
+                ways.push((way(&osm_way.tags, tags, vec![vec![nodes]]), zoom_level));
+            }
+        }
+
+        let ways_by_id: HashMap<i64, &OsmWay> = data.ways.iter().map(|way| (way.id, way)).collect();
+        for relation in &data.relations {
+            if !has_tag(&relation.tags, TAG_KEY_TYPE, MULTIPOLYGON) {
+                continue;
+            }
+            let relation_tags: Vec<_> = relation.tags.iter().filter(|(key, _)| key != TAG_KEY_TYPE).cloned().collect();
+            let Some((tags, zoom_level)) = whitelisted(&relation_tags, &self.way_tags) else {
+                continue;
+            };
+
+            let (mut outer, mut inner) = (vec![], vec![]);
+            for member in relation.members.iter().filter(|member| member.member_type == MemberType::Way) {
+                let Some(member_way) = ways_by_id.get(&member.id) else {
+                    continue;
+                };
+                if member.role == ROLE_INNER {
+                    inner.push(member_way.refs.as_slice());
+                } else {
+                    outer.push(member_way.refs.as_slice());
+                }
+            }
+
+            let polygons = polygons(&rings(outer, data), &rings(inner, data));
+            if !polygons.is_empty() {
+                ways.push((way(&relation_tags, tags, polygons), zoom_level));
+            }
+        }
+
+        let bounding_box = self
+            .bounding_box
+            .or(data.bounding_box)
+            .or_else(|| features_bounding_box(&pois, &ways))
+            .ok_or(MapforgeError::InvalidBoundingBox)?;
+
+        let mut writer = MapWriter::new(bounding_box, self.zoom_intervals.clone());
+        writer.clip_ways = true;
+        for (poi, zoom_level) in pois {
+            writer.add_poi(poi, zoom_level);
+        }
+        for (way, zoom_level) in ways {
+            writer.add_way(way, zoom_level);
+        }
+
+        Ok(writer)
+    }
+}
+
+// the tags of the whitelist and the lowest zoom level any of them is visible
+// on, None if no tag is whitelisted
+fn whitelisted(tags: &[(String, String)], whitelist: &HashMap<String, u8>) -> Option<(Vec<Tag>, u8)> {
+    let mut kept = vec![];
+    let mut zoom_level: Option<u8> = None;
+
+    for (key, value) in tags {
+        let level = whitelist
+            .get(&format!("{key}{KEY_VALUE_SEPERATOR}{value}"))
+            .or_else(|| whitelist.get(&format!("{key}{KEY_VALUE_SEPERATOR}{ANY_VALUE}")));
+
+        if let Some(&level) = level {
+            zoom_level = Some(zoom_level.map_or(level, |zoom_level| zoom_level.min(level)));
+            kept.push(Tag::new(key, value));
+        }
+    }
+
+    if kept.len() > MAX_FEATURE_TAGS {
+        warn!("dropping {} of {} whitelisted tags, a feature has at most {MAX_FEATURE_TAGS}", kept.len() - MAX_FEATURE_TAGS, kept.len());
+        kept.truncate(MAX_FEATURE_TAGS);
+    }
+
+    zoom_level.map(|zoom_level| (kept, zoom_level))
+}
+
+fn poi(osm_tags: &[(String, String)], tags: Vec<Tag>, position: LatLong) -> POI {
+    POI {
+        position,
+        layer: layer(osm_tags),
+        name: tag_value(osm_tags, TAG_KEY_NAME),
+        house_number: tag_value(osm_tags, TAG_KEY_HOUSE_NUMBER),
+        elevation: tag_value(osm_tags, TAG_KEY_ELEVATION)
+            .and_then(|elevation| elevation.trim().parse::<f64>().ok())
+            .map(|elevation| elevation.round() as i32),
+        tags: Some(tags),
+        ..Default::default()
+    }
+}
+
+// one way data block per line or polygon, the outer ring of a polygon first
+fn way(osm_tags: &[(String, String)], tags: Vec<Tag>, data_blocks: Vec<Vec<Vec<LatLong>>>) -> Way {
+    Way {
+        layer: layer(osm_tags),
+        name: tag_value(osm_tags, TAG_KEY_NAME),
+        house_number: tag_value(osm_tags, TAG_KEY_HOUSE_NUMBER),
+        reference: tag_value(osm_tags, TAG_KEY_REF),
+        tags: Some(tags),
+        coordinate_blocks: data_blocks
+            .into_iter()
+            .map(|rings| {
+                rings
+                    .into_iter()
+                    .map(WayCoordinateBlock::new)
+                    .collect()
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+// layers that can't be stored fall back to the default layer 0, like
+// mapsforge's own writer does
+fn layer(tags: &[(String, String)]) -> i8 {
+    let layer = tag_value(tags, TAG_KEY_LAYER)
+        .and_then(|layer| layer.trim().parse::<i8>().ok())
+        .filter(|layer| (-LAYER_OFFSET..=MAX_LAYER).contains(layer))
+        .unwrap_or_default();
+
+    layer + LAYER_OFFSET
+}
+
+fn tag_value(tags: &[(String, String)], key: &str) -> Option<String> {
+    tags.iter().find(|(k, _)| k == key).map(|(_, value)| value.clone())
+}
+
+fn has_tag(tags: &[(String, String)], key: &str, value: &str) -> bool {
+    tags.iter().any(|(k, v)| k == key && v == value)
+}
+
+// nodes missing from the extract are left out
+fn positions(refs: &[i64], data: &OsmData) -> Vec<LatLong> {
+    refs.iter().filter_map(|id| data.nodes.get(id)).map(|node| node.position).collect()
+}
+
+// joins member ways end to end into closed rings, ways that don't end up in a
+// closed ring are dropped
+fn rings(members: Vec<&[i64]>, data: &OsmData) -> Vec<Vec<LatLong>> {
+    let mut segments: Vec<Vec<i64>> = members.into_iter().filter(|refs| refs.len() > 1).map(<[i64]>::to_vec).collect();
+    segments.reverse();
+
+    let mut rings = vec![];
+    while let Some(mut ring) = segments.pop() {
+        while ring.first() != ring.last() {
+            let end = ring[ring.len() - 1];
+            let Some(index) = segments.iter().position(|segment| segment[0] == end || segment[segment.len() - 1] == end) else {
+                break;
+            };
+
+            let mut segment = segments.remove(index);
+            if segment[0] != end {
+                segment.reverse();
+            }
+            ring.extend(&segment[1..]);
+        }
+
+        let ring = positions(&ring, data);
+        if clip::is_closed(&ring) {
+            rings.push(ring);
+        }
+    }
+
+    rings
+}
+
+// each outer ring followed by the inner rings inside of it
+fn polygons(outer: &[Vec<LatLong>], inner: &[Vec<LatLong>]) -> Vec<Vec<Vec<LatLong>>> {
+    let mut polygons: Vec<Vec<Vec<LatLong>>> = outer.iter().map(|ring| vec![ring.clone()]).collect();
+
+    for ring in inner {
+        if let Some(polygon) = polygons.iter_mut().find(|polygon| ring_contains(&polygon[0], ring[0])) {
+            polygon.push(ring.clone());
+        }
+    }
+
+    polygons
+}
+
+// even-odd rule, counts the edges crossed by a ray going east from the node
+fn ring_contains(ring: &[LatLong], node: LatLong) -> bool {
+    let mut inside = false;
+
+    for edge in ring.windows(2) {
+        let (a, b) = (edge[0], edge[1]);
+        if (a.latitude > node.latitude) != (b.latitude > node.latitude) {
+            let longitude = a.longitude + (node.latitude - a.latitude) / (b.latitude - a.latitude) * (b.longitude - a.longitude);
+            if node.longitude < longitude {
+                inside = !inside;
+            }
+        }
+    }
+
+    inside
+}
+
+fn features_bounding_box(pois: &[(POI, u8)], ways: &[(Way, u8)]) -> Option<BoundingBox> {
+    let poi_boxes = pois.iter().map(|(poi, _)| BoundingBox {
+        min_lat: poi.position.latitude,
+        min_lon: poi.position.longitude,
+        max_lat: poi.position.latitude,
+        max_lon: poi.position.longitude,
+    });
+    let way_boxes = ways.iter().filter_map(|(way, _)| way.bounding_box());
+
+    poi_boxes.chain(way_boxes).reduce(|a, b| BoundingBox {
+        min_lat: a.min_lat.min(b.min_lat),
+        min_lon: a.min_lon.min(b.min_lon),
+        max_lat: a.max_lat.max(b.max_lat),
+        max_lon: a.max_lon.max(b.max_lon),
+    })
+}
@@ -0,0 +1,549 @@
+use std::{collections::HashMap, io::Read};
+
+use byteorder::{BigEndian, ByteOrder};
+use flate2::read::ZlibDecoder;
+
+use crate::{
+    error::MapforgeError,
+    types::{BoundingBox, LatLong},
+    Result,
+};
+
+// limits from the PBF specification
+const MAX_BLOB_HEADER_SIZE: u32 = 64 * 1024;
+const MAX_BLOB_SIZE: usize = 32 * 1024 * 1024;
+
+const NANODEGREES: f64 = 1e-9;
+const SUPPORTED_FEATURES: [&str; 2] = ["OsmSchema-V0.6", "DenseNodes"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemberType {
+    Node,
+    Way,
+    Relation,
+}
+
+#[derive(Debug, Clone)]
+pub struct OsmNode {
+    pub position: LatLong,
+    pub tags: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OsmWay {
+    pub id: i64,
+    pub refs: Vec<i64>,
+    pub tags: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OsmMember {
+    pub member_type: MemberType,
+    pub id: i64,
+    pub role: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct OsmRelation {
+    pub id: i64,
+    pub members: Vec<OsmMember>,
+    pub tags: Vec<(String, String)>,
+}
+
+// everything read from an extract, nodes by id since ways refer to them
+#[derive(Debug, Default)]
+pub struct OsmData {
+    pub bounding_box: Option<BoundingBox>,
+    pub nodes: HashMap<i64, OsmNode>,
+    pub ways: Vec<OsmWay>,
+    pub relations: Vec<OsmRelation>,
+}
+
+// reads a whole PBF file: a sequence of blobs, each a big endian length, a
+// BlobHeader and the Blob it announces, the first one an OSMHeader block
+pub fn read_pbf<R: Read>(mut reader: R) -> Result<OsmData> {
+    let mut data = OsmData::default();
+
+    loop {
+        // the file may only end before a blob, not within its size
+        let mut prefix = Vec::with_capacity(4);
+        let header_size = match reader.by_ref().take(4).read_to_end(&mut prefix)? {
+            0 => break,
+            4 => BigEndian::read_u32(&prefix),
+            read => return Err(invalid(format!("blob size cut off after {read} bytes"))),
+        };
+        if header_size > MAX_BLOB_HEADER_SIZE {
+            return Err(invalid(format!("blob header of {header_size} bytes")));
+        }
+
+        let (blob_type, blob_size) = read_blob_header(&read_exact(&mut reader, header_size as usize)?)?;
+        let block = read_blob(&read_exact(&mut reader, blob_size)?)?;
+
+        match blob_type.as_str() {
+            "OSMHeader" => data.bounding_box = read_header_block(&block)?,
+            "OSMData" => read_primitive_block(&block, &mut data)?,
+            // unknown blobs are to be skipped
+            _ => {}
+        }
+    }
+
+    Ok(data)
+}
+
+fn read_blob_header(bytes: &[u8]) -> Result<(String, usize)> {
+    let mut blob_type = None;
+    let mut blob_size = None;
+
+    let mut message = Message::new(bytes);
+    while let Some((field, value)) = message.next_field()? {
+        match field {
+            1 => blob_type = Some(value.string()?),
+            3 => blob_size = Some(value.varint()? as usize),
+            _ => {}
+        }
+    }
+
+    match (blob_type, blob_size) {
+        (Some(blob_type), Some(size)) if size <= MAX_BLOB_SIZE => Ok((blob_type, size)),
+        (Some(_), Some(size)) => Err(invalid(format!("blob of {size} bytes"))),
+        _ => Err(invalid("blob header without type or size".to_string())),
+    }
+}
+
+// the uncompressed content of a blob, only raw and zlib compressed data is supported
+fn read_blob(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut raw_size = None;
+
+    let mut message = Message::new(bytes);
+    while let Some((field, value)) = message.next_field()? {
+        match field {
+            1 => return Ok(value.bytes()?.to_vec()),
+            2 => raw_size = Some(value.varint()? as usize),
+            3 => {
+                let mut block = Vec::with_capacity(raw_size.unwrap_or_default().min(MAX_BLOB_SIZE));
+                ZlibDecoder::new(value.bytes()?)
+                    .take(MAX_BLOB_SIZE as u64)
+                    .read_to_end(&mut block)?;
+                return Ok(block);
+            }
+            4..=7 => return Err(invalid(format!("unsupported blob compression {field}"))),
+            _ => {}
+        }
+    }
+
+    Err(invalid("blob without data".to_string()))
+}
+
+fn read_header_block(bytes: &[u8]) -> Result<Option<BoundingBox>> {
+    let mut bounding_box = None;
+
+    let mut message = Message::new(bytes);
+    while let Some((field, value)) = message.next_field()? {
+        match field {
+            1 => bounding_box = Some(read_header_bbox(value.bytes()?)?),
+            4 => {
+                let feature = value.string()?;
+                if !SUPPORTED_FEATURES.contains(&feature.as_str()) {
+                    return Err(invalid(format!("unsupported required feature '{feature}'")));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(bounding_box)
+}
+
+fn read_header_bbox(bytes: &[u8]) -> Result<BoundingBox> {
+    let mut bbox = BoundingBox {
+        min_lat: 0.0,
+        min_lon: 0.0,
+        max_lat: 0.0,
+        max_lon: 0.0,
+    };
+
+    let mut message = Message::new(bytes);
+    while let Some((field, value)) = message.next_field()? {
+        let degrees = zigzag(value.varint()?) as f64 * NANODEGREES;
+        match field {
+            1 => bbox.min_lon = degrees,
+            2 => bbox.max_lon = degrees,
+            3 => bbox.max_lat = degrees,
+            4 => bbox.min_lat = degrees,
+            _ => {}
+        }
+    }
+
+    Ok(bbox)
+}
+
+// coordinates in a block are offsets in units of granularity nanodegrees,
+// strings are indices into the string table of the block
+struct BlockContext {
+    strings: Vec<String>,
+    granularity: i64,
+    lat_offset: i64,
+    lon_offset: i64,
+}
+
+impl BlockContext {
+    fn string(&self, index: u64) -> Result<String> {
+        self.strings
+            .get(index as usize)
+            .cloned()
+            .ok_or_else(|| invalid(format!("string table index {index} out of range")))
+    }
+
+    fn tags(&self, keys: &[u64], values: &[u64]) -> Result<Vec<(String, String)>> {
+        if keys.len() != values.len() {
+            return Err(invalid(format!("{} tag keys but {} values", keys.len(), values.len())));
+        }
+
+        keys.iter()
+            .zip(values)
+            .map(|(&key, &value)| Ok((self.string(key)?, self.string(value)?)))
+            .collect()
+    }
+
+    fn position(&self, lat: i64, lon: i64) -> Result<LatLong> {
+        let nanodegrees = |offset: i64, value: i64| {
+            self.granularity
+                .checked_mul(value)
+                .and_then(|value| value.checked_add(offset))
+                .map(|value| value as f64 * NANODEGREES)
+                .ok_or_else(|| invalid(format!("coordinate {value} out of range")))
+        };
+
+        Ok(LatLong {
+            latitude: nanodegrees(self.lat_offset, lat)?,
+            longitude: nanodegrees(self.lon_offset, lon)?,
+        })
+    }
+}
+
+fn read_primitive_block(bytes: &[u8], data: &mut OsmData) -> Result<()> {
+    let mut context = BlockContext {
+        strings: vec![],
+        granularity: 100,
+        lat_offset: 0,
+        lon_offset: 0,
+    };
+    let mut groups = vec![];
+
+    // the groups may come before the string table and the offsets
+    let mut message = Message::new(bytes);
+    while let Some((field, value)) = message.next_field()? {
+        match field {
+            1 => context.strings = read_string_table(value.bytes()?)?,
+            2 => groups.push(value.bytes()?),
+            17 => context.granularity = value.varint()? as i64,
+            19 => context.lat_offset = value.varint()? as i64,
+            20 => context.lon_offset = value.varint()? as i64,
+            _ => {}
+        }
+    }
+
+    for group in groups {
+        let mut message = Message::new(group);
+        while let Some((field, value)) = message.next_field()? {
+            match field {
+                1 => read_node(value.bytes()?, &context, data)?,
+                2 => read_dense_nodes(value.bytes()?, &context, data)?,
+                3 => data.ways.push(read_way(value.bytes()?, &context)?),
+                4 => data.relations.push(read_relation(value.bytes()?, &context)?),
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn read_string_table(bytes: &[u8]) -> Result<Vec<String>> {
+    let mut strings = vec![];
+
+    let mut message = Message::new(bytes);
+    while let Some((field, value)) = message.next_field()? {
+        if field == 1 {
+            strings.push(value.string()?);
+        }
+    }
+
+    Ok(strings)
+}
+
+fn read_node(bytes: &[u8], context: &BlockContext, data: &mut OsmData) -> Result<()> {
+    let (mut id, mut lat, mut lon) = (0, 0, 0);
+    let (mut keys, mut values) = (vec![], vec![]);
+
+    let mut message = Message::new(bytes);
+    while let Some((field, value)) = message.next_field()? {
+        match field {
+            1 => id = zigzag(value.varint()?),
+            2 => value.append_varints(&mut keys)?,
+            3 => value.append_varints(&mut values)?,
+            8 => lat = zigzag(value.varint()?),
+            9 => lon = zigzag(value.varint()?),
+            _ => {}
+        }
+    }
+
+    let node = OsmNode {
+        position: context.position(lat, lon)?,
+        tags: context.tags(&keys, &values)?,
+    };
+    data.nodes.insert(id, node);
+
+    Ok(())
+}
+
+// ids and coordinates are delta coded, the tags of all nodes are in one list
+// of alternating keys and values where each node's tags end with a 0
+fn read_dense_nodes(bytes: &[u8], context: &BlockContext, data: &mut OsmData) -> Result<()> {
+    let (mut ids, mut lats, mut lons, mut keys_values) = (vec![], vec![], vec![], vec![]);
+
+    let mut message = Message::new(bytes);
+    while let Some((field, value)) = message.next_field()? {
+        match field {
+            1 => value.append_varints(&mut ids)?,
+            8 => value.append_varints(&mut lats)?,
+            9 => value.append_varints(&mut lons)?,
+            10 => value.append_varints(&mut keys_values)?,
+            _ => {}
+        }
+    }
+
+    if lats.len() != ids.len() || lons.len() != ids.len() {
+        return Err(invalid(format!("{} dense node ids but {} latitudes and {} longitudes", ids.len(), lats.len(), lons.len())));
+    }
+
+    let mut keys_values = keys_values.into_iter();
+    let (mut id, mut lat, mut lon) = (0, 0, 0);
+    for index in 0..ids.len() {
+        id = add_delta(id, ids[index])?;
+        lat = add_delta(lat, lats[index])?;
+        lon = add_delta(lon, lons[index])?;
+
+        let mut tags = vec![];
+        while let Some(key) = keys_values.next().filter(|&key| key != 0) {
+            let value = keys_values
+                .next()
+                .ok_or_else(|| invalid(format!("dense node {id} has a tag key without value")))?;
+            tags.push((context.string(key)?, context.string(value)?));
+        }
+
+        data.nodes.insert(id, OsmNode { position: context.position(lat, lon)?, tags });
+    }
+
+    Ok(())
+}
+
+fn read_way(bytes: &[u8], context: &BlockContext) -> Result<OsmWay> {
+    let mut id = 0;
+    let (mut keys, mut values, mut refs) = (vec![], vec![], vec![]);
+
+    let mut message = Message::new(bytes);
+    while let Some((field, value)) = message.next_field()? {
+        match field {
+            1 => id = value.varint()? as i64,
+            2 => value.append_varints(&mut keys)?,
+            3 => value.append_varints(&mut values)?,
+            8 => value.append_varints(&mut refs)?,
+            _ => {}
+        }
+    }
+
+    Ok(OsmWay {
+        id,
+        refs: deltas(&refs)?,
+        tags: context.tags(&keys, &values)?,
+    })
+}
+
+fn read_relation(bytes: &[u8], context: &BlockContext) -> Result<OsmRelation> {
+    let mut id = 0;
+    let (mut keys, mut values, mut roles, mut member_ids, mut types) = (vec![], vec![], vec![], vec![], vec![]);
+
+    let mut message = Message::new(bytes);
+    while let Some((field, value)) = message.next_field()? {
+        match field {
+            1 => id = value.varint()? as i64,
+            2 => value.append_varints(&mut keys)?,
+            3 => value.append_varints(&mut values)?,
+            8 => value.append_varints(&mut roles)?,
+            9 => value.append_varints(&mut member_ids)?,
+            10 => value.append_varints(&mut types)?,
+            _ => {}
+        }
+    }
+
+    if roles.len() != member_ids.len() || types.len() != member_ids.len() {
+        return Err(invalid(format!("relation {id} has {} members but {} roles and {} types", member_ids.len(), roles.len(), types.len())));
+    }
+
+    let members = deltas(&member_ids)?
+        .into_iter()
+        .zip(roles.iter().zip(&types))
+        .map(|(member_id, (&role, &member_type))| {
+            let member_type = match member_type {
+                0 => MemberType::Node,
+                1 => MemberType::Way,
+                2 => MemberType::Relation,
+                other => return Err(invalid(format!("relation {id} has a member of type {other}"))),
+            };
+
+            Ok(OsmMember {
+                member_type,
+                id: member_id,
+                role: context.string(role)?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(OsmRelation {
+        id,
+        members,
+        tags: context.tags(&keys, &values)?,
+    })
+}
+
+// a value of the protobuf wire format, groups are deprecated and not used by PBF
+enum Value<'a> {
+    Varint(u64),
+    Fixed64,
+    Bytes(&'a [u8]),
+    Fixed32,
+}
+
+impl<'a> Value<'a> {
+    fn varint(&self) -> Result<u64> {
+        match self {
+            Value::Varint(value) => Ok(*value),
+            _ => Err(invalid("expected a varint field".to_string())),
+        }
+    }
+
+    fn bytes(&self) -> Result<&'a [u8]> {
+        match self {
+            Value::Bytes(bytes) => Ok(bytes),
+            _ => Err(invalid("expected a length delimited field".to_string())),
+        }
+    }
+
+    fn string(&self) -> Result<String> {
+        String::from_utf8(self.bytes()?.to_vec()).map_err(|_| invalid("invalid UTF-8 string".to_string()))
+    }
+
+    // repeated varints are usually packed into one field, but may also come
+    // one per field
+    fn append_varints(&self, values: &mut Vec<u64>) -> Result<()> {
+        match self {
+            Value::Varint(value) => values.push(*value),
+            Value::Bytes(bytes) => {
+                let mut packed = Message::new(bytes);
+                while !packed.is_empty() {
+                    values.push(packed.read_varint()?);
+                }
+            }
+            _ => return Err(invalid("expected repeated varints".to_string())),
+        }
+
+        Ok(())
+    }
+}
+
+// the fields of an encoded protobuf message
+struct Message<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Message<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Message { bytes }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn next_field(&mut self) -> Result<Option<(u64, Value<'a>)>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+
+        let key = self.read_varint()?;
+        let value = match key & 0x07 {
+            0 => Value::Varint(self.read_varint()?),
+            1 => {
+                self.take(8)?;
+                Value::Fixed64
+            }
+            2 => {
+                let length = self.read_varint()? as usize;
+                Value::Bytes(self.take(length)?)
+            }
+            5 => {
+                self.take(4)?;
+                Value::Fixed32
+            }
+            wire_type => return Err(invalid(format!("unsupported wire type {wire_type}"))),
+        };
+
+        Ok(Some((key >> 3, value)))
+    }
+
+    fn read_varint(&mut self) -> Result<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(invalid("varint longer than 64 bits".to_string()))
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8]> {
+        if length > self.bytes.len() {
+            return Err(invalid("message ends in the middle of a field".to_string()));
+        }
+
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(taken)
+    }
+}
+
+fn zigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+fn add_delta(current: i64, delta: u64) -> Result<i64> {
+    current
+        .checked_add(zigzag(delta))
+        .ok_or_else(|| invalid(format!("delta {} after {current} out of range", zigzag(delta))))
+}
+
+// running sums of zigzag encoded deltas
+fn deltas(values: &[u64]) -> Result<Vec<i64>> {
+    let mut current = 0;
+    values
+        .iter()
+        .map(|&delta| {
+            current = add_delta(current, delta)?;
+            Ok(current)
+        })
+        .collect()
+}
+
+fn read_exact<R: Read>(reader: &mut R, length: usize) -> Result<Vec<u8>> {
+    let mut bytes = vec![0; length];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn invalid(message: String) -> MapforgeError {
+    MapforgeError::InvalidPbf(message)
+}
@@ -1,53 +1,113 @@
-use std::io::{BufReader, Read};
+use std::{
+    borrow::Cow,
+    io::{Read, Seek},
+};
 
 use byteorder::ReadBytesExt;
+use log::trace;
 
-use crate::{tag, types::{LatLong, Tag, POI}, utils, Result};
+use crate::{
+    blocks::{BlockRead, CopiedStrings},
+    error::MapforgeError,
+    tag::{self, TAG_KEY_HOUSE_NUMBER, TAG_KEY_NAME},
+    types::{LatLong, PoiRef, Tag, TagRef, TileContext, POI},
+    utils, Result,
+};
 
-const POI_LAYER_BITMASK: u8 = 0xf0;
-const POI_NUMBER_OF_TAGS_BITMASK: u8 = 0x0f;
-const POI_LAYER_SHIFT: u8 = 4;
+pub(crate) const POI_SIGNATURE_PREFIX: &str = "***POIStart";
 
-pub fn process_pois<R: Read>(reader: &mut BufReader<R>, pois_on_query_zoomlevel: usize, poi_tags: &Vec<String>, debug: bool) -> Result<Vec<POI>> {
-    let mut pois: Vec<POI> = Vec::with_capacity(pois_on_query_zoomlevel);
+pub(crate) const POI_LAYER_BITMASK: u8 = 0xf0;
+pub(crate) const POI_NUMBER_OF_TAGS_BITMASK: u8 = 0x0f;
+pub(crate) const POI_LAYER_SHIFT: u8 = 4;
+pub(crate) const POI_FEATURE_NAME: u8 = 0x80;
+pub(crate) const POI_FEATURE_HOUSE_NUMBER: u8 = 0x40;
+pub(crate) const POI_FEATURE_ELEVATION: u8 = 0x20;
 
-    for _ in 0..pois_on_query_zoomlevel {
-        if debug {
-            let mut sig = [0u8; 16];
-            reader.read_exact(&mut sig)?;
-            let poi_sig = String::from_utf8_lossy(&sig).trim().to_string();
+impl PoiRef<'_> {
+    // copies the borrowed strings into a POI
+    pub fn into_owned(self) -> POI {
+        POI {
+            debug_signature: self.debug_signature.map(Cow::into_owned),
+            position_offset: self.position_offset,
+            position: self.position,
+            layer: self.layer,
+            tag_ids: self.tag_ids,
+            tags: Some(self.tags.into_iter().map(Tag::from).collect()),
+            name: self.name.map(Cow::into_owned),
+            house_number: self.house_number.map(Cow::into_owned),
+            elevation: self.elevation,
+        }
+    }
+}
+
+pub fn process_pois<R: Read + Seek>(reader: &mut R, pois_on_query_zoomlevel: usize, poi_tags: &[String], tile: &TileContext) -> Result<Vec<POI>> {
+    let pois = decode_pois(&mut CopiedStrings(reader), pois_on_query_zoomlevel, poi_tags, tile)?;
+
+    Ok(pois.into_iter().map(PoiRef::into_owned).collect())
+}
+
+// process_pois with the strings borrowed from the reader if it lends them
+pub(crate) fn decode_pois<'a>(reader: &mut impl BlockRead<'a>, pois_on_query_zoomlevel: usize, poi_tags: &'a [String], tile: &TileContext) -> Result<Vec<PoiRef<'a>>> {
+    let mut pois: Vec<PoiRef> = vec![];
 
-            println!("POI signature: {poi_sig}");
+    for index in 0..pois_on_query_zoomlevel {
+        let debug_signature = if tile.debug {
+            let signature = reader.read_debug_signature()?;
+            trace!("tile {tile} POI {index}: signature {signature}");
+            if !signature.starts_with(POI_SIGNATURE_PREFIX) {
+                return Err(MapforgeError::InvalidPoiSignature(tile.x, tile.y, index, signature.into_owned()));
+            }
+            Some(signature)
         } else {
-            println!("Skip POI signature")
-        }
+            None
+        };
 
-        let mut poi: POI = Default::default();
-            
-        poi.position_offset = LatLong {
+        // position is stored relative to the top left corner of the tile
+        let position_offset = LatLong {
             latitude: utils::read_microdegrees(reader)?,
             longitude: utils::read_microdegrees(reader)?
         };
+        let mut poi = PoiRef {
+            debug_signature,
+            position_offset,
+            position: LatLong {
+                latitude: tile.origin.latitude + position_offset.latitude,
+                longitude: tile.origin.longitude + position_offset.longitude
+            },
+            ..Default::default()
+        };
         
         let special_byte = reader.read_u8()?;   
         poi.layer = ((special_byte & POI_LAYER_BITMASK) >> POI_LAYER_SHIFT) as i8;
         let number_of_tags = special_byte & POI_NUMBER_OF_TAGS_BITMASK;
 
-        poi.tag_ids = Vec::with_capacity(number_of_tags as usize);
-        let mut tags: Vec<Tag> = Vec::with_capacity(number_of_tags as usize);
-        for _ in 1..number_of_tags {
-            let tag_id = utils::read_vbe_u_int(reader)?;
-            if tag_id < poi_tags.len() {
-                let tag_str = poi_tags[tag_id].clone();
-                let kv: Vec<&str> = tag_str.split_terminator(tag::KEY_VALUE_SEPERATOR).collect();
-                let tag = Tag::new(kv[0], kv[1]);
-                poi.tag_ids.push(tag_id);
-                tags.push(tag);
-            } else {
-                panic!("tag_id > list of tags!");
-            }
+        let (tag_ids, mut tags) = tag::read_tag_refs(reader, poi_tags, number_of_tags)?;
+        poi.tag_ids = tag_ids;
+
+        let flags = reader.read_u8()?;
+
+        if flags & POI_FEATURE_NAME != 0 {
+            // 1. bit: flag for existence of a POI name as a string.
+            let name = reader.read_str()?;
+            tags.push(TagRef { key: TAG_KEY_NAME, value: name.clone() });
+            poi.name = Some(name);
+        }
+
+        if flags & POI_FEATURE_HOUSE_NUMBER != 0 {
+            // 2. bit: flag for existence of a house number as a string.
+            let house_number = reader.read_str()?;
+            tags.push(TagRef { key: TAG_KEY_HOUSE_NUMBER, value: house_number.clone() });
+            poi.house_number = Some(house_number);
         }
-        poi.tags = Some(tags);
+
+        poi.tags = tags;
+
+        if flags & POI_FEATURE_ELEVATION != 0 {
+            // 3. bit: flag for existence of an elevation in meters as VBE-S INT.
+            poi.elevation = Some(utils::read_vbe_s_int(reader)? as i32);
+        }
+        // 4.-8. bit: reserved for future use
+
         pois.push(poi);
     }
 
@@ -0,0 +1,51 @@
+use crate::{
+    tag::{TAG_KEY_HOUSE_NUMBER, TAG_KEY_NAME, TAG_KEY_REF},
+    types::{Tag, Way, POI},
+};
+
+const PROPERTY_LAYER: &str = "layer";
+const PROPERTY_ELEVATION: &str = "ele";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum PropertyValue {
+    String(String),
+    Integer(i64),
+}
+
+// the properties of an exported POI: its tags, layer, name, house number and elevation
+pub(crate) fn poi_properties(poi: &POI) -> Vec<(String, PropertyValue)> {
+    let mut properties = tag_properties(poi.tags.as_deref());
+    properties.push((PROPERTY_LAYER.to_string(), PropertyValue::Integer(poi.layer as i64)));
+    push_field(&mut properties, TAG_KEY_NAME, &poi.name);
+    push_field(&mut properties, TAG_KEY_HOUSE_NUMBER, &poi.house_number);
+    if let Some(elevation) = poi.elevation {
+        properties.push((PROPERTY_ELEVATION.to_string(), PropertyValue::Integer(elevation as i64)));
+    }
+
+    properties
+}
+
+pub(crate) fn way_properties(way: &Way) -> Vec<(String, PropertyValue)> {
+    let mut properties = tag_properties(way.tags.as_deref());
+    properties.push((PROPERTY_LAYER.to_string(), PropertyValue::Integer(way.layer as i64)));
+    push_field(&mut properties, TAG_KEY_NAME, &way.name);
+    push_field(&mut properties, TAG_KEY_HOUSE_NUMBER, &way.house_number);
+    push_field(&mut properties, TAG_KEY_REF, &way.reference);
+
+    properties
+}
+
+fn tag_properties(tags: Option<&[Tag]>) -> Vec<(String, PropertyValue)> {
+    tags.unwrap_or_default()
+        .iter()
+        .map(|tag| (tag.key.clone(), PropertyValue::String(tag.value.clone())))
+        .collect()
+}
+
+// fields are also among the tags of features read from a map, a key is only kept once
+fn push_field(properties: &mut Vec<(String, PropertyValue)>, key: &str, value: &Option<String>) {
+    if let Some(value) = value {
+        properties.retain(|(k, _)| k != key);
+        properties.push((key.to_string(), PropertyValue::String(value.clone())));
+    }
+}
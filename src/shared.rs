@@ -0,0 +1,104 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+};
+
+use crate::{
+    blocks::{self, BlockCursor},
+    tile::{self, BlockSource},
+    types::{BoundingBox, MapFile, MapHeader, QueryResult, SharedMapFile, Tile, TileIndexEntry},
+    utils, Result,
+};
+
+impl SharedMapFile {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(path)?;
+
+        let MapFile { header, tile_indices, .. } = MapFile::from_reader(BufReader::new(&file))?;
+
+        Ok(SharedMapFile {
+            header,
+            tile_indices,
+            file,
+        })
+    }
+
+    // same as MapFile::read_tile
+    pub fn read_tile(&self, zoom: u8, x: u32, y: u32) -> Result<Tile> {
+        let mut source = self;
+        tile::read_tile(&mut source, zoom, x, y)
+    }
+
+    // same as MapFile::query
+    pub fn query(&self, bbox: BoundingBox, zoom: u8) -> Result<QueryResult> {
+        let mut source = self;
+        tile::query(&mut source, bbox, zoom)
+    }
+}
+
+impl BlockSource for &SharedMapFile {
+    type Tile = Tile;
+
+    fn header(&self) -> &MapHeader {
+        &self.header
+    }
+
+    fn index_entry(&mut self, interval_index: usize, index: usize) -> Result<TileIndexEntry> {
+        Ok(self.tile_indices[interval_index][index])
+    }
+
+    fn decode_block(&mut self, interval_index: usize, x: u32, y: u32, (start, end): (u64, u64), zoom: u8, tile_bitmask: Option<u16>) -> Result<Tile> {
+        let reader = PositionalReader {
+            file: &self.file,
+            position: start,
+        };
+
+        let block = utils::read_bytes(reader, end - start)?;
+
+        let interval = &self.header.zoom_interval_configuration[interval_index];
+        let mut cursor = BlockCursor::new(&block, start);
+        blocks::process_poi_way_block(&mut cursor, &self.header, interval, x, y, zoom, tile_bitmask)
+    }
+}
+
+// reads a shared file from its own position, without moving the file cursor
+struct PositionalReader<'a> {
+    file: &'a File,
+    position: u64,
+}
+
+impl Read for PositionalReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = read_at(self.file, buf, self.position)?;
+        self.position += read as u64;
+
+        Ok(read)
+    }
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
+// targets without positional reads seek the shared handle instead, the lock
+// keeps another thread from moving the cursor between the seek and the read
+#[cfg(not(any(unix, windows)))]
+fn read_at(mut file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    use std::{
+        io::{Seek, SeekFrom},
+        sync::{Mutex, PoisonError},
+    };
+
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    let _guard = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    file.seek(SeekFrom::Start(offset))?;
+    file.read(buf)
+}
@@ -0,0 +1,106 @@
+use std::io::{self, Cursor};
+
+use crate::{
+    blocks::{self, BlockCursor},
+    header::DEBUG_INFO_MASK,
+    tile::{self, BlockSource, INDEX_SIGNATURE_SIZE, TILE_INDEX_ENTRY_SIZE},
+    types::{BoundingBox, MapFile, MapHeader, MapSlice, QueryResult, Tile, TileIndexEntry, TileRef},
+    Result,
+};
+
+impl<'a> MapSlice<'a> {
+    // parses the header of a map held in memory, the tile index is not copied
+    // but read from the bytes whenever a tile is requested
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        let header = MapHeader::read_from_file(&mut Cursor::new(data))?;
+
+        let map = MapSlice { header, data };
+
+        for interval in &map.header.zoom_interval_configuration {
+            if map.header.flags & DEBUG_INFO_MASK != 0 {
+                tile::check_index_signature(map.bytes(interval.sub_file_start, INDEX_SIGNATURE_SIZE)?)?;
+            }
+
+            // make sure the whole index is there so reading entries can't fail later on
+            let total_tiles = MapFile::calculate_total_tiles(&map.header.bounding_box, interval.base_zoom_level)?;
+            map.bytes(map.header.tile_index_start(interval), total_tiles as u64 * TILE_INDEX_ENTRY_SIZE as u64)?;
+        }
+
+        Ok(map)
+    }
+
+    // same as MapFile::read_tile
+    pub fn read_tile(&self, zoom: u8, x: u32, y: u32) -> Result<Tile> {
+        Ok(self.read_tile_ref(zoom, x, y)?.into_owned())
+    }
+
+    // a tile whose strings point into the map wherever they are stored as is,
+    // only tag values with a number are allocated
+    pub fn read_tile_ref(&self, zoom: u8, x: u32, y: u32) -> Result<TileRef<'_>> {
+        let mut source = self;
+        tile::read_tile(&mut source, zoom, x, y)
+    }
+
+    // same as MapFile::query
+    pub fn query(&self, bbox: BoundingBox, zoom: u8) -> Result<QueryResult> {
+        let mut source = self;
+        tile::query(&mut source, bbox, zoom)
+    }
+
+    // index entry of the base tile (x, y) of a zoom interval
+    pub fn tile_index_entry(&self, interval_index: usize, x: u32, y: u32) -> Result<TileIndexEntry> {
+        let index = tile::block_index(&self.header, interval_index, x, y)?;
+
+        self.entry(interval_index, index)
+    }
+
+    // the undecoded bytes of the base tile (x, y) of a zoom interval, empty if
+    // the tile has no data
+    pub fn block(&self, interval_index: usize, x: u32, y: u32) -> Result<&'a [u8]> {
+        let mut source = self;
+        match source.block_range(interval_index, x, y)? {
+            Some((start, end)) => self.bytes(start, end - start),
+            None => Ok(&[]),
+        }
+    }
+
+    fn entry(&self, interval_index: usize, index: usize) -> Result<TileIndexEntry> {
+        let interval = tile::index_entry_interval(&self.header, interval_index, index)?;
+        let position = self.header.tile_index_start(interval) + (index * TILE_INDEX_ENTRY_SIZE) as u64;
+
+        let mut bytes = [0u8; TILE_INDEX_ENTRY_SIZE];
+        bytes.copy_from_slice(self.bytes(position, TILE_INDEX_ENTRY_SIZE as u64)?);
+
+        Ok(TileIndexEntry::from_bytes(bytes, interval.sub_file_start))
+    }
+
+    // bounds checked sub-slice, a truncated map is reported like a short read
+    fn bytes(&self, start: u64, length: u64) -> Result<&'a [u8]> {
+        let data = self.data;
+        start
+            .checked_add(length)
+            .filter(|&end| end <= data.len() as u64)
+            .map(|end| &data[start as usize..end as usize])
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof).into())
+    }
+}
+
+impl<'s> BlockSource for &'s MapSlice<'_> {
+    type Tile = TileRef<'s>;
+
+    fn header(&self) -> &MapHeader {
+        &self.header
+    }
+
+    fn index_entry(&mut self, interval_index: usize, index: usize) -> Result<TileIndexEntry> {
+        self.entry(interval_index, index)
+    }
+
+    fn decode_block(&mut self, interval_index: usize, x: u32, y: u32, (start, end): (u64, u64), zoom: u8, tile_bitmask: Option<u16>) -> Result<TileRef<'s>> {
+        let map: &'s MapSlice<'_> = self;
+        let interval = &map.header.zoom_interval_configuration[interval_index];
+        let mut cursor = BlockCursor::new(map.bytes(start, end - start)?, start);
+
+        blocks::decode_block(&mut cursor, &map.header, interval, x, y, zoom, tile_bitmask)
+    }
+}
@@ -1,10 +1,31 @@
-use crate::{types::Tag, utils};
+use std::{
+    borrow::Cow,
+    io::{Read, Seek},
+};
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+use crate::{
+    blocks::{BlockRead, CopiedStrings},
+    error::MapforgeError,
+    types::{Tag, TagRef},
+    utils, Result,
+};
 
 pub const KEY_VALUE_SEPERATOR: char = '=';
 pub const TAG_KEY_NAME: &str = "name";
 pub const TAG_KEY_HOUSE_NUMBER: &str = "addr:housenumber";
 pub const TAG_KEY_REF: &str = "ref";
 
+// tag values that are stored with the feature instead of the tag table
+const VARIABLE_VALUE_PREFIX: char = '%';
+const VARIABLE_VALUE_BYTE: &str = "%b";
+const VARIABLE_VALUE_SHORT: &str = "%h";
+const VARIABLE_VALUE_INT: &str = "%i";
+const VARIABLE_VALUE_FLOAT: &str = "%f";
+const VARIABLE_VALUE_STRING: &str = "%s";
+const COLOUR_KEY_SUFFIX: &str = ":colour";
+
 impl Tag {
     pub fn new(key: &str, value: &str) -> Tag {
         Tag {
@@ -14,4 +35,67 @@ impl Tag {
             value: value.to_string(),
         }
     }
-}
\ No newline at end of file
+}
+
+impl From<TagRef<'_>> for Tag {
+    fn from(tag: TagRef<'_>) -> Self {
+        Tag::new(tag.key, &tag.value)
+    }
+}
+
+// reads the tag ids of a POI or way and resolves them against the tag table
+// from the header, followed by the values of tags with a variable value
+pub fn read_tags<R: Read + Seek>(reader: &mut R, tag_table: &[String], number_of_tags: u8) -> Result<(Vec<usize>, Vec<Tag>)> {
+    let (tag_ids, tags) = read_tag_refs(&mut CopiedStrings(reader), tag_table, number_of_tags)?;
+
+    Ok((tag_ids, tags.into_iter().map(Tag::from).collect()))
+}
+
+// read_tags with the keys and values borrowed from the tag table, variable
+// string values from the reader if it lends them
+pub(crate) fn read_tag_refs<'a>(reader: &mut impl BlockRead<'a>, tag_table: &'a [String], number_of_tags: u8) -> Result<(Vec<usize>, Vec<TagRef<'a>>)> {
+    let mut tag_ids = Vec::with_capacity(number_of_tags as usize);
+    let mut tags: Vec<TagRef> = Vec::with_capacity(number_of_tags as usize);
+
+    for _ in 0..number_of_tags {
+        let offset = reader.stream_position()?;
+        let tag_id = utils::read_vbe_u_int(reader)?;
+
+        let Some(tag_str) = tag_table.get(tag_id) else {
+            return Err(MapforgeError::InvalidTagId(tag_id, offset));
+        };
+        let Some((key, value)) = tag_str.split_once(KEY_VALUE_SEPERATOR) else {
+            return Err(MapforgeError::MalformedTag(tag_str.clone(), offset));
+        };
+
+        tag_ids.push(tag_id);
+        tags.push(TagRef {
+            key,
+            value: Cow::Borrowed(value),
+        });
+    }
+
+    for tag in tags.iter_mut() {
+        if tag.value.len() != 2 || !tag.value.starts_with(VARIABLE_VALUE_PREFIX) {
+            continue;
+        }
+
+        tag.value = match tag.value.as_ref() {
+            VARIABLE_VALUE_BYTE => Cow::Owned(reader.read_i8()?.to_string()),
+            VARIABLE_VALUE_SHORT => Cow::Owned(reader.read_i16::<BigEndian>()?.to_string()),
+            VARIABLE_VALUE_INT => {
+                let value = reader.read_i32::<BigEndian>()?;
+                if tag.key.contains(COLOUR_KEY_SUFFIX) {
+                    Cow::Owned(format!("#{value:x}"))
+                } else {
+                    Cow::Owned(value.to_string())
+                }
+            }
+            VARIABLE_VALUE_FLOAT => Cow::Owned(reader.read_f32::<BigEndian>()?.to_string()),
+            VARIABLE_VALUE_STRING => reader.read_str()?,
+            _ => continue,
+        };
+    }
+
+    Ok((tag_ids, tags))
+}
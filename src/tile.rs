@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     f64::{self, consts::PI},
     fs::File,
     io::{BufReader, Read, Seek, SeekFrom},
@@ -6,20 +7,48 @@ use std::{
 };
 
 use crate::{
+    blocks,
     error::MapforgeError,
     header::DEBUG_INFO_MASK,
-    types::{BoundingBox, MapFile, MapHeader, TileIndexEntry},
+    types::{
+        BoundingBox, LatLong, LazyTileIndex, MapFile, MapFileOptions, MapHeader, QueryResult, Tile, TileCache,
+        TileIndexEntry, TileRef, Way, WayCoordinateBlock, WayRef, ZoomInterval,
+    },
     Result,
 };
 
-const INDEX_SIGNATURE: &str = "+++IndexStart+++";
+pub(crate) const INDEX_SIGNATURE: &str = "+++IndexStart+++";
 const WATER_TILE_MASK: u8 = 0x80;
+pub const TILE_INDEX_ENTRY_SIZE: usize = 5;
+pub(crate) const INDEX_SIGNATURE_SIZE: u64 = 16;
+const LATITUDE_MAX_MERCATOR: f64 = 85.051_128_779_806_59;
+// the deepest zoom level whose tile numbers fit into u32
+pub const MAX_ZOOM_LEVEL: u8 = 32;
+// two microdegrees in degrees
+const SAME_NODE_TOLERANCE: f64 = 2e-6;
 
 impl MapFile {
     pub fn open(path: impl AsRef<Path>) -> Result<Self> {
         let file = File::open(path)?;
-        let mut reader = BufReader::new(file);
 
+        Self::from_reader(BufReader::new(file))
+    }
+
+    pub fn open_with(path: impl AsRef<Path>, options: MapFileOptions) -> Result<Self> {
+        let file = File::open(path)?;
+
+        Self::from_reader_with(BufReader::new(file), options)
+    }
+}
+
+impl<R: Read + Seek> MapFile<R> {
+    // reads the header and tile index from any seekable source, e.g. a map
+    // held in memory as Cursor<Vec<u8>>
+    pub fn from_reader(reader: R) -> Result<Self> {
+        Self::from_reader_with(reader, Default::default())
+    }
+
+    pub fn from_reader_with(mut reader: R, options: MapFileOptions) -> Result<Self> {
         let header = MapHeader::read_from_file(&mut reader)?;
 
         let mut zoom_tile_indices = Vec::with_capacity(header.num_zoom_intervals as usize);
@@ -28,38 +57,26 @@ impl MapFile {
             reader.seek(SeekFrom::Start(interval.sub_file_start))?;
 
             if header.flags & DEBUG_INFO_MASK != 0 {
-                let mut sig = [0u8; 16];
+                let mut sig = [0u8; INDEX_SIGNATURE_SIZE as usize];
                 reader.read_exact(&mut sig)?;
-                let index_sig = String::from_utf8_lossy(&sig).trim().to_string();
+                check_index_signature(&sig)?;
+            }
 
-                if index_sig != INDEX_SIGNATURE {
-                    return Err(MapforgeError::InvalidIndexSignature);
-                }
+            if options.lazy_index {
+                continue;
             }
 
             let total_tiles_index =
-                Self::calculate_total_tiles(&header.bounding_box, interval.base_zoom_level);
+                MapFile::calculate_total_tiles(&header.bounding_box, interval.base_zoom_level)?;
 
-            let mut tile_index = Vec::with_capacity(total_tiles_index as usize);
+            // no capacity up front, a corrupt bounding box claims billions of tiles
+            let mut tile_index = vec![];
             for _ in 0..total_tiles_index {
-                let mut bytes = [0u8; 5];
+                let mut bytes = [0u8; TILE_INDEX_ENTRY_SIZE];
 
                 reader.read_exact(&mut bytes)?;
 
-                let is_water_tile = (bytes[0] & WATER_TILE_MASK) != 0;
-
-                bytes[0] &= !WATER_TILE_MASK;
-
-                let offset =u64::from_be_bytes([
-                        0, 0, 0, bytes[0], bytes[1], bytes[2], bytes[3], bytes[4],
-                    ]);
-
-                let tile_index_entry = TileIndexEntry {
-                    is_water: is_water_tile,
-                    offset,
-                    offset_abs: offset+interval.sub_file_start,
-                };
-                tile_index.push(tile_index_entry);
+                tile_index.push(TileIndexEntry::from_bytes(bytes, interval.sub_file_start));
             }
 
             zoom_tile_indices.push(tile_index);
@@ -69,15 +86,599 @@ impl MapFile {
             header,
             reader,
             tile_indices: zoom_tile_indices,
+            lazy_index: options
+                .lazy_index
+                .then(|| LazyTileIndex::new(options.index_cache_size)),
+            tile_cache: (options.tile_cache_size > 0).then(|| TileCache::new(options.tile_cache_size)),
         })
     }
 
-/*
-    pub fn get_tile_at(&mut self, lat: f64, lon:f64, zoom:u8) -> Result<Tile> {
-        
+    // reads and decodes the tile (x, y) at the given zoom level
+    // tiles above the base zoom level of their zoom interval are served from the
+    // base tile containing them, tiles below it span several base tiles whose
+    // features are merged into one tile
+    pub fn read_tile(&mut self, zoom: u8, x: u32, y: u32) -> Result<Tile> {
+        read_tile(self, zoom, x, y)
+    }
+
+    // returns all POIs and ways inside the bounding box, read from the tiles of the
+    // zoom interval containing the zoom level
+    pub fn query(&mut self, bbox: BoundingBox, zoom: u8) -> Result<QueryResult> {
+        query(self, bbox, zoom)
+    }
+
+    fn decode(&mut self, interval_index: usize, x: u32, y: u32, start: u64, zoom: u8, tile_bitmask: Option<u16>) -> Result<Tile> {
+        self.reader.seek(SeekFrom::Start(start))?;
+
+        let interval = &self.header.zoom_interval_configuration[interval_index];
+        blocks::process_poi_way_block(&mut self.reader, &self.header, interval, x, y, zoom, tile_bitmask)
+    }
+
+    // entry at position index of the tile index of a zoom interval, read from
+    // the file if the index is loaded lazily
+    pub fn tile_index_entry(&mut self, interval_index: usize, index: usize) -> Result<TileIndexEntry> {
+        let interval = index_entry_interval(&self.header, interval_index, index)?;
+
+        let Some(lazy_index) = &mut self.lazy_index else {
+            return Ok(self.tile_indices[interval_index][index]);
+        };
+
+        if let Some(entry) = lazy_index.get(interval_index, index) {
+            return Ok(entry);
+        }
+
+        let position = self.header.tile_index_start(interval) + (index * TILE_INDEX_ENTRY_SIZE) as u64;
+        self.reader.seek(SeekFrom::Start(position))?;
+
+        let mut bytes = [0u8; TILE_INDEX_ENTRY_SIZE];
+        self.reader.read_exact(&mut bytes)?;
+
+        let entry = TileIndexEntry::from_bytes(bytes, interval.sub_file_start);
+        lazy_index.insert(interval_index, index, entry);
+
+        Ok(entry)
+    }
+
+    // index of the zoom interval that contains the given zoom level
+    pub fn zoom_interval_index(&self, zoom: u8) -> Option<usize> {
+        self.header.zoom_interval_index(zoom)
+    }
+}
+
+impl<R: Read + Seek> BlockSource for MapFile<R> {
+    type Tile = Tile;
+
+    fn header(&self) -> &MapHeader {
+        &self.header
+    }
+
+    fn index_entry(&mut self, interval_index: usize, index: usize) -> Result<TileIndexEntry> {
+        self.tile_index_entry(interval_index, index)
+    }
+
+    // with a tile cache the whole block is decoded once and filtered per read
+    fn decode_block(&mut self, interval_index: usize, x: u32, y: u32, (start, _): (u64, u64), zoom: u8, tile_bitmask: Option<u16>) -> Result<Tile> {
+        if self.tile_cache.is_none() {
+            return self.decode(interval_index, x, y, start, zoom, tile_bitmask);
+        }
+
+        let interval = &self.header.zoom_interval_configuration[interval_index];
+        if let Some(block) = self.tile_cache.as_mut().and_then(|cache| cache.get(interval_index, x, y)) {
+            return Ok(block.features_for(interval, zoom, tile_bitmask));
+        }
+
+        let block = self.decode(interval_index, x, y, start, interval.max_zoom_level, None)?;
+
+        let interval = &self.header.zoom_interval_configuration[interval_index];
+        let tile = block.features_for(interval, zoom, tile_bitmask);
+        if let Some(cache) = &mut self.tile_cache {
+            cache.insert(interval_index, x, y, block);
+        }
+
+        Ok(tile)
+    }
+}
+
+impl MapHeader {
+    // index of the zoom interval that contains the given zoom level
+    pub fn zoom_interval_index(&self, zoom: u8) -> Option<usize> {
+        self.zoom_interval_configuration
+            .iter()
+            .position(|interval| interval.min_zoom_level <= zoom && zoom <= interval.max_zoom_level)
+    }
+
+    // the tile index follows the optional index signature at the start of a sub-file
+    pub(crate) fn tile_index_start(&self, interval: &ZoomInterval) -> u64 {
+        if self.flags & DEBUG_INFO_MASK != 0 {
+            interval.sub_file_start + INDEX_SIGNATURE_SIZE
+        } else {
+            interval.sub_file_start
+        }
+    }
+}
+
+impl LazyTileIndex {
+    pub fn new(capacity: usize) -> Self {
+        LazyTileIndex {
+            capacity,
+            ..Default::default()
+        }
     }
-*/
-    pub fn calculate_total_tiles(bounding_box: &BoundingBox, zoom: u8) -> u32 {
+
+    pub fn get(&self, interval_index: usize, index: usize) -> Option<TileIndexEntry> {
+        self.entries.get(&(interval_index, index)).copied()
+    }
+
+    pub fn insert(&mut self, interval_index: usize, index: usize, entry: TileIndexEntry) {
+        if self.capacity == 0 || self.entries.contains_key(&(interval_index, index)) {
+            return;
+        }
+
+        if self.entries.len() >= self.capacity {
+            if let Some(oldest) = self.insertion_order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.entries.insert((interval_index, index), entry);
+        self.insertion_order.push_back((interval_index, index));
+    }
+}
+
+impl TileIndexEntry {
+    // decodes a 5 byte index entry: the water flag followed by a 39 bit offset
+    // relative to the start of the sub-file
+    pub fn from_bytes(mut bytes: [u8; TILE_INDEX_ENTRY_SIZE], sub_file_start: u64) -> Self {
+        let is_water_tile = (bytes[0] & WATER_TILE_MASK) != 0;
+
+        bytes[0] &= !WATER_TILE_MASK;
+
+        let offset = u64::from_be_bytes([
+            0, 0, 0, bytes[0], bytes[1], bytes[2], bytes[3], bytes[4],
+        ]);
+
+        TileIndexEntry {
+            is_water: is_water_tile,
+            offset,
+            offset_abs: offset + sub_file_start,
+        }
+    }
+}
+
+// a decoded tile block, owned or borrowing its strings from the map
+pub(crate) trait BlockTile: Default {
+    type Poi;
+    type Way: BlockWay;
+
+    fn zoom_table(&mut self) -> &mut Vec<(u32, u32)>;
+
+    fn pois(&mut self) -> &mut Vec<Self::Poi>;
+
+    fn ways(&mut self) -> &mut Vec<Self::Way>;
+
+    fn into_tile(self) -> Tile;
+
+    // a block without data, all counts of its zoom table are zero
+    fn empty(interval: &ZoomInterval) -> Self {
+        let levels = usize::from(interval.max_zoom_level.saturating_sub(interval.min_zoom_level)) + 1;
+        let mut tile = Self::default();
+        *tile.zoom_table() = vec![(0, 0); levels];
+        tile
+    }
+
+    // adds the features of another block, the zoom tables are summed up
+    fn merge(&mut self, mut block: Self) {
+        if self.zoom_table().is_empty() {
+            *self = block;
+            return;
+        }
+
+        for (sum, (pois, ways)) in self.zoom_table().iter_mut().zip(block.zoom_table().iter()) {
+            sum.0 += pois;
+            sum.1 += ways;
+        }
+        self.pois().append(block.pois());
+        self.ways().append(block.ways());
+    }
+}
+
+impl BlockTile for Tile {
+    type Poi = crate::types::POI;
+    type Way = Way;
+
+    fn zoom_table(&mut self) -> &mut Vec<(u32, u32)> {
+        &mut self.zoom_table
+    }
+
+    fn pois(&mut self) -> &mut Vec<Self::Poi> {
+        &mut self.pois
+    }
+
+    fn ways(&mut self) -> &mut Vec<Way> {
+        &mut self.ways
+    }
+
+    fn into_tile(self) -> Tile {
+        self
+    }
+}
+
+impl<'a> BlockTile for TileRef<'a> {
+    type Poi = crate::types::PoiRef<'a>;
+    type Way = WayRef<'a>;
+
+    fn zoom_table(&mut self) -> &mut Vec<(u32, u32)> {
+        &mut self.zoom_table
+    }
+
+    fn pois(&mut self) -> &mut Vec<Self::Poi> {
+        &mut self.pois
+    }
+
+    fn ways(&mut self) -> &mut Vec<WayRef<'a>> {
+        &mut self.ways
+    }
+
+    fn into_tile(self) -> Tile {
+        self.into_owned()
+    }
+}
+
+// the parts of a way that tell its copies in several blocks apart
+pub(crate) trait BlockWay {
+    fn key(&self) -> WayKey<'_>;
+
+    fn coordinate_blocks(&self) -> &[Vec<WayCoordinateBlock>];
+}
+
+impl BlockWay for Way {
+    fn key(&self) -> WayKey<'_> {
+        WayKey {
+            layer: self.layer,
+            tag_ids: &self.tag_ids,
+            name: self.name.as_deref(),
+            house_number: self.house_number.as_deref(),
+            reference: self.reference.as_deref(),
+            node_counts: node_counts(&self.coordinate_blocks),
+        }
+    }
+
+    fn coordinate_blocks(&self) -> &[Vec<WayCoordinateBlock>] {
+        &self.coordinate_blocks
+    }
+}
+
+impl BlockWay for WayRef<'_> {
+    fn key(&self) -> WayKey<'_> {
+        WayKey {
+            layer: self.layer,
+            tag_ids: &self.tag_ids,
+            name: self.name.as_deref(),
+            house_number: self.house_number.as_deref(),
+            reference: self.reference.as_deref(),
+            node_counts: node_counts(&self.coordinate_blocks),
+        }
+    }
+
+    fn coordinate_blocks(&self) -> &[Vec<WayCoordinateBlock>] {
+        &self.coordinate_blocks
+    }
+}
+
+impl QueryResult {
+    // adds the features of a tile that are inside the bounding box
+    pub(crate) fn add_tile(&mut self, tile: Tile, bbox: &BoundingBox) {
+        self.pois
+            .extend(tile.pois.into_iter().filter(|poi| bbox.contains(&poi.position)));
+        self.ways.extend(tile.ways.into_iter().filter(|way| {
+            way.bounding_box()
+                .is_some_and(|way_bbox| way_bbox.intersects(bbox))
+        }));
+    }
+}
+
+// the blocks on the base zoom level of a zoom interval a tile read or query has
+// to decode, independent of where the map is read from
+pub(crate) struct BlockRequest {
+    pub interval_index: usize,
+    pub blocks: Vec<(u32, u32)>,
+    pub tile_bitmask: Option<u16>,
+}
+
+impl BlockRequest {
+    pub(crate) fn for_tile(header: &MapHeader, zoom: u8, x: u32, y: u32) -> Result<Self> {
+        let interval_index = header
+            .zoom_interval_index(zoom)
+            .ok_or(MapforgeError::InvalidZoomLevel(zoom))?;
+        let base_zoom_level = header.zoom_interval_configuration[interval_index].base_zoom_level;
+
+        // the header only has zoom intervals up to MAX_ZOOM_LEVEL, shifted in
+        // u64 none of the shifts can overflow
+        let (tile_x, tile_y) = (u64::from(x), u64::from(y));
+        if tile_x >= 1 << zoom || tile_y >= 1 << zoom {
+            return Err(MapforgeError::TileOutOfBounds(zoom, x, y));
+        }
+
+        let (from_x, from_y, to_x, to_y) = if zoom >= base_zoom_level {
+            let shift = zoom - base_zoom_level;
+            (tile_x >> shift, tile_y >> shift, tile_x >> shift, tile_y >> shift)
+        } else {
+            let shift = base_zoom_level - zoom;
+            (tile_x << shift, tile_y << shift, ((tile_x + 1) << shift) - 1, ((tile_y + 1) << shift) - 1)
+        };
+
+        // only the base tiles inside the map bounding box are stored in the file
+        let (x_min, y_min, x_max, y_max) = MapFile::tile_range(&header.bounding_box, base_zoom_level);
+        let (from_x, from_y) = (from_x.max(x_min.into()), from_y.max(y_min.into()));
+        let (to_x, to_y) = (to_x.min(x_max.into()), to_y.min(y_max.into()));
+
+        if from_x > to_x || from_y > to_y {
+            return Err(MapforgeError::TileOutOfBounds(zoom, x, y));
+        }
+
+        // all of them are within the u32 tile range of the map now
+        Ok(BlockRequest {
+            interval_index,
+            blocks: blocks_in_range(from_x as u32, from_y as u32, to_x as u32, to_y as u32),
+            tile_bitmask: MapFile::tile_bitmask(base_zoom_level, zoom, x, y)?,
+        })
+    }
+
+    pub(crate) fn for_query(header: &MapHeader, bbox: &BoundingBox, zoom: u8) -> Result<Self> {
+        let interval_index = header
+            .zoom_interval_index(zoom)
+            .ok_or(MapforgeError::InvalidZoomLevel(zoom))?;
+        let base_zoom_level = header.zoom_interval_configuration[interval_index].base_zoom_level;
+
+        let mut request = BlockRequest {
+            interval_index,
+            blocks: Vec::new(),
+            tile_bitmask: None,
+        };
+
+        if !bbox.intersects(&header.bounding_box) {
+            return Ok(request);
+        }
+
+        let (x_min, y_min, x_max, y_max) = MapFile::tile_range(&header.bounding_box, base_zoom_level);
+        let (from_x, from_y, to_x, to_y) = MapFile::tile_range(bbox, base_zoom_level);
+
+        request.blocks = blocks_in_range(from_x.max(x_min), from_y.max(y_min), to_x.min(x_max), to_y.min(y_max));
+
+        Ok(request)
+    }
+}
+
+// where the tile blocks of a map are read from, all readers select, locate and
+// merge blocks the same way and only differ in how they get to the bytes
+pub(crate) trait BlockSource {
+    type Tile: BlockTile;
+
+    fn header(&self) -> &MapHeader;
+
+    // entry at position index of the tile index of a zoom interval
+    fn index_entry(&mut self, interval_index: usize, index: usize) -> Result<TileIndexEntry>;
+
+    // decodes the block at the absolute (start, end) range, x and y are tile
+    // numbers on the base zoom level of the interval, zoom is the level
+    // features are read for
+    fn decode_block(&mut self, interval_index: usize, x: u32, y: u32, range: (u64, u64), zoom: u8, tile_bitmask: Option<u16>) -> Result<Self::Tile>;
+
+    // absolute start and end of the base tile (x, y) of a zoom interval, None
+    // for a block without data
+    fn block_range(&mut self, interval_index: usize, x: u32, y: u32) -> Result<Option<(u64, u64)>> {
+        let header = self.header();
+        let index = block_index(header, interval_index, x, y)?;
+        let interval = &header.zoom_interval_configuration[interval_index];
+        let total_tiles = MapFile::calculate_total_tiles(&header.bounding_box, interval.base_zoom_level)? as usize;
+        let sub_file_size = interval.sub_file_size;
+
+        let entry = self.index_entry(interval_index, index)?;
+
+        // a block ends where the next one starts, the last one at the end of the sub-file
+        let block_end = if index + 1 < total_tiles {
+            self.index_entry(interval_index, index + 1)?.offset
+        } else {
+            sub_file_size
+        };
+
+        Ok((block_end > entry.offset).then(|| (entry.offset_abs, entry.offset_abs + (block_end - entry.offset))))
+    }
+
+    fn read_block(&mut self, interval_index: usize, x: u32, y: u32, zoom: u8, tile_bitmask: Option<u16>) -> Result<Self::Tile> {
+        match self.block_range(interval_index, x, y)? {
+            Some(range) => self.decode_block(interval_index, x, y, range, zoom, tile_bitmask),
+            None => Ok(Self::Tile::empty(&self.header().zoom_interval_configuration[interval_index])),
+        }
+    }
+}
+
+// the reads behind MapFile::read_tile and query, shared by all readers
+pub(crate) fn read_tile<S: BlockSource>(source: &mut S, zoom: u8, x: u32, y: u32) -> Result<S::Tile> {
+    let request = BlockRequest::for_tile(source.header(), zoom, x, y)?;
+
+    let mut tile = S::Tile::default();
+    for &(block_x, block_y) in &request.blocks {
+        let block = source.read_block(request.interval_index, block_x, block_y, zoom, request.tile_bitmask)?;
+        tile.merge(block);
+    }
+    if request.blocks.len() > 1 {
+        dedup_ways(tile.ways());
+    }
+
+    Ok(tile)
+}
+
+pub(crate) fn query(source: &mut impl BlockSource, bbox: BoundingBox, zoom: u8) -> Result<QueryResult> {
+    let request = BlockRequest::for_query(source.header(), &bbox, zoom)?;
+
+    let mut result: QueryResult = Default::default();
+    for &(x, y) in &request.blocks {
+        let tile = source.read_block(request.interval_index, x, y, zoom, None)?;
+        result.add_tile(tile.into_tile(), &bbox);
+    }
+    dedup_ways(&mut result.ways);
+
+    Ok(result)
+}
+
+// a way is stored in every base tile it touches, the copies only differ in
+// rounding as their nodes are stored relative to the origin of their tile
+// the first copy of each way is kept
+fn dedup_ways<W: BlockWay>(ways: &mut Vec<W>) {
+    let mut kept: HashMap<WayKey, Vec<usize>> = HashMap::new();
+    let keep: Vec<bool> = (0..ways.len())
+        .map(|index| {
+            let copies = kept.entry(ways[index].key()).or_default();
+            if copies.iter().any(|&copy| same_nodes(&ways[copy], &ways[index])) {
+                return false;
+            }
+            copies.push(index);
+            true
+        })
+        .collect();
+
+    let mut keep = keep.into_iter();
+    ways.retain(|_| keep.next().unwrap_or(true));
+}
+
+// everything of a way that is the same in each of its copies, besides the nodes
+#[derive(PartialEq, Eq, Hash)]
+pub(crate) struct WayKey<'w> {
+    layer: i8,
+    tag_ids: &'w [usize],
+    name: Option<&'w str>,
+    house_number: Option<&'w str>,
+    reference: Option<&'w str>,
+    node_counts: Vec<Vec<usize>>,
+}
+
+fn node_counts(coordinate_blocks: &[Vec<WayCoordinateBlock>]) -> Vec<Vec<usize>> {
+    coordinate_blocks
+        .iter()
+        .map(|blocks| blocks.iter().map(|block| block.coordinates.len()).collect())
+        .collect()
+}
+
+// nodes are rounded to microdegrees relative to different tile origins, so the
+// same node may be a microdegree apart in two copies
+fn same_nodes(a: &impl BlockWay, b: &impl BlockWay) -> bool {
+    let close = |a: &LatLong, b: &LatLong| {
+        (a.latitude - b.latitude).abs() <= SAME_NODE_TOLERANCE && (a.longitude - b.longitude).abs() <= SAME_NODE_TOLERANCE
+    };
+
+    a.coordinate_blocks()
+        .iter()
+        .flatten()
+        .zip(b.coordinate_blocks().iter().flatten())
+        .all(|(a, b)| a.coordinates.iter().zip(&b.coordinates).all(|(a, b)| close(a, b)))
+}
+
+// tiles of the range row by row, the order they are stored in
+fn blocks_in_range(from_x: u32, from_y: u32, to_x: u32, to_y: u32) -> Vec<(u32, u32)> {
+    (from_y..=to_y)
+        .flat_map(|y| (from_x..=to_x).map(move |x| (x, y)))
+        .collect()
+}
+
+// position of the base tile (x, y) in the tile index of a zoom interval, the
+// index covers the map bounding box row by row
+pub(crate) fn block_index(header: &MapHeader, interval_index: usize, x: u32, y: u32) -> Result<usize> {
+    let interval = zoom_interval(header, interval_index)?;
+    let (x_min, y_min, x_max, y_max) = MapFile::tile_range(&header.bounding_box, interval.base_zoom_level);
+    if !(x_min..=x_max).contains(&x) || !(y_min..=y_max).contains(&y) {
+        return Err(MapforgeError::TileOutOfBounds(interval.base_zoom_level, x, y));
+    }
+
+    let row_width = (x_max - x_min + 1) as usize;
+    Ok((y - y_min) as usize * row_width + (x - x_min) as usize)
+}
+
+fn zoom_interval(header: &MapHeader, interval_index: usize) -> Result<&ZoomInterval> {
+    header
+        .zoom_interval_configuration
+        .get(interval_index)
+        .ok_or(MapforgeError::InvalidZoomIntervalIndex(interval_index))
+}
+
+// the zoom interval of the tile index entry at position index, checking that
+// the map has the entry
+pub(crate) fn index_entry_interval(header: &MapHeader, interval_index: usize, index: usize) -> Result<&ZoomInterval> {
+    let interval = zoom_interval(header, interval_index)?;
+    if index >= MapFile::calculate_total_tiles(&header.bounding_box, interval.base_zoom_level)? as usize {
+        return Err(MapforgeError::InvalidTileIndexEntry(interval_index, index));
+    }
+
+    Ok(interval)
+}
+
+pub(crate) fn check_index_signature(sig: &[u8]) -> Result<()> {
+    let index_sig = String::from_utf8_lossy(sig).trim().to_string();
+
+    if index_sig != INDEX_SIGNATURE {
+        return Err(MapforgeError::InvalidIndexSignature);
+    }
+
+    Ok(())
+}
+
+// tile math, independent of the source the map is read from
+impl MapFile {
+    // each way stores which of the 4x4 sub-tiles of its base tile (on base zoom
+    // level + 2) it touches, the first bit is the top left sub-tile followed row
+    // by row. This returns the sub-tiles covered by tile (x, y) or None for tiles
+    // on or below the base zoom level, which cover the whole base tile.
+    // Zoom levels too far above the base zoom level for the tile numbers are an
+    // InvalidZoomLevel error.
+    pub fn tile_bitmask(base_zoom_level: u8, zoom: u8, x: u32, y: u32) -> Result<Option<u16>> {
+        let Some(difference) = zoom.checked_sub(base_zoom_level) else {
+            return Ok(None);
+        };
+
+        match difference {
+            0 => Ok(None),
+            1 => {
+                // a quarter of the base tile: 2x2 sub-tiles
+                let column = (x % 2) * 2;
+                let row = (y % 2) * 2;
+                let top_left = 0x8000u16 >> (row * 4 + column);
+                Ok(Some(top_left | top_left >> 1 | top_left >> 4 | top_left >> 5))
+            }
+            difference => {
+                // the sub-tile containing the tile
+                let shift = u32::from(difference - 2);
+                let (Some(sub_tile_x), Some(sub_tile_y)) = (x.checked_shr(shift), y.checked_shr(shift)) else {
+                    return Err(MapforgeError::InvalidZoomLevel(zoom));
+                };
+                Ok(Some(0x8000u16 >> ((sub_tile_y % 4) * 4 + sub_tile_x % 4)))
+            }
+        }
+    }
+
+    pub fn calculate_total_tiles(bounding_box: &BoundingBox, zoom: u8) -> Result<u32> {
+        if zoom > MAX_ZOOM_LEVEL {
+            return Err(MapforgeError::InvalidZoomLevel(zoom));
+        }
+        let (x_min, y_min, x_max, y_max) = Self::tile_range(bounding_box, zoom);
+
+        let num_x = (x_max - x_min).checked_add(1);
+        let num_y = (y_max - y_min).checked_add(1);
+
+        num_x
+            .zip(num_y)
+            .and_then(|(num_x, num_y)| num_x.checked_mul(num_y))
+            .ok_or(MapforgeError::TooManyTiles(zoom))
+    }
+
+    // returns the tile numbers (x_min, y_min, x_max, y_max) covering the bounding box
+    pub fn tile_range(bounding_box: &BoundingBox, zoom: u8) -> (u32, u32, u32, u32) {
+        // the mercator projection is only defined up to about 85 degrees latitude
+        let bounding_box = &BoundingBox {
+            min_lat: bounding_box.min_lat.clamp(-LATITUDE_MAX_MERCATOR, LATITUDE_MAX_MERCATOR),
+            max_lat: bounding_box.max_lat.clamp(-LATITUDE_MAX_MERCATOR, LATITUDE_MAX_MERCATOR),
+            ..*bounding_box
+        };
+
         // X calculation (longitude)
         let x_min =
             ((bounding_box.min_lon + 180.0) / 360.0 * 2_f64.powi(zoom as i32)).floor() as i64;
@@ -95,34 +696,47 @@ impl MapFile {
             * 2_f64.powi(zoom as i32))
         .floor() as i64;
 
-        let num_x = (x_max - x_min + 1) as u32;
-        let num_y = (y_max - y_min + 1) as u32;
-
-        
+        let max_tile = (1i64 << zoom) - 1;
 
-        num_x * num_y
+        (
+            x_min.clamp(0, max_tile) as u32,
+            y_min.clamp(0, max_tile) as u32,
+            x_max.clamp(0, max_tile) as u32,
+            y_max.clamp(0, max_tile) as u32,
+        )
     }
     
-    pub fn get_tiles(lat_deg: f64, lon_deg: f64, zoom: u8) -> (i32, i32) {
-        let n = (1 << zoom as i32) as f64;
+    // tile numbers of the tile containing the position, positions outside of
+    // the mercator projection are outside of any tile
+    pub fn get_tiles(lat_deg: f64, lon_deg: f64, zoom: u8) -> Result<(u32, u32)> {
+        if zoom > MAX_ZOOM_LEVEL {
+            return Err(MapforgeError::InvalidZoomLevel(zoom));
+        }
+        let n = 2_f64.powi(zoom as i32);
 
-        let x = (n * (lon_deg + 180.0) / 360.0) as i32;
+        let x = (n * (lon_deg + 180.0) / 360.0).floor();
 
         let lat_rad = lat_deg.to_radians();
-        let y = (n * (1.0 - (lat_rad.tan() + (1.0 / lat_rad.cos())).ln() / PI) / 2.0) as i32;
+        let y = (n * (1.0 - (lat_rad.tan() + (1.0 / lat_rad.cos())).ln() / PI) / 2.0).floor();
 
-        (x, y)
+        // also false for NaN, e.g. at the poles
+        if !(0.0..n).contains(&x) || !(0.0..n).contains(&y) {
+            return Err(MapforgeError::TileOutOfBounds(zoom, x as u32, y as u32));
+        }
+
+        Ok((x as u32, y as u32))
     }
 
-    fn tilex2long(x: u32, zoom: u8) -> f64
+    // longitude of the left edge of tile x
+    pub fn tilex2long(x: u32, zoom: u8) -> f64
     {
-        x as f64 / (1 << zoom) as f64 * 360.0 - 180.0
+        x as f64 / (1u64 << zoom) as f64 * 360.0 - 180.0
     }
     
-    fn tiley2lat(y: u32, zoom: u8) -> f64
+    // latitude of the top edge of tile y
+    pub fn tiley2lat(y: u32, zoom: u8) -> f64
     {
-        let n = PI - 2.0 * PI * y as f64 / (1 << zoom) as f64;
-        180.0 / PI * (0.5 * (n.exp()) - (-n).exp()).atan()
+        let n = PI - 2.0 * PI * y as f64 / (1u64 << zoom) as f64;
+        180.0 / PI * n.sinh().atan()
     }
-
 }
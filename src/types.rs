@@ -1,4 +1,10 @@
-use std::{fs::File, io::BufReader};
+use lru::LruCache;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::BufReader,
+};
 
 #[derive(Debug,Clone, Copy)]
 pub struct BoundingBox {
@@ -8,7 +14,7 @@ pub struct BoundingBox {
     pub max_lon: f64,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ZoomInterval {
     pub base_zoom_level: u8,
     pub min_zoom_level: u8,
@@ -18,7 +24,7 @@ pub struct ZoomInterval {
    
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MapHeader {
     pub magic: String,
     pub header_size: u32,
@@ -56,21 +62,136 @@ pub struct TileIndexHeader {
     pub debug_signature: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct TileIndexEntry {
     pub is_water: bool,   
     pub offset: u64,
     pub offset_abs: u64,
 }
 
+// a map file read from any seekable source, files opened by path are buffered
+#[derive(Debug)]
+pub struct MapFile<R = BufReader<File>> {
+    pub header: MapHeader,
+    pub reader: R,
+    // all tile index entries per zoom interval, empty if the index is read lazily
+    pub tile_indices: Vec<Vec<TileIndexEntry>>,
+    // set if tile index entries are read from the file when a tile needs them
+    pub lazy_index: Option<LazyTileIndex>,
+    // set if decoded tiles are kept in memory
+    pub tile_cache: Option<TileCache>,
+}
+
+// a map file that can be read from several threads at once through &self,
+// blocks are read with positional reads so the file handle is never seeked.
+// the tile index is always read up front and there is no tile cache, the
+// MapFileOptions only apply to MapFile
 #[derive(Debug)]
-pub struct MapFile {
+pub struct SharedMapFile {
     pub header: MapHeader,
-    pub reader: BufReader<File>,  
     pub tile_indices: Vec<Vec<TileIndexEntry>>,
+    pub file: File,
 }
 
+// a map file read through tokio, blocks are read asynchronously as a whole and
+// decoded from memory
+#[cfg(feature = "async")]
 #[derive(Debug)]
+pub struct AsyncMapFile<R = tokio::fs::File> {
+    pub header: MapHeader,
+    pub reader: R,
+    pub tile_indices: Vec<Vec<TileIndexEntry>>,
+}
+
+// collects POIs and ways with the lowest zoom level they are visible on and
+// writes them as a map file, header holds the metadata written as is
+#[derive(Debug)]
+pub struct MapWriter {
+    pub header: MapHeader,
+    pub pois: Vec<(POI, u8)>,
+    pub ways: Vec<(Way, u8)>,
+    // cut ways at the borders of the tiles they are written to instead of
+    // storing them whole in every tile they touch
+    pub clip_ways: bool,
+}
+
+// converts OpenStreetMap PBF extracts into map files, only nodes and ways with
+// a whitelisted tag are kept, tags map "key=value", or "key=*" for any value,
+// to the lowest zoom level a feature with the tag is visible on
+#[cfg(feature = "osm")]
+#[derive(Debug, Clone)]
+pub struct OsmConverter {
+    pub poi_tags: HashMap<String, u8>,
+    pub way_tags: HashMap<String, u8>,
+    pub zoom_intervals: Vec<ZoomInterval>,
+    // area of the map, by default the bounding box of the extract or, if it
+    // has none, of the converted features
+    pub bounding_box: Option<BoundingBox>,
+}
+
+// features with a tag of key, and value if it is set, go into the MVT layer
+#[cfg(feature = "mvt")]
+#[derive(Debug, Clone)]
+pub struct MvtLayerRule {
+    pub key: String,
+    pub value: Option<String>,
+    pub layer: String,
+}
+
+// encodes POIs and ways as Mapbox vector tiles, a feature goes into the layer
+// of the first rule matching one of its tags or, if none does, into the
+// default layer, without a default layer it is left out
+#[cfg(feature = "mvt")]
+#[derive(Debug, Clone)]
+pub struct MvtEncoder {
+    pub rules: Vec<MvtLayerRule>,
+    pub default_layer: Option<String>,
+    // size of the tile in the integer coordinates of the tile
+    pub extent: u32,
+    // geometries are clipped this far outside of the tile, in the units of extent
+    pub buffer: u32,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MapFileOptions {
+    // read tile index entries on demand instead of the whole index when opening
+    // the map, for large maps that would take seconds and gigabytes
+    pub lazy_index: bool,
+    // number of index entries a lazy index keeps in memory, 0 disables the cache
+    pub index_cache_size: usize,
+    // approximate number of bytes decoded tiles may take in memory, 0 disables
+    // the tile cache
+    pub tile_cache_size: usize,
+}
+
+// tile index entries read on demand, keyed by zoom interval and position in the
+// index, the oldest entry is dropped once the cache is full
+#[derive(Debug, Default)]
+pub struct LazyTileIndex {
+    pub capacity: usize,
+    pub entries: HashMap<(usize, usize), TileIndexEntry>,
+    pub insertion_order: VecDeque<(usize, usize)>,
+}
+
+// fully decoded tile blocks keyed by zoom interval and tile numbers on its base
+// zoom level, the least recently used blocks are dropped once the approximate
+// size of all blocks exceeds the budget
+#[derive(Debug)]
+pub struct TileCache {
+    pub budget: usize,
+    pub size: usize,
+    pub tiles: LruCache<(usize, u32, u32), Tile>,
+}
+
+// a map held in memory, the tile index and blocks are read from the bytes
+// without a reader and tiles can be decoded without copying their strings
+#[derive(Debug)]
+pub struct MapSlice<'a> {
+    pub header: MapHeader,
+    pub data: &'a [u8],
+}
+
+#[derive(Debug, Default, Clone)]
 pub struct Tile {
  
     pub debug_signature: Option<String>,
@@ -84,12 +205,77 @@ pub struct Tile {
     pub ways: Vec<Way>
 }
 
+// a tile decoded from a MapSlice, its strings are borrowed from the map bytes
+// and the tag tables of the header, only tag values a feature stores as
+// numbers are formatted into owned strings
+#[derive(Debug, Default, Clone)]
+pub struct TileRef<'a> {
+    pub debug_signature: Option<Cow<'a, str>>,
+    pub zoom_table: Vec<(u32, u32)>,
+    pub first_way_offset: u32,
+    pub pois: Vec<PoiRef<'a>>,
+    pub ways: Vec<WayRef<'a>>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct PoiRef<'a> {
+    pub debug_signature: Option<Cow<'a, str>>,
+    pub position_offset: LatLong,
+    pub position: LatLong,
+    pub layer: i8,
+    pub tag_ids: Vec<usize>,
+    pub tags: Vec<TagRef<'a>>,
+    pub name: Option<Cow<'a, str>>,
+    pub house_number: Option<Cow<'a, str>>,
+    pub elevation: Option<i32>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct WayRef<'a> {
+    pub debug_signature: Option<Cow<'a, str>>,
+    pub sub_tile_bitmap: u16,
+    pub layer: i8,
+    pub tag_ids: Vec<usize>,
+    pub tags: Vec<TagRef<'a>>,
+    pub name: Option<Cow<'a, str>>,
+    pub house_number: Option<Cow<'a, str>>,
+    pub reference: Option<Cow<'a, str>>,
+    pub label_position: Option<LatLong>,
+    // like Way::coordinate_blocks
+    pub coordinate_blocks: Vec<Vec<WayCoordinateBlock>>,
+    pub double_delta_encoding: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagRef<'a> {
+    pub key: &'a str,
+    pub value: Cow<'a, str>,
+}
+
+// the tile block the POI and way decoders are working on
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TileContext {
+    pub zoom_level: u8,
+    pub x: u32,
+    pub y: u32,
+    // top left corner of the tile, feature positions are stored relative to it
+    pub origin: LatLong,
+    pub debug: bool
+}
+
 #[derive(Debug, Default)]
+pub struct QueryResult {
+    pub pois: Vec<POI>,
+    pub ways: Vec<Way>
+}
+
+#[derive(Debug, Default, Clone)]
 pub struct POI {
 
     pub debug_signature: Option<String>,
     
     pub position_offset: LatLong,
+    pub position: LatLong,
     pub layer: i8,
     pub tag_ids: Vec<usize>,
     pub tags: Option<Vec<Tag>>,
@@ -97,7 +283,7 @@ pub struct POI {
     pub house_number: Option<String>, 
     pub elevation: Option<i32>
 }
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Way {
 
     pub debug_signature: Option<String>,
@@ -111,13 +297,15 @@ pub struct Way {
     pub reference: Option<String>,
     pub label_position: Option<LatLong>,  
     
-
-    pub coordinate_blocks: Vec<WayCoordinateBlock>,
+    // the coordinate blocks of each way data block, a data block is a line or
+    // a polygon: its first coordinate block is the line or outer ring, the
+    // following ones are the inner rings
+    pub coordinate_blocks: Vec<Vec<WayCoordinateBlock>>,
     
 
     pub double_delta_encoding: bool
 }
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct WayCoordinateBlock {
     
     pub initial_position: LatLong, 
@@ -125,13 +313,24 @@ pub struct WayCoordinateBlock {
     pub coordinates: Vec<LatLong>
 }
 
+// simple features geometry of a POI or way, the last node of every ring is
+// its first one and the first ring of a polygon is its outer ring
+#[derive(Debug, Clone)]
+pub enum Geometry {
+    Point(LatLong),
+    LineString(Vec<LatLong>),
+    MultiLineString(Vec<Vec<LatLong>>),
+    Polygon(Vec<Vec<LatLong>>),
+    MultiPolygon(Vec<Vec<Vec<LatLong>>>),
+}
+
 #[derive(Debug)]
 pub struct TagMapping {
     pub poi_tags: Vec<String>,
     pub way_tags: Vec<String>
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Tag {
     pub key: String,
     pub key_code: usize,
@@ -1,9 +1,10 @@
 use byteorder::{ReadBytesExt};
 
 use crate::{
+    error::MapforgeError,
     Result,
 };
-use std::io::{BufReader, Read};
+use std::io::{self, Read, Seek, Write};
 
 pub const LONGITUDE_MAX: f64 = 180f64;
 pub const LONGITUDE_MIN: f64 = -LONGITUDE_MAX;
@@ -12,31 +13,45 @@ pub const LATITUDE_MAX: f64 = 90f64;
 pub const LATITUDE_MIN: f64 = -LATITUDE_MAX;
 
 
-pub fn read_vbe_u<R: Read>(reader: &mut BufReader<R>) -> Result<String> {
-    let mut length = 0u32;
+// invalid UTF-8 is reported with the byte offset of the string
+pub fn read_string<R: Read + Seek>(reader: &mut R) -> Result<String> {
+    let string_bytes = read_vbe_u_bytes(reader)?;
+    let length = string_bytes.len() as u64;
 
-    let mut shift = 0;
-
-    loop {
-        let byte = reader.read_u8()?;
+    match String::from_utf8(string_bytes) {
+        Ok(string) => Ok(string),
+        Err(_) => Err(MapforgeError::InvalidUtf8(reader.stream_position()? - length)),
+    }
+}
 
-        length |= ((byte & 0x7F) as u32) << shift;
+fn read_vbe_u_bytes<R: Read>(reader: &mut R) -> Result<Vec<u8>> {
+    let length = read_vbe_u_int(reader)? as u64;
 
-        if byte & 0x80 == 0 {
-            break;
-        }
+    read_bytes(reader, length)
+}
 
-        shift += 7
+// exactly length bytes, the buffer grows with the data read so a corrupt
+// length doesn't allocate up front
+pub(crate) fn read_bytes<R: Read>(reader: R, length: u64) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    if (reader.take(length).read_to_end(&mut bytes)? as u64) < length {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
     }
 
-    let mut string_bytes = vec![0u8; length as usize];
+    Ok(bytes)
+}
+
+// debug signatures of tiles, POIs and ways are 32 bytes padded with spaces
+pub(crate) const DEBUG_SIGNATURE_SIZE: usize = 32;
 
-    reader.read_exact(&mut string_bytes)?;
+pub fn read_debug_signature<R: Read>(reader: &mut R) -> Result<String> {
+    let mut sig = [0u8; DEBUG_SIGNATURE_SIZE];
+    reader.read_exact(&mut sig)?;
 
-    Ok(String::from_utf8(string_bytes).expect("Error parsing vbe_u"))
+    Ok(String::from_utf8_lossy(&sig).trim().to_string())
 }
 
-pub fn read_vbe_u_int<R: Read>(reader: &mut BufReader<R>) -> Result<usize> {
+pub fn read_vbe_u_int<R: Read>(reader: &mut R) -> Result<usize> {
     let mut length = 0usize;
 
     let mut shift = 0;
@@ -49,15 +64,15 @@ pub fn read_vbe_u_int<R: Read>(reader: &mut BufReader<R>) -> Result<usize> {
             break;
         }
         
-        length |= ((byte & 0x7F) as usize) << shift;
+        length = add_vbe_bits(length, byte & 0x7F, shift)?;
 
         shift += 7;
     }
 
-    Ok(length | ((byte as usize) << shift))
+    add_vbe_bits(length, byte, shift)
 }
 
-pub fn read_vbe_s_int<R: Read>(reader: &mut BufReader<R>) -> Result<isize> {
+pub fn read_vbe_s_int<R: Read>(reader: &mut R) -> Result<isize> {
     let mut length = 0usize;
 
     let mut shift = 0;
@@ -67,24 +82,71 @@ pub fn read_vbe_s_int<R: Read>(reader: &mut BufReader<R>) -> Result<isize> {
         byte = reader.read_u8()?;
 
         if byte & 0x80 == 0 {
-            // read the six data bits from the last byte
-            if byte & 0x40 != 0 {
-                // negative number
-                length |= length | ((byte & 0x3F) as usize) << shift;
-                return Ok(-(length as isize));
-            } else {
-                length |= ((byte & 0x7F) as usize) << shift;
-                return Ok(length as isize);
-            }
+            break;
         }
 
-        length |= ((byte & 0x7F) as usize) << shift;
+        length = add_vbe_bits(length, byte & 0x7F, shift)?;
         
         shift += 7
     }
+
+    // read the six data bits and the sign from the last byte
+    let length = add_vbe_bits(length, byte & 0x3F, shift)?;
+    let length = isize::try_from(length).map_err(|_| MapforgeError::VbeNumberTooLarge)?;
+
+    if byte & 0x40 != 0 {
+        Ok(-length)
+    } else {
+        Ok(length)
+    }
 }
 
-pub fn read_microdegrees<R: Read>(reader: &mut BufReader<R>) -> Result<f64> {
+// adds the data bits of one byte at shift, a corrupt number may go on for
+// more bytes than fit into usize
+fn add_vbe_bits(number: usize, bits: u8, shift: u32) -> Result<usize> {
+    let bits = bits as usize;
+
+    match bits.checked_shl(shift) {
+        Some(shifted) if shifted >> shift == bits => Ok(number | shifted),
+        _ => Err(MapforgeError::VbeNumberTooLarge),
+    }
+}
+
+// writes value in as many bytes as needed, 7 bits each, the first bit of a
+// byte tells if another one follows
+pub fn write_vbe_u_int<W: Write>(writer: &mut W, mut value: usize) -> Result<()> {
+    while value > 0x7F {
+        writer.write_all(&[(value & 0x7F) as u8 | 0x80])?;
+        value >>= 7;
+    }
+    writer.write_all(&[value as u8])?;
+
+    Ok(())
+}
+
+// like write_vbe_u_int, but the last byte holds the sign in its second bit and
+// only six data bits
+pub fn write_vbe_s_int<W: Write>(writer: &mut W, value: isize) -> Result<()> {
+    let mut magnitude = value.unsigned_abs();
+    while magnitude > 0x3F {
+        writer.write_all(&[(magnitude & 0x7F) as u8 | 0x80])?;
+        magnitude >>= 7;
+    }
+    let sign = if value < 0 { 0x40 } else { 0 };
+    writer.write_all(&[magnitude as u8 | sign])?;
+
+    Ok(())
+}
+
+// strings are stored as their length in bytes as VBE-U INT followed by UTF-8
+pub fn write_string<W: Write>(writer: &mut W, value: &str) -> Result<()> {
+    write_vbe_u_int(writer, value.len())?;
+    writer.write_all(value.as_bytes())?;
+
+    Ok(())
+}
+
+pub fn read_microdegrees<R: Read>(reader: &mut R) -> Result<f64> {
     let microdegrees = read_vbe_s_int(reader)?;
     
     Ok(microdegrees_to_degrees(microdegrees))
@@ -94,15 +156,16 @@ pub fn microdegrees_to_degrees(microdegrees: isize) ->  f64 {
     microdegrees as f64 / 1_000_000.0
 }
 
-pub fn decode_poi_tag(tag_id: usize) {
-
+pub fn degrees_to_microdegrees(degrees: f64) -> isize {
+    (degrees * 1_000_000.0).round() as isize
 }
 
 pub fn hash_tag_parameter(key_value: &str) -> usize {
-    let n = key_value.len();
+    // count characters, not bytes, so multi-byte UTF-8 names hash without panicking
+    let n = key_value.chars().count();
     let mut hash: usize = 0;
-    for i in 0..n {
-        hash = hash.wrapping_add(key_value.chars().nth(i).unwrap() as usize).wrapping_mul(31_usize.wrapping_pow((n - i + 1) as u32));
+    for (i, c) in key_value.chars().enumerate() {
+        hash = hash.wrapping_add(c as usize).wrapping_mul(31_usize.wrapping_pow((n - i + 1) as u32));
     }
     hash
 }
\ No newline at end of file
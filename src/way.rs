@@ -1,93 +1,163 @@
-use std::{io::{BufReader, Read}};
+use std::{
+    borrow::Cow,
+    io::{Read, Seek},
+};
 
 use byteorder::ReadBytesExt;
+use log::trace;
 
-use crate::{tag::{self, TAG_KEY_HOUSE_NUMBER, TAG_KEY_NAME, TAG_KEY_REF}, types::{LatLong, Tag, Way, WayCoordinateBlock}, utils, Result};
+use crate::{
+    blocks::{BlockRead, CopiedStrings},
+    error::MapforgeError,
+    tag::{self, TAG_KEY_HOUSE_NUMBER, TAG_KEY_NAME, TAG_KEY_REF},
+    types::{BoundingBox, LatLong, Tag, TagRef, TileContext, Way, WayCoordinateBlock, WayRef},
+    utils, Result,
+};
 
-const WAY_LAYER_BITMASK: u8 = 0xf0;
-const WAY_NUMBER_OF_TAGS_BITMASK: u8 = 0x0f;
-const WAY_LAYER_SHIFT: u8 = 4;
-const WAY_FEATURE_NAME: u8 = 0x80;
-const WAY_FEATURE_HOUSE_NUMBER: u8 = 0x40;
-const WAY_FEATURE_REF: u8 = 0x20;
-const WAY_FEATURE_LABEL_POSITION: u8 = 0x10;
-const WAY_FEATURE_DATA_BLOCKS_BYTE: u8 = 0x08;
-const WAY_FEATURE_DOUBLE_DELTA_ENCODING: u8 = 0x04;
+pub(crate) const WAY_SIGNATURE_PREFIX: &str = "---WayStart";
+pub(crate) const WAY_LAYER_BITMASK: u8 = 0xf0;
+pub(crate) const WAY_NUMBER_OF_TAGS_BITMASK: u8 = 0x0f;
+pub(crate) const WAY_LAYER_SHIFT: u8 = 4;
+pub(crate) const WAY_FEATURE_NAME: u8 = 0x80;
+pub(crate) const WAY_FEATURE_HOUSE_NUMBER: u8 = 0x40;
+pub(crate) const WAY_FEATURE_REF: u8 = 0x20;
+pub(crate) const WAY_FEATURE_LABEL_POSITION: u8 = 0x10;
+pub(crate) const WAY_FEATURE_DATA_BLOCKS_BYTE: u8 = 0x08;
+pub(crate) const WAY_FEATURE_DOUBLE_DELTA_ENCODING: u8 = 0x04;
 
-pub fn process_ways<R: Read>(reader: &mut BufReader<R>, ways_on_query_zoomlevel: usize, way_tags: &Vec<String>, debug: bool) -> Result<Vec<Way>> {
-    let mut ways: Vec<Way> = Vec::with_capacity(ways_on_query_zoomlevel);
+impl Way {
+    // smallest bounding box containing all way nodes, None if the way has no nodes
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        let mut nodes = self
+            .coordinate_blocks
+            .iter()
+            .flatten()
+            .flat_map(|block| block.coordinates.iter());
 
-    for _ in 0..ways_on_query_zoomlevel {
-        if debug {
-            let mut sig = [0u8; 16];
-            reader.read_exact(&mut sig)?;
-            let way_sig = String::from_utf8_lossy(&sig).trim().to_string();
+        let first = nodes.next()?;
+        let init = BoundingBox {
+            min_lat: first.latitude,
+            min_lon: first.longitude,
+            max_lat: first.latitude,
+            max_lon: first.longitude,
+        };
 
-            println!("Way signature: {way_sig}");
-        } else {
-            println!("Skip way signature")
+        Some(nodes.fold(init, |bbox, node| BoundingBox {
+            min_lat: bbox.min_lat.min(node.latitude),
+            min_lon: bbox.min_lon.min(node.longitude),
+            max_lat: bbox.max_lat.max(node.latitude),
+            max_lon: bbox.max_lon.max(node.longitude),
+        }))
+    }
+}
+
+impl WayCoordinateBlock {
+    // a block of absolute coordinates, starting at its first node
+    pub fn new(coordinates: Vec<LatLong>) -> Self {
+        WayCoordinateBlock {
+            initial_position: coordinates.first().copied().unwrap_or_default(),
+            coordinates,
+        }
+    }
+}
+
+impl WayRef<'_> {
+    // copies the borrowed strings into a Way
+    pub fn into_owned(self) -> Way {
+        Way {
+            debug_signature: self.debug_signature.map(Cow::into_owned),
+            sub_tile_bitmap: self.sub_tile_bitmap,
+            layer: self.layer,
+            tag_ids: self.tag_ids,
+            tags: Some(self.tags.into_iter().map(Tag::from).collect()),
+            name: self.name.map(Cow::into_owned),
+            house_number: self.house_number.map(Cow::into_owned),
+            reference: self.reference.map(Cow::into_owned),
+            label_position: self.label_position,
+            coordinate_blocks: self.coordinate_blocks,
+            double_delta_encoding: self.double_delta_encoding,
         }
+    }
+}
+
+// ways whose sub-tile bitmap doesn't overlap tile_bitmask are skipped, None reads all of them
+pub fn process_ways<R: Read + Seek>(reader: &mut R, ways_on_query_zoomlevel: usize, way_tags: &[String], tile: &TileContext, tile_bitmask: Option<u16>) -> Result<Vec<Way>> {
+    let ways = decode_ways(&mut CopiedStrings(reader), ways_on_query_zoomlevel, way_tags, tile, tile_bitmask)?;
 
-        let mut way: Way = Default::default();
+    Ok(ways.into_iter().map(WayRef::into_owned).collect())
+}
+
+// process_ways with the strings borrowed from the reader if it lends them
+pub(crate) fn decode_ways<'a>(reader: &mut impl BlockRead<'a>, ways_on_query_zoomlevel: usize, way_tags: &'a [String], tile: &TileContext, tile_bitmask: Option<u16>) -> Result<Vec<WayRef<'a>>> {
+    let mut ways: Vec<WayRef> = vec![];
+
+    for index in 0..ways_on_query_zoomlevel {
+        let debug_signature = if tile.debug {
+            let signature = reader.read_debug_signature()?;
+            trace!("tile {tile} way {index}: signature {signature}");
+            if !signature.starts_with(WAY_SIGNATURE_PREFIX) {
+                return Err(MapforgeError::InvalidWaySignature(tile.x, tile.y, index, signature.into_owned()));
+            }
+            Some(signature)
+        } else {
+            None
+        };
+
+        let mut way = WayRef {
+            debug_signature,
+            ..Default::default()
+        };
     
         let way_data_size = utils::read_vbe_u_int(reader)?;
-        println!("way_data_size: {way_data_size}");
-
-        println!("TODO: check if BigEndian!");
         let sub_tile_bitmap = reader.read_u16::<byteorder::BigEndian>()?;
-        println!("sub_tile_bitmap: {sub_tile_bitmap:016b}");
+        trace!("tile {tile} way {index}: {way_data_size} bytes, sub-tile bitmap {sub_tile_bitmap:016b}");
+
+        if tile_bitmask.is_some_and(|mask| sub_tile_bitmap & mask == 0) {
+            trace!("tile {tile} way {index}: outside of the requested sub-tile, skipped");
+            // the way data size includes the sub-tile bitmap that has been read already
+            reader.seek_relative(way_data_size as i64 - 2)?;
+            continue;
+        }
         way.sub_tile_bitmap = sub_tile_bitmap;
 
         let special_byte = reader.read_u8()?;   
         way.layer = ((special_byte & WAY_LAYER_BITMASK) >> WAY_LAYER_SHIFT) as i8;
         let number_of_tags = special_byte & WAY_NUMBER_OF_TAGS_BITMASK;
 
-        way.tag_ids = Vec::with_capacity(number_of_tags as usize);
-        let mut tags: Vec<Tag> = Vec::with_capacity(number_of_tags as usize);
-        for _ in 1..number_of_tags {
-            let tag_id = utils::read_vbe_u_int(reader)?;
-            if tag_id < way_tags.len() {
-                let tag_str = way_tags[tag_id].clone();
-                let kv: Vec<&str> = tag_str.split_terminator(tag::KEY_VALUE_SEPERATOR).collect();
-                let tag = Tag::new(kv[0], kv[1]);
-                way.tag_ids.push(tag_id);
-                tags.push(tag);
-            } else {
-                panic!("tag_id > list of tags!");
-            }
-        }
+        let (tag_ids, mut tags) = tag::read_tag_refs(reader, way_tags, number_of_tags)?;
+        way.tag_ids = tag_ids;
 
         let flags = reader.read_u8()?;
-        println!("flags: {flags:08b}");
+        trace!("tile {tile} way {index}: layer {}, tags {:?}, flags {flags:08b}", way.layer, way.tag_ids);
         
         if flags & WAY_FEATURE_NAME != 0 {
             // 1. bit: flag for existence of a way name as a string.
-            let name = utils::read_vbe_u(reader)?;
-            tags.push(Tag::new(TAG_KEY_NAME, &name));
-            println!("name {name}");
+            let name = reader.read_str()?;
+            tags.push(TagRef { key: TAG_KEY_NAME, value: name.clone() });
+            way.name = Some(name);
         }
         
         if flags & WAY_FEATURE_HOUSE_NUMBER != 0 {
             // 2. bit: flag for existence of a house number as a string.
-            let house_number = utils::read_vbe_u(reader)?;
-            tags.push(Tag::new(TAG_KEY_HOUSE_NUMBER, &house_number));
-            println!("house number {house_number}");
+            let house_number = reader.read_str()?;
+            tags.push(TagRef { key: TAG_KEY_HOUSE_NUMBER, value: house_number.clone() });
+            way.house_number = Some(house_number);
         }
         
         if flags & WAY_FEATURE_REF != 0 {
             // 3. bit: flag for existence of a reference as a string
-            let ref_str = utils::read_vbe_u(reader)?;
-            tags.push(Tag::new(TAG_KEY_REF, &ref_str));
-            println!("reference {ref_str}");
+            let ref_str = reader.read_str()?;
+            tags.push(TagRef { key: TAG_KEY_REF, value: ref_str.clone() });
+            way.reference = Some(ref_str);
         }
 
-        way.tags = Some(tags);
+        way.tags = tags;
         
         if flags & WAY_FEATURE_LABEL_POSITION != 0 {
             // 4. bit: flag for existence of a label position
             //         geo coordinate difference to the first way node in 
             //         microdegrees as 2 × VBE-S INT, in the order lat-diff,
-            //         lon-diff.
+            //         lon-diff. Made absolute once the first way node is known.
             way.label_position = Some(LatLong {
                 latitude: utils::read_microdegrees(reader)?,
                 longitude: utils::read_microdegrees(reader)?
@@ -97,66 +167,74 @@ pub fn process_ways<R: Read>(reader: &mut BufReader<R>, ways_on_query_zoomlevel:
         // 5. bit: flag for existence of number of way data blocks field
         let number_of_way_data_blocks = if flags & WAY_FEATURE_DATA_BLOCKS_BYTE != 0 {
             //     case 1: field exists, more than one block
-            println!("Read number of way data blocks from file");
             utils::read_vbe_u_int(reader)?
         } else {
             //     case 0: field does not exist, number of blocks is one
             1
         };
-        println!("number of way data blocks {number_of_way_data_blocks}");
         
         // 6. bit: flag indicating encoding of way coordinate blocks
         let way_coordinate_single_delta_encoding = if flags & WAY_FEATURE_DOUBLE_DELTA_ENCODING != 0 {
             //     case 1: double delta encoding
-            println!("use double delta encoding");
+            way.double_delta_encoding = true;
             false
         } else {
             //     case 0: single delta encoding
-            println!("use singe delta encoding");
             true
         };
         // 7.-8. bit: reserved for future use
         
-        // read way data blocks
+        // read way data blocks, each one is a line or polygon of its own
         for i in 0..number_of_way_data_blocks {
-            println!("Way data block {i}");
             let num_way_coordinates = utils::read_vbe_u_int(reader)?;
+            trace!("tile {tile} way {index}: data block {i} of {number_of_way_data_blocks} has {num_way_coordinates} coordinate blocks");
             if num_way_coordinates == 0 {
-                println!("skip empty data_block");
                 continue;
             }
-            println!("  number of way coordinate blocks {num_way_coordinates}");
-            let num_way_nodes = utils::read_vbe_u_int(reader)?;
+            // the first coordinate block is the way itself or the outer ring of a
+            // polygon, the following ones are its inner rings
+            let mut data_block = vec![];
             for _ in 0..num_way_coordinates {
+                let num_way_nodes = utils::read_vbe_u_int(reader)?;
                 let way_coordinate_block = if way_coordinate_single_delta_encoding {
-                    decode_way_nodes_single_delta(reader, num_way_nodes)?
+                    decode_way_nodes_single_delta(reader, num_way_nodes, tile.origin)?
                 } else {
-                    decode_way_nodes_double_delta(reader, num_way_nodes)?
-                };        
-                println!("TODO: way nodes coordinates as degrees from tile top/left {way_coordinate_block:?}");
+                    decode_way_nodes_double_delta(reader, num_way_nodes, tile.origin)?
+                };
+                data_block.push(way_coordinate_block);
             }
+            way.coordinate_blocks.push(data_block);
+        }
+
+        if let (Some(label_offset), Some(first_block)) = (way.label_position, way.coordinate_blocks.first().and_then(|blocks| blocks.first())) {
+            way.label_position = Some(LatLong {
+                latitude: first_block.initial_position.latitude + label_offset.latitude,
+                longitude: first_block.initial_position.longitude + label_offset.longitude
+            });
         }
+
         ways.push(way);
     }
 
     Ok(ways)
 }
 
-fn decode_way_nodes_double_delta<R: Read>(reader: &mut BufReader<R>, num_way_nodes: usize) -> Result<WayCoordinateBlock> {
+// the first way node is stored relative to the top left corner of the tile, every
+// following one as the difference to the previous node's difference
+fn decode_way_nodes_double_delta<R: Read + Seek>(reader: &mut R, num_way_nodes: usize, tile_origin: LatLong) -> Result<WayCoordinateBlock> {
     let mut way_coordinate_block: WayCoordinateBlock = Default::default();
-    
-    way_coordinate_block.coordinates.reserve(num_way_nodes);
-    let mut way_node_latitude = utils::read_microdegrees(reader)?;
-    let mut way_node_longitude = utils::read_microdegrees(reader)?;
-    way_coordinate_block.initial_position = LatLong {
-        latitude: way_node_latitude,
-        longitude: way_node_longitude
-    };
-    let previous_single_delta_latitude = 0f64;
-    let previous_single_delta_longitude = 0f64;
+
+    // accumulate in microdegrees so rounding errors don't add up along the way
+    let mut way_node_latitude = utils::read_vbe_s_int(reader)?;
+    let mut way_node_longitude = utils::read_vbe_s_int(reader)?;
+    way_coordinate_block.initial_position = way_node_position(tile_origin, way_node_latitude, way_node_longitude);
+    way_coordinate_block.coordinates.push(way_coordinate_block.initial_position);
+
+    let mut previous_single_delta_latitude = 0isize;
+    let mut previous_single_delta_longitude = 0isize;
     for _ in 1..num_way_nodes {
-        let double_delta_latitude = utils::read_microdegrees(reader)?;
-        let double_delta_longitude = utils::read_microdegrees(reader)?;
+        let double_delta_latitude = utils::read_vbe_s_int(reader)?;
+        let double_delta_longitude = utils::read_vbe_s_int(reader)?;
 
         let single_delta_latitude = double_delta_latitude + previous_single_delta_latitude;
         let single_delta_longitude = double_delta_longitude + previous_single_delta_longitude;
@@ -164,40 +242,50 @@ fn decode_way_nodes_double_delta<R: Read>(reader: &mut BufReader<R>, num_way_nod
         way_node_latitude += single_delta_latitude;
         way_node_longitude += single_delta_longitude;
 
-        // Decoding near international date line can return values slightly outside valid [-180°, 180°] due to calculation precision
-        if way_node_longitude < utils::LONGITUDE_MIN
-            && (utils::LONGITUDE_MIN - way_node_longitude) < 0.001 {
-        way_node_longitude = utils::LONGITUDE_MIN;
-        } else if way_node_longitude > utils::LONGITUDE_MAX
-                && (way_node_longitude - utils::LONGITUDE_MAX) < 0.001 {
-            way_node_longitude = utils::LONGITUDE_MAX;
-        }
-        way_coordinate_block.coordinates.push(LatLong { 
-            latitude: way_node_latitude, 
-            longitude: way_node_longitude 
-        });
+        previous_single_delta_latitude = single_delta_latitude;
+        previous_single_delta_longitude = single_delta_longitude;
+
+        way_coordinate_block.coordinates.push(way_node_position(tile_origin, way_node_latitude, way_node_longitude));
     }
 
     Ok(way_coordinate_block)
 }
 
-fn decode_way_nodes_single_delta<R: Read>(reader: &mut BufReader<R>, num_way_nodes: usize) -> Result<WayCoordinateBlock> {
+// the first way node is stored relative to the top left corner of the tile, every
+// following one as the difference to the previous node
+fn decode_way_nodes_single_delta<R: Read + Seek>(reader: &mut R, num_way_nodes: usize, tile_origin: LatLong) -> Result<WayCoordinateBlock> {
     let mut way_coordinate_block: WayCoordinateBlock = Default::default();
 
-    way_coordinate_block.initial_position = LatLong {
-        latitude: utils::read_microdegrees(reader)?,
-        longitude: utils::read_microdegrees(reader)?
-    };
+    let mut way_node_latitude = utils::read_vbe_s_int(reader)?;
+    let mut way_node_longitude = utils::read_vbe_s_int(reader)?;
+    way_coordinate_block.initial_position = way_node_position(tile_origin, way_node_latitude, way_node_longitude);
     way_coordinate_block.coordinates.push(way_coordinate_block.initial_position);
+
     for _ in 1..num_way_nodes {
-        // geo coordinates of the remaining way nodes stored as differences to the previous 
-        // way node in microdegrees as 2 × VBE-S INT in the order lat-diff, lon-diff using 
-        // either single or double delta encoding (see below).
-        way_coordinate_block.coordinates.push(LatLong {
-            latitude: utils::read_microdegrees(reader)?,
-            longitude: utils::read_microdegrees(reader)?
-        });
+        way_node_latitude += utils::read_vbe_s_int(reader)?;
+        way_node_longitude += utils::read_vbe_s_int(reader)?;
+
+        way_coordinate_block.coordinates.push(way_node_position(tile_origin, way_node_latitude, way_node_longitude));
     }
 
     Ok(way_coordinate_block)
 }
+
+// absolute position of a way node given in microdegrees from the tile origin
+fn way_node_position(tile_origin: LatLong, latitude: isize, longitude: isize) -> LatLong {
+    let mut way_node_longitude = tile_origin.longitude + utils::microdegrees_to_degrees(longitude);
+
+    // Decoding near international date line can return values slightly outside valid [-180°, 180°] due to calculation precision
+    if way_node_longitude < utils::LONGITUDE_MIN
+        && (utils::LONGITUDE_MIN - way_node_longitude) < 0.001 {
+        way_node_longitude = utils::LONGITUDE_MIN;
+    } else if way_node_longitude > utils::LONGITUDE_MAX
+            && (way_node_longitude - utils::LONGITUDE_MAX) < 0.001 {
+        way_node_longitude = utils::LONGITUDE_MAX;
+    }
+
+    LatLong {
+        latitude: tile_origin.latitude + utils::microdegrees_to_degrees(latitude),
+        longitude: way_node_longitude
+    }
+}
@@ -0,0 +1,130 @@
+use crate::types::{Geometry, LatLong, Way, POI};
+
+// WKB geometry types of the simple features specification
+const WKB_POINT: u32 = 1;
+const WKB_LINESTRING: u32 = 2;
+const WKB_POLYGON: u32 = 3;
+const WKB_MULTILINESTRING: u32 = 5;
+const WKB_MULTIPOLYGON: u32 = 6;
+const WKB_LITTLE_ENDIAN: u8 = 1;
+
+impl Geometry {
+    // well-known text with longitude as x and latitude as y
+    pub fn to_wkt(&self) -> String {
+        match self {
+            Geometry::Point(position) => format!("POINT ({})", wkt_position(position)),
+            Geometry::LineString(line) => format!("LINESTRING {}", wkt_positions(line)),
+            Geometry::MultiLineString(lines) => format!("MULTILINESTRING {}", wkt_list(lines, |line| wkt_positions(line))),
+            Geometry::Polygon(rings) => format!("POLYGON {}", wkt_rings(rings)),
+            Geometry::MultiPolygon(polygons) => format!("MULTIPOLYGON {}", wkt_list(polygons, |rings| wkt_rings(rings))),
+        }
+    }
+
+    // little endian well-known binary with longitude as x and latitude as y
+    pub fn to_wkb(&self) -> Vec<u8> {
+        let mut wkb = vec![];
+        write_wkb(&mut wkb, self);
+        wkb
+    }
+}
+
+impl POI {
+    pub fn to_wkt(&self) -> String {
+        self.geometry().to_wkt()
+    }
+
+    pub fn to_wkb(&self) -> Vec<u8> {
+        self.geometry().to_wkb()
+    }
+}
+
+impl Way {
+    // see Way::geometry for which coordinate blocks become what geometry
+    pub fn to_wkt(&self) -> String {
+        self.geometry().to_wkt()
+    }
+
+    pub fn to_wkb(&self) -> Vec<u8> {
+        self.geometry().to_wkb()
+    }
+}
+
+fn wkt_position(position: &LatLong) -> String {
+    format!("{} {}", position.longitude, position.latitude)
+}
+
+fn wkt_positions(positions: &[LatLong]) -> String {
+    wkt_list(positions, wkt_position)
+}
+
+fn wkt_rings(rings: &[Vec<LatLong>]) -> String {
+    wkt_list(rings, |ring| wkt_positions(ring))
+}
+
+// the items in parentheses, EMPTY if there are none
+fn wkt_list<T>(items: &[T], item_to_wkt: impl Fn(&T) -> String) -> String {
+    if items.is_empty() {
+        return "EMPTY".to_string();
+    }
+
+    let items: Vec<String> = items.iter().map(item_to_wkt).collect();
+    format!("({})", items.join(", "))
+}
+
+fn write_wkb(wkb: &mut Vec<u8>, geometry: &Geometry) {
+    match geometry {
+        Geometry::Point(position) => {
+            write_wkb_type(wkb, WKB_POINT);
+            write_wkb_position(wkb, position);
+        }
+        Geometry::LineString(line) => {
+            write_wkb_type(wkb, WKB_LINESTRING);
+            write_wkb_positions(wkb, line);
+        }
+        Geometry::Polygon(rings) => {
+            write_wkb_type(wkb, WKB_POLYGON);
+            write_wkb_rings(wkb, rings);
+        }
+        // the parts of multi geometries are geometries of their own
+        Geometry::MultiLineString(lines) => {
+            write_wkb_type(wkb, WKB_MULTILINESTRING);
+            wkb.extend((lines.len() as u32).to_le_bytes());
+            for line in lines {
+                write_wkb_type(wkb, WKB_LINESTRING);
+                write_wkb_positions(wkb, line);
+            }
+        }
+        Geometry::MultiPolygon(polygons) => {
+            write_wkb_type(wkb, WKB_MULTIPOLYGON);
+            wkb.extend((polygons.len() as u32).to_le_bytes());
+            for rings in polygons {
+                write_wkb_type(wkb, WKB_POLYGON);
+                write_wkb_rings(wkb, rings);
+            }
+        }
+    }
+}
+
+fn write_wkb_type(wkb: &mut Vec<u8>, geometry_type: u32) {
+    wkb.push(WKB_LITTLE_ENDIAN);
+    wkb.extend(geometry_type.to_le_bytes());
+}
+
+fn write_wkb_position(wkb: &mut Vec<u8>, position: &LatLong) {
+    wkb.extend(position.longitude.to_le_bytes());
+    wkb.extend(position.latitude.to_le_bytes());
+}
+
+fn write_wkb_positions(wkb: &mut Vec<u8>, positions: &[LatLong]) {
+    wkb.extend((positions.len() as u32).to_le_bytes());
+    for position in positions {
+        write_wkb_position(wkb, position);
+    }
+}
+
+fn write_wkb_rings(wkb: &mut Vec<u8>, rings: &[Vec<LatLong>]) {
+    wkb.extend((rings.len() as u32).to_le_bytes());
+    for ring in rings {
+        write_wkb_positions(wkb, ring);
+    }
+}
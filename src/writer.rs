@@ -0,0 +1,556 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use byteorder::{BigEndian, WriteBytesExt};
+
+use crate::{
+    blocks::TILE_SIGNATURE_PREFIX,
+    clip,
+    error::MapforgeError,
+    header::{
+        COMMENT_MASK, CREATED_BY_MASK, DEBUG_INFO_MASK, LANGUAGE_PREFERENCE_MASK, MAGIC_BYTES,
+        MAP_START_POSITION_MASK, START_ZOOM_LEVEL_MASK,
+    },
+    poi::{
+        POI_FEATURE_ELEVATION, POI_FEATURE_HOUSE_NUMBER, POI_FEATURE_NAME, POI_LAYER_SHIFT,
+        POI_NUMBER_OF_TAGS_BITMASK, POI_SIGNATURE_PREFIX,
+    },
+    tag::{KEY_VALUE_SEPERATOR, TAG_KEY_HOUSE_NUMBER, TAG_KEY_NAME, TAG_KEY_REF},
+    tile::{INDEX_SIGNATURE, TILE_INDEX_ENTRY_SIZE},
+    types::{BoundingBox, LatLong, MapFile, MapHeader, MapWriter, Tag, TileContext, Way, ZoomInterval, POI},
+    utils,
+    way::{
+        WAY_FEATURE_DATA_BLOCKS_BYTE, WAY_FEATURE_DOUBLE_DELTA_ENCODING, WAY_FEATURE_HOUSE_NUMBER, WAY_FEATURE_LABEL_POSITION,
+        WAY_FEATURE_NAME, WAY_FEATURE_REF, WAY_LAYER_SHIFT, WAY_NUMBER_OF_TAGS_BITMASK,
+        WAY_SIGNATURE_PREFIX,
+    },
+    Result,
+};
+
+const FILE_VERSION: u32 = 5;
+const TILE_SIZE: u16 = 256;
+const PROJECTION: &str = "Mercator";
+const DEBUG_SIGNATURE_SIZE: usize = 32;
+// clipped ways reach this share of the tile size beyond the tile border, so
+// that lines drawn at the border don't end in a gap
+const CLIP_BUFFER: f64 = 1.0 / 32.0;
+
+impl ZoomInterval {
+    // a zoom interval whose sub-file position is filled in when the map is written
+    pub fn new(base_zoom_level: u8, min_zoom_level: u8, max_zoom_level: u8) -> Self {
+        ZoomInterval {
+            base_zoom_level,
+            min_zoom_level,
+            max_zoom_level,
+            sub_file_start: 0,
+            sub_file_size: 0,
+        }
+    }
+}
+
+// the features of one tile block, each with the row of the zoom table it is
+// counted in, ways also with the sub-tiles they touch
+#[derive(Default)]
+struct TileFeatures<'a> {
+    pois: Vec<(usize, &'a POI)>,
+    ways: Vec<(usize, Cow<'a, Way>, u16)>,
+}
+
+impl MapWriter {
+    pub fn new(bounding_box: BoundingBox, zoom_intervals: Vec<ZoomInterval>) -> Self {
+        let creation_date = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_millis() as u64);
+
+        MapWriter {
+            header: MapHeader {
+                magic: MAGIC_BYTES.to_string(),
+                header_size: 0,
+                file_version: FILE_VERSION,
+                file_size: 0,
+                creation_date,
+                bounding_box,
+                tile_size: TILE_SIZE,
+                projection: PROJECTION.to_string(),
+                flags: 0,
+                map_start_position: None,
+                start_zoom_level: None,
+                language_preference: None,
+                comment: None,
+                created_by: None,
+                poi_tags: vec![],
+                way_tags: vec![],
+                num_zoom_intervals: zoom_intervals.len() as u8,
+                zoom_interval_configuration: zoom_intervals,
+            },
+            pois: vec![],
+            ways: vec![],
+            clip_ways: false,
+        }
+    }
+
+    // the POI is stored at its absolute position and shown from zoom_level on
+    pub fn add_poi(&mut self, poi: POI, zoom_level: u8) {
+        self.pois.push((poi, zoom_level));
+    }
+
+    // the way is stored with one data block per line or polygon, and shown from
+    // zoom_level on
+    pub fn add_way(&mut self, way: Way, zoom_level: u8) {
+        self.ways.push((way, zoom_level));
+    }
+
+    pub fn write_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    // writes the header followed by one sub-file per zoom interval
+    // the tag tables are built from the tags of the features, the flags for
+    // optional header fields from the fields that are set, only the debug flag
+    // is taken from the header
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let mut header = self.header.clone();
+
+        header.poi_tags = tag_table(self.pois.iter().map(|(poi, _)| poi_tags(poi)));
+        header.way_tags = tag_table(self.ways.iter().map(|(way, _)| way_tags(way)));
+        header.flags = optional_field_flags(&header);
+        header.num_zoom_intervals = header.zoom_interval_configuration.len() as u8;
+
+        let poi_tag_ids = tag_ids(&header.poi_tags);
+        let way_tag_ids = tag_ids(&header.way_tags);
+
+        let sub_files = header
+            .zoom_interval_configuration
+            .iter()
+            .map(|interval| self.sub_file(&header, interval, &poi_tag_ids, &way_tag_ids))
+            .collect::<Result<Vec<_>>>()?;
+
+        // sub-file positions are fixed size fields, so the size of the header
+        // doesn't depend on them
+        let mut header_bytes = vec![];
+        header.write_to(&mut header_bytes)?;
+
+        let mut position = header_bytes.len() as u64;
+        for (interval, sub_file) in header.zoom_interval_configuration.iter_mut().zip(&sub_files) {
+            interval.sub_file_start = position;
+            interval.sub_file_size = sub_file.len() as u64;
+            position += sub_file.len() as u64;
+        }
+        header.file_size = position;
+
+        header.write_to(writer)?;
+        for sub_file in &sub_files {
+            writer.write_all(sub_file)?;
+        }
+
+        Ok(())
+    }
+
+    // the tile index followed by the tile blocks of all tiles on the base zoom
+    // level of the interval that are covered by the map
+    fn sub_file(&self, header: &MapHeader, interval: &ZoomInterval, poi_tag_ids: &HashMap<String, usize>, way_tag_ids: &HashMap<String, usize>) -> Result<Vec<u8>> {
+        let debug = header.flags & DEBUG_INFO_MASK != 0;
+        let base_zoom_level = interval.base_zoom_level;
+
+        let (x_min, y_min, x_max, y_max) = MapFile::tile_range(&header.bounding_box, base_zoom_level);
+        let row_width = (x_max - x_min + 1) as usize;
+        let tile_index = |x: u32, y: u32| (y - y_min) as usize * row_width + (x - x_min) as usize;
+
+        // only tiles with features are kept, most tiles of a large map are empty
+        let total_tiles = MapFile::calculate_total_tiles(&header.bounding_box, base_zoom_level)? as usize;
+        let mut tiles: HashMap<usize, TileFeatures> = HashMap::new();
+
+        for (poi, zoom_level) in &self.pois {
+            let Some(row) = zoom_table_row(interval, *zoom_level) else {
+                continue;
+            };
+
+            let Ok((x, y)) = MapFile::get_tiles(poi.position.latitude, poi.position.longitude, base_zoom_level) else {
+                continue;
+            };
+            if (x_min..=x_max).contains(&x) && (y_min..=y_max).contains(&y) {
+                tiles.entry(tile_index(x, y)).or_default().pois.push((row, poi));
+            }
+        }
+
+        for (way, zoom_level) in &self.ways {
+            let (Some(row), Some(bbox)) = (zoom_table_row(interval, *zoom_level), way.bounding_box()) else {
+                continue;
+            };
+
+            let (from_x, from_y, to_x, to_y) = MapFile::tile_range(&bbox, base_zoom_level);
+            for y in from_y.max(y_min)..=to_y.min(y_max) {
+                for x in from_x.max(x_min)..=to_x.min(x_max) {
+                    let clip_bbox = clip_bounds(base_zoom_level, x, y);
+                    let piece = if self.clip_ways && !clip_bbox.contains_box(&bbox) {
+                        clip::clip_way(way, &clip_bbox).map(Cow::Owned)
+                    } else {
+                        Some(Cow::Borrowed(way))
+                    };
+
+                    let Some((piece, piece_bbox)) = piece.and_then(|piece| piece.bounding_box().map(|bbox| (piece, bbox))) else {
+                        continue;
+                    };
+                    let sub_tile_bitmap = sub_tile_bitmap(&piece_bbox, base_zoom_level, x, y);
+                    if sub_tile_bitmap != 0 {
+                        tiles.entry(tile_index(x, y)).or_default().ways.push((row, piece, sub_tile_bitmap));
+                    }
+                }
+            }
+        }
+
+        let mut index = vec![];
+        if debug {
+            index.extend(INDEX_SIGNATURE.as_bytes());
+        }
+        let index_size = (index.len() + total_tiles * TILE_INDEX_ENTRY_SIZE) as u64;
+
+        let mut blocks = vec![];
+        for position in 0..total_tiles {
+            let offset = index_size + blocks.len() as u64;
+            index.extend(&offset.to_be_bytes()[8 - TILE_INDEX_ENTRY_SIZE..]);
+
+            let Some(features) = tiles.get_mut(&position) else {
+                continue;
+            };
+
+            let x = x_min + (position % row_width) as u32;
+            let y = y_min + (position / row_width) as u32;
+            let tile = TileContext::new(base_zoom_level, x, y, debug);
+            write_tile_block(&mut blocks, interval, &tile, features, poi_tag_ids, way_tag_ids)?;
+        }
+
+        index.extend(blocks);
+
+        Ok(index)
+    }
+}
+
+// features are sorted by the row of the zoom table they are counted in, so a
+// reader can stop after the features of its zoom level
+fn write_tile_block(out: &mut Vec<u8>, interval: &ZoomInterval, tile: &TileContext, features: &mut TileFeatures, poi_tag_ids: &HashMap<String, usize>, way_tag_ids: &HashMap<String, usize>) -> Result<()> {
+    features.pois.sort_by_key(|&(row, _)| row);
+    features.ways.sort_by_key(|&(row, _, _)| row);
+
+    if tile.debug {
+        write_debug_signature(out, &format!("{TILE_SIGNATURE_PREFIX}{},{}", tile.x, tile.y))?;
+    }
+
+    let rows = (interval.max_zoom_level - interval.min_zoom_level + 1) as usize;
+    let mut zoom_table = vec![(0, 0); rows];
+    for &(row, _) in &features.pois {
+        zoom_table[row].0 += 1;
+    }
+    for &(row, _, _) in &features.ways {
+        zoom_table[row].1 += 1;
+    }
+    for (pois, ways) in zoom_table {
+        utils::write_vbe_u_int(out, pois)?;
+        utils::write_vbe_u_int(out, ways)?;
+    }
+
+    let mut poi_section = vec![];
+    for (index, &(_, poi)) in features.pois.iter().enumerate() {
+        write_poi(&mut poi_section, poi, index, tile, poi_tag_ids)?;
+    }
+
+    // the first way offset is the size of the POI section
+    utils::write_vbe_u_int(out, poi_section.len())?;
+    out.extend(poi_section);
+
+    for (index, (_, way, sub_tile_bitmap)) in features.ways.iter().enumerate() {
+        write_way(out, way, index, *sub_tile_bitmap, tile, way_tag_ids)?;
+    }
+
+    Ok(())
+}
+
+fn write_poi(out: &mut Vec<u8>, poi: &POI, index: usize, tile: &TileContext, tag_ids: &HashMap<String, usize>) -> Result<()> {
+    if tile.debug {
+        write_debug_signature(out, &format!("{POI_SIGNATURE_PREFIX}{index}"))?;
+    }
+
+    // position relative to the top left corner of the tile
+    utils::write_vbe_s_int(out, utils::degrees_to_microdegrees(poi.position.latitude - tile.origin.latitude))?;
+    utils::write_vbe_s_int(out, utils::degrees_to_microdegrees(poi.position.longitude - tile.origin.longitude))?;
+
+    let tags = poi_tags(poi);
+    out.write_u8(special_byte(poi.layer, POI_LAYER_SHIFT, tags.len(), POI_NUMBER_OF_TAGS_BITMASK)?)?;
+    for tag in &tags {
+        utils::write_vbe_u_int(out, tag_ids[tag])?;
+    }
+
+    let mut flags = 0;
+    if poi.name.is_some() {
+        flags |= POI_FEATURE_NAME;
+    }
+    if poi.house_number.is_some() {
+        flags |= POI_FEATURE_HOUSE_NUMBER;
+    }
+    if poi.elevation.is_some() {
+        flags |= POI_FEATURE_ELEVATION;
+    }
+    out.write_u8(flags)?;
+
+    for value in [&poi.name, &poi.house_number].into_iter().flatten() {
+        utils::write_string(out, value)?;
+    }
+    if let Some(elevation) = poi.elevation {
+        utils::write_vbe_s_int(out, elevation as isize)?;
+    }
+
+    Ok(())
+}
+
+fn write_way(out: &mut Vec<u8>, way: &Way, index: usize, sub_tile_bitmap: u16, tile: &TileContext, tag_ids: &HashMap<String, usize>) -> Result<()> {
+    if tile.debug {
+        write_debug_signature(out, &format!("{WAY_SIGNATURE_PREFIX}{index}"))?;
+    }
+
+    // everything after the way data size
+    let mut data = vec![];
+    data.write_u16::<BigEndian>(sub_tile_bitmap)?;
+
+    let tags = way_tags(way);
+    data.write_u8(special_byte(way.layer, WAY_LAYER_SHIFT, tags.len(), WAY_NUMBER_OF_TAGS_BITMASK)?)?;
+    for tag in &tags {
+        utils::write_vbe_u_int(&mut data, tag_ids[tag])?;
+    }
+
+    let first_node = way.coordinate_blocks.iter().flatten().next().map(|block| block.initial_position);
+    let label_position = way.label_position.zip(first_node);
+
+    let mut flags = 0;
+    if way.name.is_some() {
+        flags |= WAY_FEATURE_NAME;
+    }
+    if way.house_number.is_some() {
+        flags |= WAY_FEATURE_HOUSE_NUMBER;
+    }
+    if way.reference.is_some() {
+        flags |= WAY_FEATURE_REF;
+    }
+    if label_position.is_some() {
+        flags |= WAY_FEATURE_LABEL_POSITION;
+    }
+    if way.coordinate_blocks.len() > 1 {
+        flags |= WAY_FEATURE_DATA_BLOCKS_BYTE;
+    }
+    if way.double_delta_encoding {
+        flags |= WAY_FEATURE_DOUBLE_DELTA_ENCODING;
+    }
+    data.write_u8(flags)?;
+
+    for value in [&way.name, &way.house_number, &way.reference].into_iter().flatten() {
+        utils::write_string(&mut data, value)?;
+    }
+
+    // the label position is stored relative to the first way node
+    if let Some((label, first_node)) = label_position {
+        utils::write_vbe_s_int(&mut data, utils::degrees_to_microdegrees(label.latitude - first_node.latitude))?;
+        utils::write_vbe_s_int(&mut data, utils::degrees_to_microdegrees(label.longitude - first_node.longitude))?;
+    }
+
+    if way.coordinate_blocks.len() > 1 {
+        utils::write_vbe_u_int(&mut data, way.coordinate_blocks.len())?;
+    }
+    for blocks in &way.coordinate_blocks {
+        utils::write_vbe_u_int(&mut data, blocks.len())?;
+        for block in blocks {
+            utils::write_vbe_u_int(&mut data, block.coordinates.len())?;
+            write_way_nodes(&mut data, &block.coordinates, tile.origin, way.double_delta_encoding)?;
+        }
+    }
+
+    utils::write_vbe_u_int(out, data.len())?;
+    out.extend(data);
+
+    Ok(())
+}
+
+// the first node relative to the tile origin, the following ones as the
+// difference to the previous node, or to the previous difference with double
+// delta encoding, all in microdegrees
+fn write_way_nodes(out: &mut Vec<u8>, nodes: &[LatLong], tile_origin: LatLong, double_delta: bool) -> Result<()> {
+    let mut previous = (0, 0);
+    let mut previous_delta = (0, 0);
+
+    for (index, node) in nodes.iter().enumerate() {
+        let position = (
+            utils::degrees_to_microdegrees(node.latitude - tile_origin.latitude),
+            utils::degrees_to_microdegrees(node.longitude - tile_origin.longitude),
+        );
+
+        let value = if index == 0 {
+            position
+        } else {
+            let delta = (position.0 - previous.0, position.1 - previous.1);
+            let value = if double_delta {
+                (delta.0 - previous_delta.0, delta.1 - previous_delta.1)
+            } else {
+                delta
+            };
+            previous_delta = delta;
+            value
+        };
+        previous = position;
+
+        utils::write_vbe_s_int(out, value.0)?;
+        utils::write_vbe_s_int(out, value.1)?;
+    }
+
+    Ok(())
+}
+
+// layer in the upper four bits, number of tags in the lower four
+fn special_byte(layer: i8, layer_shift: u8, number_of_tags: usize, tags_bitmask: u8) -> Result<u8> {
+    if number_of_tags > tags_bitmask as usize {
+        return Err(MapforgeError::TooManyFeatureTags(number_of_tags));
+    }
+    if !(0..=(!tags_bitmask >> layer_shift) as i8).contains(&layer) {
+        return Err(MapforgeError::InvalidLayer(layer));
+    }
+
+    Ok((layer as u8) << layer_shift | number_of_tags as u8)
+}
+
+// debug signatures are padded with spaces to 32 bytes
+fn write_debug_signature(out: &mut Vec<u8>, signature: &str) -> Result<()> {
+    let mut bytes = signature.as_bytes().to_vec();
+    bytes.resize(DEBUG_SIGNATURE_SIZE, b' ');
+    out.write_all(&bytes)?;
+
+    Ok(())
+}
+
+// row of the zoom table of the interval a feature is counted in, None if it
+// isn't visible on any zoom level of the interval
+fn zoom_table_row(interval: &ZoomInterval, zoom_level: u8) -> Option<usize> {
+    (zoom_level <= interval.max_zoom_level)
+        .then(|| (zoom_level.max(interval.min_zoom_level) - interval.min_zoom_level) as usize)
+}
+
+// the 4x4 sub-tiles of base tile (x, y) on base zoom level + 2 that the
+// bounding box touches, the first bit is the top left sub-tile
+fn sub_tile_bitmap(bbox: &BoundingBox, base_zoom_level: u8, x: u32, y: u32) -> u16 {
+    let zoom = base_zoom_level + 2;
+    let mut bitmap = 0;
+
+    for row in 0..4 {
+        for column in 0..4 {
+            let sub_tile_x = x * 4 + column;
+            let sub_tile_y = y * 4 + row;
+            let sub_tile = BoundingBox {
+                min_lat: MapFile::tiley2lat(sub_tile_y + 1, zoom),
+                min_lon: MapFile::tilex2long(sub_tile_x, zoom),
+                max_lat: MapFile::tiley2lat(sub_tile_y, zoom),
+                max_lon: MapFile::tilex2long(sub_tile_x + 1, zoom),
+            };
+
+            if sub_tile.intersects(bbox) {
+                bitmap |= 0x8000 >> (row * 4 + column);
+            }
+        }
+    }
+
+    bitmap
+}
+
+// the base tile (x, y) grown by the clip buffer on every side
+fn clip_bounds(base_zoom_level: u8, x: u32, y: u32) -> BoundingBox {
+    let min_lat = MapFile::tiley2lat(y + 1, base_zoom_level);
+    let max_lat = MapFile::tiley2lat(y, base_zoom_level);
+    let min_lon = MapFile::tilex2long(x, base_zoom_level);
+    let max_lon = MapFile::tilex2long(x + 1, base_zoom_level);
+    let lat_buffer = (max_lat - min_lat) * CLIP_BUFFER;
+    let lon_buffer = (max_lon - min_lon) * CLIP_BUFFER;
+
+    BoundingBox {
+        min_lat: min_lat - lat_buffer,
+        min_lon: min_lon - lon_buffer,
+        max_lat: max_lat + lat_buffer,
+        max_lon: max_lon + lon_buffer,
+    }
+}
+
+fn optional_field_flags(header: &MapHeader) -> u8 {
+    let fields = [
+        (MAP_START_POSITION_MASK, header.map_start_position.is_some()),
+        (START_ZOOM_LEVEL_MASK, header.start_zoom_level.is_some()),
+        (LANGUAGE_PREFERENCE_MASK, header.language_preference.is_some()),
+        (COMMENT_MASK, header.comment.is_some()),
+        (CREATED_BY_MASK, header.created_by.is_some()),
+    ];
+
+    fields
+        .iter()
+        .filter(|(_, is_set)| *is_set)
+        .fold(header.flags & DEBUG_INFO_MASK, |flags, (mask, _)| flags | mask)
+}
+
+// the tags of a POI that go into the tag table, name and house number are
+// stored as fields of the POI when they are set
+fn poi_tags(poi: &POI) -> Vec<String> {
+    let mut field_keys = vec![];
+    if poi.name.is_some() {
+        field_keys.push(TAG_KEY_NAME);
+    }
+    if poi.house_number.is_some() {
+        field_keys.push(TAG_KEY_HOUSE_NUMBER);
+    }
+
+    table_entries(poi.tags.as_deref(), &field_keys)
+}
+
+// the tags of a way that go into the tag table, name, house number and ref
+// are stored as fields of the way when they are set
+fn way_tags(way: &Way) -> Vec<String> {
+    let mut field_keys = vec![];
+    if way.name.is_some() {
+        field_keys.push(TAG_KEY_NAME);
+    }
+    if way.house_number.is_some() {
+        field_keys.push(TAG_KEY_HOUSE_NUMBER);
+    }
+    if way.reference.is_some() {
+        field_keys.push(TAG_KEY_REF);
+    }
+
+    table_entries(way.tags.as_deref(), &field_keys)
+}
+
+fn table_entries(tags: Option<&[Tag]>, field_keys: &[&str]) -> Vec<String> {
+    tags.unwrap_or_default()
+        .iter()
+        .filter(|tag| !field_keys.contains(&tag.key.as_str()))
+        .map(|tag| format!("{}{KEY_VALUE_SEPERATOR}{}", tag.key, tag.value))
+        .collect()
+}
+
+// every distinct tag once, the most frequent ones first so they get the
+// shortest ids
+fn tag_table(features: impl Iterator<Item = Vec<String>>) -> Vec<String> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for tag in features.flatten() {
+        *counts.entry(tag).or_default() += 1;
+    }
+
+    let mut table: Vec<(String, usize)> = counts.into_iter().collect();
+    table.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+
+    table.into_iter().map(|(tag, _)| tag).collect()
+}
+
+fn tag_ids(table: &[String]) -> HashMap<String, usize> {
+    table.iter().enumerate().map(|(id, tag)| (tag.clone(), id)).collect()
+}
@@ -0,0 +1,83 @@
+#![cfg(feature = "async")]
+
+mod common;
+
+use std::io::{Cursor, ErrorKind};
+
+use common::features_fixture;
+use mapsforge_rs::{
+    types::{AsyncMapFile, BoundingBox, MapFile},
+    MapforgeError, Result,
+};
+
+#[tokio::test]
+async fn test_async_read_tile() -> Result<()> {
+    let file = features_fixture(true).write();
+    let mut async_map = AsyncMapFile::open(file.path()).await?;
+    let mut map_file = MapFile::open(file.path())?;
+
+    assert_eq!(async_map.tile_indices[0].len(), 2);
+
+    for (zoom, x, y) in [(8, 137, 84), (10, 548, 336), (10, 549, 336), (12, 548 * 4, 336 * 4), (12, 548 * 4 + 3, 336 * 4)] {
+        let tile = async_map.read_tile(zoom, x, y).await?;
+        let expected = map_file.read_tile(zoom, x, y)?;
+        assert_eq!(format!("{tile:?}"), format!("{expected:?}"), "tile {zoom}/{x}/{y}");
+    }
+
+    let bbox = BoundingBox {
+        min_lat: 52.0,
+        min_lon: 12.0,
+        max_lat: 53.0,
+        max_lon: 14.0,
+    };
+    let result = async_map.query(bbox, 10).await?;
+    assert_eq!(format!("{result:?}"), format!("{:?}", map_file.query(bbox, 10)?));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_async_errors_keep_file_offsets() {
+    let mut data = features_fixture(true).build();
+
+    // make the name of the first POI invalid UTF-8
+    let name = data.windows(4).position(|window| window == b"west").unwrap();
+    data[name] = 0xff;
+
+    let mut async_map = AsyncMapFile::from_reader(Cursor::new(data.clone())).await.unwrap();
+    match async_map.read_tile(10, 548, 336).await {
+        Err(MapforgeError::InvalidUtf8(offset)) => assert_eq!(offset, name as u64),
+        other => panic!("unexpected result {other:?}"),
+    }
+
+    // a truncated map
+    data.truncate(data.len() - 10);
+    let mut async_map = AsyncMapFile::from_reader(Cursor::new(data)).await.unwrap();
+    assert!(matches!(async_map.read_tile(10, 549, 336).await, Err(MapforgeError::Io(_))));
+}
+
+#[tokio::test]
+async fn test_async_query_with_corrupt_sub_file_size() -> Result<()> {
+    let mut data = features_fixture(true).build();
+
+    // the last block of the query is read up to the end of the sub-file
+    let header_size = u32::from_be_bytes(data[20..24].try_into().unwrap()) as usize;
+    data[24 + header_size - 8..24 + header_size].copy_from_slice(&(u64::MAX / 2).to_be_bytes());
+
+    let mut async_map = AsyncMapFile::from_reader(Cursor::new(data)).await?;
+    let bbox = BoundingBox {
+        min_lat: 52.0,
+        min_lon: 12.0,
+        max_lat: 53.0,
+        max_lon: 14.0,
+    };
+    match async_map.query(bbox, 10).await {
+        Err(MapforgeError::Io(err)) => assert_eq!(err.kind(), ErrorKind::UnexpectedEof),
+        other => panic!("unexpected result {other:?}"),
+    }
+
+    // the blocks before it are still read
+    assert_eq!(async_map.read_tile(10, 548, 336).await?.pois.len(), 2);
+
+    Ok(())
+}
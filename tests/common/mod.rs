@@ -0,0 +1,390 @@
+// builders for hand-made map file fixtures, following the mapsforge binary
+// map file specification byte by byte
+#![allow(dead_code)]
+
+use std::io::Write;
+
+use mapsforge_rs::types::{BoundingBox, LatLong, MapWriter, Tag, Way, WayCoordinateBlock, ZoomInterval, POI};
+use tempfile::NamedTempFile;
+
+pub const MAGIC_BYTES: &[u8] = b"mapsforge binary OSM";
+
+pub fn vbe_u(mut value: u64) -> Vec<u8> {
+    let mut bytes = vec![];
+    while value > 0x7f {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+    bytes
+}
+
+pub fn vbe_s(value: i64) -> Vec<u8> {
+    let mut bytes = vec![];
+    let negative = value < 0;
+    let mut value = value.unsigned_abs();
+    while value > 0x3f {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8 | if negative { 0x40 } else { 0 });
+    bytes
+}
+
+pub fn vbe_string(value: &str) -> Vec<u8> {
+    let mut bytes = vbe_u(value.len() as u64);
+    bytes.extend(value.as_bytes());
+    bytes
+}
+
+// pads a debug signature with spaces to its fixed size of 32 bytes
+pub fn signature(value: &str) -> Vec<u8> {
+    let mut bytes = value.as_bytes().to_vec();
+    bytes.resize(32, b' ');
+    bytes
+}
+
+// a tile block: optional signature, zoom table, first way offset, POIs and ways
+pub fn tile_block(signature: Option<&str>, zoom_table: &[(u64, u64)], pois: &[Vec<u8>], ways: &[Vec<u8>]) -> Vec<u8> {
+    let mut bytes = vec![];
+    if let Some(sig) = signature {
+        bytes.extend(self::signature(sig));
+    }
+    for &(pois, ways) in zoom_table {
+        bytes.extend(vbe_u(pois));
+        bytes.extend(vbe_u(ways));
+    }
+    let poi_bytes: Vec<u8> = pois.concat();
+    bytes.extend(vbe_u(poi_bytes.len() as u64));
+    bytes.extend(poi_bytes);
+    bytes.extend(ways.concat());
+    bytes
+}
+
+#[derive(Default)]
+pub struct PoiFixture {
+    pub signature: Option<String>,
+    // microdegrees relative to the top left corner of the tile
+    pub lat_diff: i64,
+    pub lon_diff: i64,
+    pub layer: u8,
+    pub tag_ids: Vec<u64>,
+    pub name: Option<String>,
+    pub house_number: Option<String>,
+    pub elevation: Option<i64>,
+}
+
+impl PoiFixture {
+    pub fn build(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        if let Some(sig) = &self.signature {
+            bytes.extend(signature(sig));
+        }
+        bytes.extend(vbe_s(self.lat_diff));
+        bytes.extend(vbe_s(self.lon_diff));
+        bytes.push(self.layer << 4 | self.tag_ids.len() as u8);
+        for &tag_id in &self.tag_ids {
+            bytes.extend(vbe_u(tag_id));
+        }
+        let mut flags = 0u8;
+        if self.name.is_some() {
+            flags |= 0x80;
+        }
+        if self.house_number.is_some() {
+            flags |= 0x40;
+        }
+        if self.elevation.is_some() {
+            flags |= 0x20;
+        }
+        bytes.push(flags);
+        if let Some(name) = &self.name {
+            bytes.extend(vbe_string(name));
+        }
+        if let Some(house_number) = &self.house_number {
+            bytes.extend(vbe_string(house_number));
+        }
+        if let Some(elevation) = self.elevation {
+            bytes.extend(vbe_s(elevation));
+        }
+        bytes
+    }
+}
+
+#[derive(Default)]
+pub struct WayFixture {
+    pub signature: Option<String>,
+    pub sub_tile_bitmap: u16,
+    pub layer: u8,
+    pub tag_ids: Vec<u64>,
+    pub name: Option<String>,
+    pub house_number: Option<String>,
+    pub reference: Option<String>,
+    // microdegrees relative to the first way node
+    pub label_position: Option<(i64, i64)>,
+    pub double_delta: bool,
+    // way data blocks made of coordinate blocks, the nodes in microdegrees
+    // relative to the top left corner of the tile
+    pub data_blocks: Vec<Vec<Vec<(i64, i64)>>>,
+}
+
+impl WayFixture {
+    pub fn build(&self) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend(self.sub_tile_bitmap.to_be_bytes());
+        data.push(self.layer << 4 | self.tag_ids.len() as u8);
+        for &tag_id in &self.tag_ids {
+            data.extend(vbe_u(tag_id));
+        }
+        let mut flags = 0u8;
+        if self.name.is_some() {
+            flags |= 0x80;
+        }
+        if self.house_number.is_some() {
+            flags |= 0x40;
+        }
+        if self.reference.is_some() {
+            flags |= 0x20;
+        }
+        if self.label_position.is_some() {
+            flags |= 0x10;
+        }
+        if self.data_blocks.len() > 1 {
+            flags |= 0x08;
+        }
+        if self.double_delta {
+            flags |= 0x04;
+        }
+        data.push(flags);
+        for value in [&self.name, &self.house_number, &self.reference].into_iter().flatten() {
+            data.extend(vbe_string(value));
+        }
+        if let Some((lat_diff, lon_diff)) = self.label_position {
+            data.extend(vbe_s(lat_diff));
+            data.extend(vbe_s(lon_diff));
+        }
+        if self.data_blocks.len() > 1 {
+            data.extend(vbe_u(self.data_blocks.len() as u64));
+        }
+        for data_block in &self.data_blocks {
+            data.extend(vbe_u(data_block.len() as u64));
+            for nodes in data_block {
+                data.extend(vbe_u(nodes.len() as u64));
+                data.extend(encode_nodes(nodes, self.double_delta));
+            }
+        }
+
+        let mut bytes = vec![];
+        if let Some(sig) = &self.signature {
+            bytes.extend(signature(sig));
+        }
+        bytes.extend(vbe_u(data.len() as u64));
+        bytes.extend(data);
+        bytes
+    }
+}
+
+// first node as is, the following ones as single or double deltas
+pub fn encode_nodes(nodes: &[(i64, i64)], double_delta: bool) -> Vec<u8> {
+    let mut bytes = vec![];
+    let mut previous = (0, 0);
+    let mut previous_delta = (0, 0);
+    for (i, &node) in nodes.iter().enumerate() {
+        let value = if i == 0 {
+            node
+        } else {
+            let delta = (node.0 - previous.0, node.1 - previous.1);
+            let value = if double_delta {
+                (delta.0 - previous_delta.0, delta.1 - previous_delta.1)
+            } else {
+                delta
+            };
+            previous_delta = delta;
+            value
+        };
+        previous = node;
+        bytes.extend(vbe_s(value.0));
+        bytes.extend(vbe_s(value.1));
+    }
+    bytes
+}
+
+pub struct MapFixture {
+    // min_lat, min_lon, max_lat, max_lon in degrees
+    pub bounding_box: (f64, f64, f64, f64),
+    pub base_zoom_level: u8,
+    pub min_zoom_level: u8,
+    pub max_zoom_level: u8,
+    pub poi_tags: Vec<String>,
+    pub way_tags: Vec<String>,
+    pub debug: bool,
+    // tile blocks in index order, an empty block has no data
+    pub blocks: Vec<Vec<u8>>,
+}
+
+impl Default for MapFixture {
+    // covers the tiles (548, 336) and (549, 336) on base zoom level 10
+    fn default() -> Self {
+        MapFixture {
+            bounding_box: (52.3, 12.8, 52.4, 13.2),
+            base_zoom_level: 10,
+            min_zoom_level: 8,
+            max_zoom_level: 12,
+            poi_tags: vec![],
+            way_tags: vec![],
+            debug: false,
+            blocks: vec![],
+        }
+    }
+}
+
+impl MapFixture {
+    pub fn build(&self) -> Vec<u8> {
+        let microdegrees = |degrees: f64| ((degrees * 1_000_000.0).round() as i32).to_be_bytes();
+
+        let mut header = vec![];
+        header.extend(3u32.to_be_bytes());
+        header.extend(0u64.to_be_bytes()); // file size, patched below
+        header.extend(1_234_567_890u64.to_be_bytes());
+        let (min_lat, min_lon, max_lat, max_lon) = self.bounding_box;
+        for degrees in [min_lat, min_lon, max_lat, max_lon] {
+            header.extend(microdegrees(degrees));
+        }
+        header.extend(256u16.to_be_bytes());
+        header.extend(vbe_string("Mercator"));
+        header.push(if self.debug { 0x80 } else { 0 });
+        header.extend((self.poi_tags.len() as u16).to_be_bytes());
+        for tag in &self.poi_tags {
+            header.extend(vbe_string(tag));
+        }
+        header.extend((self.way_tags.len() as u16).to_be_bytes());
+        for tag in &self.way_tags {
+            header.extend(vbe_string(tag));
+        }
+        header.push(1);
+        header.extend([self.base_zoom_level, self.min_zoom_level, self.max_zoom_level]);
+
+        // magic, header size field and the sub-file start and size still to come
+        let sub_file_start = (MAGIC_BYTES.len() + 4 + header.len() + 16) as u64;
+
+        let mut sub_file: Vec<u8> = vec![];
+        if self.debug {
+            sub_file.extend(b"+++IndexStart+++");
+        }
+        let mut offset = (sub_file.len() + 5 * self.blocks.len()) as u64;
+        for block in &self.blocks {
+            sub_file.extend(&offset.to_be_bytes()[3..]);
+            offset += block.len() as u64;
+        }
+        for block in &self.blocks {
+            sub_file.extend(block);
+        }
+
+        header.extend(sub_file_start.to_be_bytes());
+        header.extend((sub_file.len() as u64).to_be_bytes());
+
+        let mut bytes = MAGIC_BYTES.to_vec();
+        bytes.extend((header.len() as u32).to_be_bytes());
+        bytes.extend(header);
+        bytes.extend(sub_file);
+
+        let file_size = bytes.len() as u64;
+        bytes[28..36].copy_from_slice(&file_size.to_be_bytes());
+        bytes
+    }
+
+    pub fn write(&self) -> NamedTempFile {
+        let mut file = NamedTempFile::new().expect("create fixture file");
+        file.write_all(&self.build()).expect("write fixture file");
+        file
+    }
+}
+
+// the map most reader tests share: POIs named "west" on zoom level 8 and
+// "west 10" on zoom level 10 and a way in the top left sub-tile of (548, 336),
+// a POI named "east" in (549, 336), with or without debug signatures
+pub fn features_fixture(debug: bool) -> MapFixture {
+    let poi = |name: &str| {
+        PoiFixture {
+            signature: debug.then(|| "***POIStart1".to_string()),
+            name: Some(name.to_string()),
+            ..Default::default()
+        }
+        .build()
+    };
+    let way = WayFixture {
+        signature: debug.then(|| "---WayStart1".to_string()),
+        sub_tile_bitmap: 0x8000,
+        name: Some("way".to_string()),
+        data_blocks: vec![vec![vec![(-130_000, 300_000), (-130_100, 300_100)]]],
+        ..Default::default()
+    }
+    .build();
+
+    MapFixture {
+        debug,
+        blocks: vec![
+            tile_block(debug.then_some("###TileStart548,336"), &[(1, 1), (0, 0), (1, 0), (0, 0), (0, 0)], &[poi("west"), poi("west 10")], &[way]),
+            tile_block(debug.then_some("###TileStart549,336"), &[(1, 0), (0, 0), (0, 0), (0, 0), (0, 0)], &[poi("east")], &[]),
+        ],
+        ..Default::default()
+    }
+}
+
+pub fn position(latitude: f64, longitude: f64) -> LatLong {
+    LatLong { latitude, longitude }
+}
+
+// a way with the coordinate blocks of each data block
+pub fn way(data_blocks: &[&[&[(f64, f64)]]]) -> Way {
+    Way {
+        coordinate_blocks: data_blocks
+            .iter()
+            .map(|blocks| {
+                blocks
+                    .iter()
+                    .map(|nodes| WayCoordinateBlock::new(nodes.iter().map(|&(lat, lon)| position(lat, lon)).collect()))
+                    .collect()
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+// a map written by MapWriter for the command line tools, it covers the tiles
+// (548, 336) and (549, 336) on zoom level 10 and has a POI and a way in the first
+pub fn written_map() -> NamedTempFile {
+    let bbox = BoundingBox {
+        min_lat: 52.3,
+        min_lon: 12.8,
+        max_lat: 52.4,
+        max_lon: 13.2,
+    };
+    let mut writer = MapWriter::new(bbox, vec![ZoomInterval::new(7, 5, 9), ZoomInterval::new(10, 10, 14)]);
+    writer.header.creation_date = 1_700_000_000_000;
+    writer.header.created_by = Some("mapsforge-rs".to_string());
+    writer.header.map_start_position = Some(position(52.35, 13.0));
+
+    writer.add_poi(
+        POI {
+            position: position(52.35, 12.85),
+            layer: 5,
+            tags: Some(vec![Tag::new("amenity", "cafe")]),
+            name: Some("Ecke".to_string()),
+            ..Default::default()
+        },
+        10,
+    );
+    writer.add_way(
+        Way {
+            layer: 7,
+            tags: Some(vec![Tag::new("highway", "primary")]),
+            reference: Some("B 96".to_string()),
+            ..way(&[&[&[(52.31, 12.9), (52.33, 12.95)]]])
+        },
+        10,
+    );
+
+    let file = NamedTempFile::new().unwrap();
+    writer.write_file(file.path()).unwrap();
+    file
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use common::{tile_block, MapFixture, PoiFixture, WayFixture};
+use mapsforge_rs::{types::MapFile, MapforgeError, Result};
+
+const ZOOM_TABLE: [(u64, u64); 5] = [(2, 2), (0, 0), (0, 0), (0, 0), (0, 0)];
+
+fn debug_fixture(tile_signature: &str, poi_signatures: [&str; 2], way_signatures: [&str; 2]) -> MapFixture {
+    let pois: Vec<_> = poi_signatures
+        .iter()
+        .map(|sig| {
+            PoiFixture {
+                signature: Some(sig.to_string()),
+                ..Default::default()
+            }
+            .build()
+        })
+        .collect();
+    let ways: Vec<_> = way_signatures
+        .iter()
+        .map(|sig| {
+            WayFixture {
+                signature: Some(sig.to_string()),
+                sub_tile_bitmap: 0xffff,
+                data_blocks: vec![vec![vec![(-100, 100), (-200, 200)]]],
+                ..Default::default()
+            }
+            .build()
+        })
+        .collect();
+
+    MapFixture {
+        debug: true,
+        blocks: vec![
+            tile_block(Some(tile_signature), &ZOOM_TABLE, &pois, &ways),
+            vec![],
+        ],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_valid_debug_signatures() -> Result<()> {
+    let file = debug_fixture(
+        "###TileStart548,336###",
+        ["***POIStart1***", "***POIStart2***"],
+        ["---WayStart1---", "---WayStart2---"],
+    )
+    .write();
+    let mut map_file = MapFile::open(file.path())?;
+
+    let tile = map_file.read_tile(10, 548, 336)?;
+    assert_eq!(tile.debug_signature.as_deref(), Some("###TileStart548,336###"));
+    assert_eq!(tile.pois[1].debug_signature.as_deref(), Some("***POIStart2***"));
+    assert_eq!(tile.ways[0].debug_signature.as_deref(), Some("---WayStart1---"));
+    assert_eq!(tile.ways.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_invalid_tile_signature() {
+    let file = debug_fixture(
+        "###TileBroken###",
+        ["***POIStart1***", "***POIStart2***"],
+        ["---WayStart1---", "---WayStart2---"],
+    )
+    .write();
+    let mut map_file = MapFile::open(file.path()).unwrap();
+
+    match map_file.read_tile(10, 548, 336) {
+        Err(MapforgeError::InvalidTileSignature(548, 336, signature)) => {
+            assert_eq!(signature, "###TileBroken###")
+        }
+        other => panic!("unexpected result {other:?}"),
+    }
+}
+
+#[test]
+fn test_invalid_poi_signature() {
+    let file = debug_fixture(
+        "###TileStart548,336###",
+        ["***POIStart1***", "***POIBroken***"],
+        ["---WayStart1---", "---WayStart2---"],
+    )
+    .write();
+    let mut map_file = MapFile::open(file.path()).unwrap();
+
+    assert!(matches!(
+        map_file.read_tile(10, 548, 336),
+        Err(MapforgeError::InvalidPoiSignature(548, 336, 1, _))
+    ));
+}
+
+#[test]
+fn test_invalid_way_signature() {
+    let file = debug_fixture(
+        "###TileStart548,336###",
+        ["***POIStart1***", "***POIStart2***"],
+        ["+++WayStart1---", "---WayStart2---"],
+    )
+    .write();
+    let mut map_file = MapFile::open(file.path()).unwrap();
+
+    assert!(matches!(
+        map_file.read_tile(10, 548, 336),
+        Err(MapforgeError::InvalidWaySignature(548, 336, 0, _))
+    ));
+}
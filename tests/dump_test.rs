@@ -0,0 +1,70 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::process::{Command, Output};
+
+use common::written_map;
+use serde_json::{json, Value};
+use tempfile::NamedTempFile;
+
+fn dump(map: &NamedTempFile, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mapsforge-dump"))
+        .arg(map.path())
+        .args(args)
+        .output()
+        .expect("run mapsforge-dump")
+}
+
+#[test]
+fn test_text_output() {
+    let map = written_map();
+    let output = dump(&map, &["--zoom", "10", "--x", "548", "--y", "336"]);
+    assert!(output.status.success());
+
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.starts_with("tile 10/548/336: 1 POIs, 1 ways"));
+    assert!(text.contains("  position:     52.350000, 12.850000"));
+    assert!(text.contains("  tags:         amenity=cafe"));
+    assert!(text.contains("  name:         Ecke"));
+    assert!(text.contains("  layer:           7"));
+    assert!(text.contains("  tags:            highway=primary, ref=B 96"));
+    assert!(text.contains("  data block 0:\n    block 0: 2 nodes\n      52.310000, 12.900000\n      52.330000, 12.950000"));
+}
+
+#[test]
+fn test_json_output_for_position() {
+    let map = written_map();
+    let output = dump(&map, &["--zoom", "10", "--lat", "52.35", "--lon", "12.85", "--json"]);
+    assert!(output.status.success());
+
+    let json: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!((json["x"].clone(), json["y"].clone()), (json!(548), json!(336)));
+    assert_eq!(json["pois"][0]["name"], "Ecke");
+    assert_eq!(json["pois"][0]["tags"], json!([["amenity", "cafe"], ["name", "Ecke"]]));
+    assert_eq!(json["ways"][0]["ref"], "B 96");
+    assert_eq!(json["ways"][0]["coordinate_blocks"][0][0].as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn test_invalid_arguments() {
+    let map = written_map();
+
+    // neither tile numbers nor a position
+    assert!(!dump(&map, &["--zoom", "10"]).status.success());
+    // both
+    assert!(!dump(&map, &["--zoom", "10", "--x", "548", "--y", "336", "--lat", "52.35", "--lon", "12.85"]).status.success());
+
+    let output = dump(&map, &["--zoom", "10", "--lat", "52.35", "--lon", "-190"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("outside of the map"));
+
+    // zoom levels the map doesn't have, with and without tile math
+    for args in [["--zoom", "40", "--lat", "52.35", "--lon", "12.85"], ["--zoom", "40", "--x", "548", "--y", "336"]] {
+        let output = dump(&map, &args);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("zoom level 40"), "{stderr}");
+        assert!(!stderr.contains("panicked"), "{stderr}");
+    }
+}
@@ -0,0 +1,137 @@
+# writes tests/fixtures/village.osm.pbf, a small OSM PBF extract following
+# OSMPBF fileformat.proto and osmformat.proto. run with python3 from anywhere,
+# the fixture is written next to this script
+import os, struct, zlib
+
+def varint(v):
+    out = bytearray()
+    while v >= 0x80:
+        out.append((v & 0x7f) | 0x80); v >>= 7
+    out.append(v); return bytes(out)
+
+def zz(v): return (v << 1) ^ (v >> 63)
+def key(field, wt): return varint(field << 3 | wt)
+def f_varint(field, v): return key(field, 0) + varint(v & 0xffffffffffffffff)
+def f_bytes(field, b): return key(field, 2) + varint(len(b)) + b
+def f_packed(field, vals): return f_bytes(field, b''.join(varint(v & 0xffffffffffffffff) for v in vals))
+def delta(vals):
+    out, prev = [], 0
+    for v in vals: out.append(zz(v - prev)); prev = v
+    return out
+
+class Strings:
+    def __init__(self): self.s = [b'']
+    def __call__(self, x):
+        x = x.encode()
+        if x not in self.s: self.s.append(x)
+        return self.s.index(x)
+    def table(self): return b''.join(f_bytes(1, x) for x in self.s)
+
+GRAN = 100
+DATE_GRAN = 1000
+TIMESTAMP = 1735689600  # 2025-01-01
+USER, UID = 'mapper', 4711
+
+def block(strings, group):
+    return f_bytes(1, strings.table()) + f_bytes(2, group) + f_varint(17, GRAN) + f_varint(18, DATE_GRAN)
+
+def info(strings, version, changeset):
+    return f_varint(1, version) + f_varint(2, TIMESTAMP) + f_varint(3, changeset) + f_varint(4, UID) + f_varint(5, strings(USER))
+
+def blob(kind, data, compress):
+    if compress:
+        b = f_varint(2, len(data)) + f_bytes(3, zlib.compress(data, 9))
+    else:
+        b = f_bytes(1, data)
+    header = f_bytes(1, kind.encode()) + f_varint(3, len(b))
+    return struct.pack('>I', len(header)) + header + b
+
+nodes = [
+    # id, lat, lon, tags
+    (100, 52.3512, 12.9105, [('amenity', 'cafe'), ('name', 'Café am See'), ('opening_hours', 'Mo-Su 08:00-18:00')]),
+    (101, 52.3618, 12.9702, [('shop', 'bakery'), ('addr:housenumber', '3'), ('layer', '-1')]),
+    (102, 52.3855, 13.0412, [('natural', 'peak'), ('name', 'Kleiner Berg'), ('ele', '68.4')]),
+    (103, 52.3701, 12.9503, [('created_by', 'JOSM')]),
+    # Dorfstraße, across the tile border at longitude 13.0078125
+    (201, 52.3550, 12.9800), (202, 52.3560, 12.9950), (203, 52.3575, 13.0200),
+    # the bridge and the tunnel
+    (204, 52.3400, 12.9000), (205, 52.3405, 12.9100),
+    (206, 52.3300, 12.9000), (207, 52.3305, 12.9100),
+    # the railway and the service road
+    (208, 52.3200, 12.8800), (209, 52.3250, 12.9400),
+    (210, 52.3450, 12.8700), (211, 52.3460, 12.8800),
+    # the footpath, its middle node is missing from the extract
+    (212, 52.3650, 12.9200), (213, 52.3660, 12.9300),
+    # the school building
+    (220, 52.3620, 12.9600), (221, 52.3620, 12.9620), (222, 52.3635, 12.9620), (223, 52.3635, 12.9600),
+    # the lakes and the island in the first one
+    (230, 52.3470, 12.8900), (231, 52.3470, 12.9050), (232, 52.3540, 12.9050), (233, 52.3540, 12.8900),
+    (234, 52.3490, 12.8950), (235, 52.3490, 12.8990), (236, 52.3510, 12.8990), (237, 52.3510, 12.8950),
+    (240, 52.3720, 12.9000), (241, 52.3720, 12.9150), (242, 52.3780, 12.9150), (243, 52.3780, 12.9000),
+]
+nodes = [n if len(n) == 4 else (*n, []) for n in nodes]
+
+ways = [
+    (300, [201, 202, 203], [('highway', 'residential'), ('name', 'Dorfstraße'), ('maxspeed', '30')]),
+    (301, [204, 205], [('highway', 'primary'), ('bridge', 'yes'), ('layer', '1'), ('ref', 'B 2')]),
+    (302, [206, 207], [('highway', 'primary'), ('tunnel', 'yes'), ('layer', '-1'), ('ref', 'B 2')]),
+    (303, [208, 209], [('railway', 'rail'), ('layer', '12')]),
+    (304, [210, 211], [('highway', 'service'), ('layer', '10')]),
+    (305, [212, 9999, 213], [('highway', 'footway')]),
+    (306, [220, 221, 222, 223, 220], [('building', 'school'), ('name', 'Grundschule'), ('addr:housenumber', '12')]),
+    (310, [230, 231, 232], []),
+    (311, [232, 233, 230], []),
+    (312, [234, 235, 236, 237, 234], []),
+    (313, [240, 241, 242, 243, 240], []),
+]
+
+relations = [
+    (400, [(1, 310, 'outer'), (1, 312, 'inner'), (1, 311, 'outer'), (1, 313, 'outer')],
+     [('type', 'multipolygon'), ('natural', 'water'), ('name', 'Seen')]),
+    (401, [(0, 100, 'stop'), (1, 300, '')], [('type', 'route'), ('route', 'bus'), ('ref', '614')]),
+]
+
+def e7(d): return round(d * 1e9 / GRAN)
+
+# header block
+hb = f_bytes(1, f_varint(1, zz(round(12.85e9))) + f_varint(2, zz(round(13.05e9))) + f_varint(3, zz(round(52.39e9))) + f_varint(4, zz(round(52.31e9))))
+hb += f_bytes(4, b'OsmSchema-V0.6') + f_bytes(4, b'DenseNodes') + f_bytes(5, b'Sort.Type_then_ID')
+hb += f_bytes(16, b'mapsforge-rs test fixture') + f_bytes(34, b'https://www.openstreetmap.org/api/0.6')
+
+out = blob('OSMHeader', hb, compress=True)
+
+# nodes
+s = Strings()
+kv = []
+for n in nodes:
+    for k, v in n[3]: kv += [s(k), s(v)]
+    kv.append(0)
+dense_info = (f_packed(1, [1 + n[0] % 3 for n in nodes])
+              + f_packed(2, delta([TIMESTAMP] * len(nodes)))
+              + f_packed(3, delta([90000000 + n[0] for n in nodes]))
+              + f_packed(4, delta([UID] * len(nodes)))
+              + f_packed(5, delta([s(USER)] * len(nodes))))
+dense = (f_packed(1, delta([n[0] for n in nodes])) + f_bytes(5, dense_info)
+         + f_packed(8, delta([e7(n[1]) for n in nodes])) + f_packed(9, delta([e7(n[2]) for n in nodes])) + f_packed(10, kv))
+out += blob('OSMData', block(s, f_bytes(2, dense)), compress=True)
+
+# ways
+s = Strings()
+group = b''
+for wid, refs, tags in ways:
+    group += f_bytes(3, f_varint(1, wid) + f_packed(2, [s(k) for k, _ in tags]) + f_packed(3, [s(v) for _, v in tags])
+                     + f_bytes(4, info(s, 2, 90001000 + wid)) + f_packed(8, delta(refs)))
+out += blob('OSMData', block(s, group), compress=True)
+
+# relations
+s = Strings()
+group = b''
+for rid, members, tags in relations:
+    group += f_bytes(4, f_varint(1, rid) + f_packed(2, [s(k) for k, _ in tags]) + f_packed(3, [s(v) for _, v in tags])
+                     + f_bytes(4, info(s, 5, 90002000 + rid))
+                     + f_packed(8, [s(r) for _, _, r in members]) + f_packed(9, delta([m for _, m, _ in members]))
+                     + f_packed(10, [t for t, _, _ in members]))
+out += blob('OSMData', block(s, group), compress=True)
+
+open(os.path.join(os.path.dirname(os.path.abspath(__file__)), 'village.osm.pbf'), 'wb').write(out)
+print(len(out))
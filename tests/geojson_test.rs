@@ -0,0 +1,89 @@
+#![cfg(feature = "geojson")]
+
+mod common;
+
+use common::position;
+use mapsforge_rs::types::{MapFile, QueryResult, Tag, Way, POI};
+use serde_json::json;
+
+fn way(data_blocks: &[&[&[(f64, f64)]]]) -> Way {
+    Way {
+        layer: 5,
+        tags: Some(vec![Tag::new("building", "yes")]),
+        name: Some("Rathaus".to_string()),
+        reference: Some("A1".to_string()),
+        ..common::way(data_blocks)
+    }
+}
+
+#[test]
+fn test_poi_feature() {
+    let poi = POI {
+        position: position(52.5, 13.4),
+        layer: 6,
+        tags: Some(vec![Tag::new("amenity", "cafe")]),
+        name: Some("Ecke".to_string()),
+        house_number: Some("3a".to_string()),
+        elevation: Some(34),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        poi.to_geojson(),
+        json!({
+            "type": "Feature",
+            "geometry": { "type": "Point", "coordinates": [13.4, 52.5] },
+            "properties": {
+                "amenity": "cafe",
+                "layer": 6,
+                "name": "Ecke",
+                "addr:housenumber": "3a",
+                "ele": 34,
+            },
+        })
+    );
+}
+
+#[test]
+fn test_way_features() {
+    let line = way(&[&[&[(52.0, 13.0), (52.1, 13.1)]]]);
+    let feature = line.to_geojson();
+    assert_eq!(feature["geometry"], json!({ "type": "LineString", "coordinates": [[13.0, 52.0], [13.1, 52.1]] }));
+    assert_eq!(feature["properties"], json!({ "building": "yes", "layer": 5, "name": "Rathaus", "ref": "A1" }));
+
+    let outer: &[(f64, f64)] = &[(52.0, 13.0), (52.0, 13.1), (52.1, 13.1), (52.0, 13.0)];
+    let hole: &[(f64, f64)] = &[(52.01, 13.05), (52.01, 13.07), (52.02, 13.07), (52.01, 13.05)];
+    let island: &[(f64, f64)] = &[(53.0, 14.0), (53.0, 14.1), (53.1, 14.1), (53.0, 14.0)];
+
+    let polygon = way(&[&[outer, hole]]).to_geojson();
+    assert_eq!(polygon["geometry"]["type"], "Polygon");
+    assert_eq!(polygon["geometry"]["coordinates"][1][0], json!([13.05, 52.01]));
+
+    let multi_polygon = way(&[&[outer, hole], &[island]]).to_geojson();
+    assert_eq!(multi_polygon["geometry"]["type"], "MultiPolygon");
+    assert_eq!(multi_polygon["geometry"]["coordinates"].as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn test_feature_collection() {
+    let result = QueryResult {
+        pois: vec![POI::default()],
+        ways: vec![way(&[&[&[(52.0, 13.0), (52.1, 13.1)]]])],
+    };
+
+    let collection = result.to_geojson();
+    assert_eq!(collection["type"], "FeatureCollection");
+    let types: Vec<_> = collection["features"].as_array().unwrap().iter().map(|f| f["geometry"]["type"].clone()).collect();
+    assert_eq!(types, [json!("Point"), json!("LineString")]);
+}
+
+#[test]
+fn test_tile_of_test_map() {
+    let mut map_file = MapFile::open("test_data/test_map.map").unwrap();
+    let interval = &map_file.header.zoom_interval_configuration[0];
+    let (x, y, _, _) = MapFile::tile_range(&map_file.header.bounding_box, interval.base_zoom_level);
+    let tile = map_file.read_tile(interval.base_zoom_level, x, y).unwrap();
+
+    let collection = tile.to_geojson();
+    assert_eq!(collection["features"].as_array().unwrap().len(), tile.pois.len() + tile.ways.len());
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use common::way;
+use mapsforge_rs::types::{Geometry, LatLong, POI};
+
+fn ring(min: (f64, f64), max: (f64, f64)) -> [(f64, f64); 5] {
+    [(min.0, min.1), (min.0, max.1), (max.0, max.1), (max.0, min.1), (min.0, min.1)]
+}
+
+#[test]
+fn test_poi_is_point() {
+    let poi = POI {
+        position: LatLong { latitude: 52.5, longitude: 13.4 },
+        ..Default::default()
+    };
+
+    assert!(matches!(poi.geometry(), Geometry::Point(LatLong { latitude: 52.5, longitude: 13.4 })));
+}
+
+#[test]
+fn test_open_ways_are_lines() {
+    let line = &ring((52.0, 13.0), (52.1, 13.1))[..3];
+
+    assert!(matches!(way(&[&[line]]).geometry(), Geometry::LineString(nodes) if nodes.len() == 3));
+    assert!(matches!(way(&[&[line], &[line]]).geometry(), Geometry::MultiLineString(lines) if lines.len() == 2));
+    assert!(matches!(way(&[]).geometry(), Geometry::LineString(nodes) if nodes.is_empty()));
+}
+
+#[test]
+fn test_closed_ways_are_polygons() {
+    let outer = ring((52.0, 13.0), (52.1, 13.1));
+    let hole = ring((52.02, 13.02), (52.04, 13.04));
+    let island = ring((52.2, 13.2), (52.3, 13.3));
+
+    let Geometry::Polygon(rings) = way(&[&[&outer, &hole]]).geometry() else {
+        panic!("expected a polygon");
+    };
+    assert_eq!(rings.len(), 2);
+
+    // every data block is a polygon of its own
+    let Geometry::MultiPolygon(polygons) = way(&[&[&outer, &hole], &[&island]]).geometry() else {
+        panic!("expected a multipolygon");
+    };
+    assert_eq!(polygons.iter().map(Vec::len).collect::<Vec<_>>(), [2, 1]);
+}
+
+#[test]
+fn test_rings_are_closed() {
+    // the last node of the outer ring is off by a rounding error, the hole is open
+    let mut outer = ring((52.0, 13.0), (52.1, 13.1));
+    outer[4].1 += 1e-12;
+    let hole = &ring((52.02, 13.02), (52.04, 13.04))[..4];
+
+    let Geometry::Polygon(rings) = way(&[&[&outer, hole]]).geometry() else {
+        panic!("expected a polygon");
+    };
+    for ring in rings {
+        let (first, last) = (ring[0], ring[ring.len() - 1]);
+        assert_eq!((first.latitude, first.longitude), (last.latitude, last.longitude));
+        assert_eq!(ring.len(), 5);
+    }
+}
@@ -0,0 +1,223 @@
+use std::{
+    fs::File,
+    io::{BufReader, Cursor, Read},
+};
+
+use mapsforge_rs::{
+    header::COMMENT_MASK,
+    types::{LatLong, MapFile, MapWriter, Tag, ZoomInterval, POI},
+    utils, BoundingBox, MapHeader, MapforgeError, Result,
+};
+
+fn header() -> MapHeader {
+    MapHeader {
+        magic: "mapsforge binary OSM".to_string(),
+        header_size: 0,
+        file_version: 5,
+        file_size: 123_456,
+        creation_date: 1_700_000_000_000,
+        bounding_box: BoundingBox {
+            min_lat: 52.3,
+            min_lon: 12.8,
+            max_lat: 52.4,
+            max_lon: 13.2,
+        },
+        tile_size: 256,
+        projection: "Mercator".to_string(),
+        flags: 0x80 | 0x40 | 0x20 | 0x10 | 0x08 | 0x04,
+        map_start_position: Some(LatLong {
+            latitude: 52.35,
+            longitude: -13.000_001,
+        }),
+        start_zoom_level: Some(14),
+        language_preference: Some("de,en".to_string()),
+        comment: Some("Straßen und Plätze".to_string()),
+        created_by: Some("mapsforge-rs".to_string()),
+        poi_tags: vec!["amenity=cafe".to_string(), "shop=bakery".to_string()],
+        way_tags: vec!["highway=primary".to_string()],
+        num_zoom_intervals: 2,
+        zoom_interval_configuration: vec![
+            ZoomInterval {
+                base_zoom_level: 5,
+                min_zoom_level: 0,
+                max_zoom_level: 7,
+                sub_file_start: 200,
+                sub_file_size: 1_000,
+            },
+            ZoomInterval {
+                base_zoom_level: 10,
+                min_zoom_level: 8,
+                max_zoom_level: 11,
+                sub_file_start: 1_200,
+                sub_file_size: 122_256,
+            },
+        ],
+    }
+}
+
+#[test]
+fn test_header_round_trip() -> Result<()> {
+    let header = header();
+
+    let mut bytes = vec![];
+    header.write_to(&mut bytes)?;
+
+    let read = MapHeader::read_from_file(&mut Cursor::new(&bytes))?;
+    assert_eq!(read.header_size as usize, bytes.len() - 24);
+
+    // the same apart from the computed header size
+    let expected = format!("{:?}", MapHeader { header_size: read.header_size, ..header });
+    assert_eq!(format!("{read:?}"), expected);
+
+    let mut written_again = vec![];
+    read.write_to(&mut written_again)?;
+    assert_eq!(written_again, bytes);
+
+    Ok(())
+}
+
+#[test]
+fn test_header_without_optional_fields() -> Result<()> {
+    let header = MapHeader {
+        flags: 0,
+        ..header()
+    };
+
+    let mut bytes = vec![];
+    header.write_to(&mut bytes)?;
+    let read = MapHeader::read_from_file(&mut Cursor::new(&bytes))?;
+
+    // fields without their flag are not written
+    assert!(read.map_start_position.is_none());
+    assert!(read.comment.is_none());
+    assert!(read.created_by.is_none());
+    assert_eq!(read.way_tags, ["highway=primary"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_header_with_missing_optional_field() {
+    let header = MapHeader {
+        comment: None,
+        ..header()
+    };
+
+    let result = header.write_to(&mut vec![]);
+    assert!(matches!(result, Err(MapforgeError::MissingHeaderField(field)) if field == "comment"));
+}
+
+#[test]
+fn test_rewrite_header_of_map_file() -> Result<()> {
+    let mut original = vec![];
+    File::open("test_data/test_map.map")?.read_to_end(&mut original)?;
+
+    let header = MapHeader::read_from_file(&mut BufReader::new(Cursor::new(&original)))?;
+    let mut bytes = vec![];
+    header.write_to(&mut bytes)?;
+
+    // the test map pads its header, the fields themselves are written unchanged
+    assert_eq!(bytes[..20], original[..20]);
+    assert_eq!(bytes[24..], original[24..bytes.len()]);
+    assert!(original[bytes.len()..24 + header.header_size as usize].iter().all(|&byte| byte == 0));
+
+    Ok(())
+}
+
+// a small map with a comment to patch
+fn map() -> Result<Vec<u8>> {
+    let mut writer = MapWriter::new(header().bounding_box, vec![ZoomInterval::new(10, 8, 12)]);
+    writer.header.flags |= COMMENT_MASK;
+    writer.header.comment = Some("original comment".to_string());
+    writer.add_poi(
+        POI {
+            position: LatLong { latitude: 52.35, longitude: 12.85 },
+            tags: Some(vec![Tag::new("amenity", "cafe")]),
+            name: Some("Café".to_string()),
+            ..Default::default()
+        },
+        10,
+    );
+
+    let mut bytes = vec![];
+    writer.write_to(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[test]
+fn test_rewrite_with_larger_header() -> Result<()> {
+    let original = map()?;
+    let mut header = MapHeader::read_from_file(&mut Cursor::new(&original))?;
+    header.comment = Some("a comment that is a lot longer than the original one".to_string());
+
+    let mut bytes = vec![];
+    header.rewrite(&mut Cursor::new(&original), &mut bytes)?;
+
+    // the sub-file moved behind the larger header
+    let mut map_file = MapFile::from_reader(Cursor::new(bytes.clone()))?;
+    let shift = map_file.header.header_size - header.header_size;
+    assert_eq!(shift, 36);
+    assert_eq!(map_file.header.comment, header.comment);
+    assert_eq!(map_file.header.file_size, bytes.len() as u64);
+    assert_eq!(map_file.header.zoom_interval_configuration[0].sub_file_start, header.zoom_interval_configuration[0].sub_file_start + shift as u64);
+
+    let tile = map_file.read_tile(10, 548, 336)?;
+    assert_eq!(tile.pois[0].name.as_deref(), Some("Café"));
+
+    Ok(())
+}
+
+#[test]
+fn test_rewrite_with_smaller_header() -> Result<()> {
+    let original = map()?;
+    let mut header = MapHeader::read_from_file(&mut Cursor::new(&original))?;
+    header.flags &= !COMMENT_MASK;
+    header.comment = None;
+
+    let mut bytes = vec![];
+    header.rewrite(&mut Cursor::new(&original), &mut bytes)?;
+
+    // padded to the original size, so nothing behind the header moves
+    assert_eq!(bytes.len(), original.len());
+    let end = 24 + header.header_size as usize;
+    assert_eq!(bytes[end..], original[end..]);
+
+    let mut map_file = MapFile::from_reader(Cursor::new(bytes))?;
+    assert_eq!(map_file.header.header_size, header.header_size);
+    assert!(map_file.header.comment.is_none());
+    assert_eq!(map_file.read_tile(10, 548, 336)?.pois.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_header_with_too_many_zoom_intervals() {
+    let interval = header().zoom_interval_configuration.remove(0);
+    let header = MapHeader {
+        zoom_interval_configuration: vec![interval; 256],
+        ..header()
+    };
+
+    assert!(matches!(header.write_to(&mut vec![]), Err(MapforgeError::TooManyZoomIntervals(256))));
+}
+
+#[test]
+fn test_vbe_round_trip() -> Result<()> {
+    let values = [0isize, 1, -1, 63, -63, 64, -64, 127, 128, 8191, -8192, 1 << 20, -(1 << 31)];
+
+    let mut bytes = vec![];
+    for &value in &values {
+        utils::write_vbe_s_int(&mut bytes, value)?;
+        utils::write_vbe_u_int(&mut bytes, value.unsigned_abs())?;
+    }
+    utils::write_string(&mut bytes, "Hauptstraße")?;
+
+    let mut reader = Cursor::new(&bytes);
+    for &value in &values {
+        assert_eq!(utils::read_vbe_s_int(&mut reader)?, value);
+        assert_eq!(utils::read_vbe_u_int(&mut reader)?, value.unsigned_abs());
+    }
+    assert_eq!(utils::read_string(&mut reader)?, "Hauptstraße");
+
+    Ok(())
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::process::{Command, Output};
+
+use common::written_map;
+use serde_json::Value;
+
+fn info(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mapsforge-info"))
+        .args(args)
+        .output()
+        .expect("run mapsforge-info")
+}
+
+#[test]
+fn test_text_output() {
+    let map = written_map();
+    let output = info(&[map.path().to_str().unwrap()]);
+    assert!(output.status.success());
+
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.contains("creation date:       2023-11-14 22:13:20 UTC (1700000000000 ms)"));
+    assert!(text.contains("created by:          mapsforge-rs"));
+    assert!(text.contains("start position:      52.35, 13"));
+    assert!(text.contains("comment:             -"));
+    // index, base, min and max zoom level, sub-file start and size, tiles
+    let interval: Vec<&str> = text.lines().last().unwrap().split_whitespace().collect();
+    assert_eq!(interval[..4], ["1", "10", "10", "14"]);
+    assert_eq!(interval[6], "2");
+}
+
+#[test]
+fn test_json_output() {
+    let map = written_map();
+    let output = info(&["--json", map.path().to_str().unwrap()]);
+    assert!(output.status.success());
+
+    let json: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["created_by"], "mapsforge-rs");
+    assert_eq!(json["comment"], Value::Null);
+    assert_eq!(json["zoom_intervals"].as_array().unwrap().len(), 2);
+    assert_eq!(json["zoom_intervals"][1]["base_zoom_level"], 10);
+    assert_eq!(json["zoom_intervals"][1]["tiles"], 2);
+    assert_eq!(json["file_size"], std::fs::metadata(map.path()).unwrap().len());
+}
+
+#[test]
+fn test_missing_file() {
+    let output = info(&["test_data/missing.map"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("mapsforge-info: test_data/missing.map:"));
+}
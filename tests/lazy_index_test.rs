@@ -0,0 +1,145 @@
+mod common;
+
+use std::collections::BTreeSet;
+
+use common::{features_fixture, tile_block, MapFixture, PoiFixture};
+use mapsforge_rs::{
+    types::{LazyTileIndex, MapFile, MapFileOptions, TileIndexEntry},
+    BoundingBox, MapforgeError, Result,
+};
+
+#[test]
+fn test_read_tile_with_lazy_index() -> Result<()> {
+    for debug in [false, true] {
+        let file = features_fixture(debug).write();
+        let options = MapFileOptions {
+            lazy_index: true,
+            index_cache_size: 16,
+            ..Default::default()
+        };
+
+        let mut lazy = MapFile::open_with(file.path(), options)?;
+        let mut eager = MapFile::open(file.path())?;
+        assert!(lazy.tile_indices.is_empty());
+
+        for x in [548, 549] {
+            let tile = lazy.read_tile(10, x, 336)?;
+            assert_eq!(format!("{tile:?}"), format!("{:?}", eager.read_tile(10, x, 336)?));
+        }
+        assert_eq!(lazy.read_tile(8, 137, 84)?.pois.len(), 2);
+
+        for index in 0..2 {
+            let entry = lazy.tile_index_entry(0, index)?;
+            assert_eq!(entry.offset_abs, eager.tile_indices[0][index].offset_abs);
+        }
+        assert_eq!(lazy.lazy_index.as_ref().unwrap().entries.len(), 2);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_tile_index_entry_out_of_range() -> Result<()> {
+    let file = features_fixture(false).write();
+    let lazy = MapFileOptions {
+        lazy_index: true,
+        ..Default::default()
+    };
+
+    for options in [MapFileOptions::default(), lazy] {
+        let mut map_file = MapFile::open_with(file.path(), options)?;
+        assert!(matches!(map_file.tile_index_entry(0, 2), Err(MapforgeError::InvalidTileIndexEntry(0, 2))));
+        assert!(matches!(map_file.tile_index_entry(1, 0), Err(MapforgeError::InvalidZoomIntervalIndex(1))));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_lazy_index_without_cache() -> Result<()> {
+    let file = features_fixture(false).write();
+    let options = MapFileOptions {
+        lazy_index: true,
+        index_cache_size: 0,
+        ..Default::default()
+    };
+
+    let mut map_file = MapFile::open_with(file.path(), options)?;
+    assert_eq!(map_file.read_tile(10, 549, 336)?.pois[0].name.as_deref(), Some("east"));
+    assert!(map_file.lazy_index.unwrap().entries.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_lazy_index_cache_drops_oldest_entry() {
+    let entry = |offset| TileIndexEntry {
+        is_water: false,
+        offset,
+        offset_abs: offset + 100,
+    };
+
+    let mut index = LazyTileIndex::new(2);
+    index.insert(0, 0, entry(10));
+    index.insert(0, 1, entry(20));
+    index.insert(0, 1, entry(20));
+    index.insert(1, 0, entry(30));
+
+    assert!(index.get(0, 0).is_none());
+    assert_eq!(index.get(0, 1).unwrap().offset, 20);
+    assert_eq!(index.get(1, 0).unwrap().offset_abs, 130);
+    assert_eq!(index.entries.len(), 2);
+}
+
+#[test]
+fn test_lazy_index_reads_only_needed_entries() -> Result<()> {
+    // 10 by 8 tiles, only one in the middle and the last one have data
+    let bounding_box = (52.0, 12.8, 53.5, 16.0);
+    let bbox = BoundingBox {
+        min_lat: bounding_box.0,
+        min_lon: bounding_box.1,
+        max_lat: bounding_box.2,
+        max_lon: bounding_box.3,
+    };
+    let (x_min, y_min, x_max, y_max) = MapFile::tile_range(&bbox, 10);
+    let total_tiles = MapFile::calculate_total_tiles(&bbox, 10)? as usize;
+    assert_eq!(total_tiles, 80);
+
+    let block = |name: &str| {
+        let poi = PoiFixture {
+            name: Some(name.to_string()),
+            ..Default::default()
+        };
+        tile_block(None, &[(1, 0), (0, 0), (0, 0), (0, 0), (0, 0)], &[poi.build()], &[])
+    };
+    let mut blocks = vec![vec![]; total_tiles];
+    blocks[23] = block("middle");
+    blocks[total_tiles - 1] = block("last");
+    let file = MapFixture {
+        bounding_box,
+        blocks,
+        ..Default::default()
+    }
+    .write();
+
+    let options = MapFileOptions {
+        lazy_index: true,
+        index_cache_size: 16,
+        ..Default::default()
+    };
+    let mut map_file = MapFile::open_with(file.path(), options)?;
+    let row_width = x_max - x_min + 1;
+
+    // the entry of the tile and the next one for where its block ends
+    let tile = map_file.read_tile(10, x_min + 23 % row_width, y_min + 23 / row_width)?;
+    assert_eq!(tile.pois[0].name.as_deref(), Some("middle"));
+    let entries = &map_file.lazy_index.as_ref().unwrap().entries;
+    assert_eq!(entries.keys().map(|&(_, index)| index).collect::<BTreeSet<_>>(), BTreeSet::from([23, 24]));
+
+    // the last block ends with the sub-file
+    let tile = map_file.read_tile(10, x_max, y_max)?;
+    assert_eq!(tile.pois[0].name.as_deref(), Some("last"));
+    assert_eq!(map_file.lazy_index.as_ref().unwrap().entries.len(), 3);
+
+    Ok(())
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use std::sync::Mutex;
+
+use common::{tile_block, MapFixture, WayFixture};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use mapsforge_rs::{types::MapFile, Result};
+
+// collects log messages so the tests can check their context
+struct CaptureLogger {
+    messages: Mutex<Vec<(Level, String)>>,
+}
+
+impl Log for CaptureLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        self.messages
+            .lock()
+            .unwrap()
+            .push((record.level(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CaptureLogger = CaptureLogger {
+    messages: Mutex::new(Vec::new()),
+};
+
+#[test]
+fn test_decoding_logs_with_tile_context() -> Result<()> {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Trace);
+
+    let way = WayFixture {
+        sub_tile_bitmap: 0xffff,
+        data_blocks: vec![vec![vec![(-100, 100), (-200, 200)]]],
+        ..Default::default()
+    }
+    .build();
+    let file = MapFixture {
+        blocks: vec![
+            tile_block(None, &[(0, 1), (0, 0), (0, 0), (0, 0), (0, 0)], &[], &[way]),
+            vec![],
+        ],
+        ..Default::default()
+    }
+    .write();
+
+    let mut map_file = MapFile::open(file.path())?;
+    map_file.read_tile(10, 548, 336)?;
+
+    let messages = LOGGER.messages.lock().unwrap();
+    assert!(messages
+        .iter()
+        .any(|(level, message)| *level == Level::Debug && message.starts_with("tile 10/548/336: zoom table")));
+    assert!(messages
+        .iter()
+        .any(|(level, message)| *level == Level::Trace && message.starts_with("tile 10/548/336 way 0:")));
+
+    Ok(())
+}
@@ -0,0 +1,224 @@
+mod common;
+
+use std::io::{BufReader, Cursor, ErrorKind};
+
+use common::{tile_block, vbe_s, vbe_u, MapFixture, PoiFixture, WayFixture};
+use mapsforge_rs::{
+    poi::process_pois,
+    types::{BoundingBox, MapFile, MapSlice},
+    utils::{read_string, read_vbe_s_int, read_vbe_u_int},
+    way::process_ways,
+    MapforgeError,
+};
+
+fn tags() -> Vec<String> {
+    vec!["amenity=cafe".to_string(), "highway".to_string()]
+}
+
+#[test]
+fn test_invalid_tag_id() {
+    let data = PoiFixture {
+        lat_diff: -1_000,
+        lon_diff: 1_000,
+        tag_ids: vec![0, 200],
+        ..Default::default()
+    }
+    .build();
+    // position, special byte and the first tag id come before the invalid one
+    let offset = (vbe_s(-1_000).len() + vbe_s(1_000).len() + 1 + 1) as u64;
+
+    let mut reader = Cursor::new(&data[..]);
+    match process_pois(&mut reader, 1, &tags(), &Default::default()) {
+        Err(MapforgeError::InvalidTagId(200, actual)) => assert_eq!(actual, offset),
+        other => panic!("unexpected result {other:?}"),
+    }
+}
+
+#[test]
+fn test_malformed_tag() {
+    let data = WayFixture {
+        tag_ids: vec![1],
+        data_blocks: vec![vec![vec![(0, 0), (1, 1)]]],
+        ..Default::default()
+    }
+    .build();
+    // way data size, sub-tile bitmap and special byte come before the tag id
+    let offset = (vbe_u(data.len() as u64 - 1).len() + 2 + 1) as u64;
+
+    let mut reader = Cursor::new(&data[..]);
+    match process_ways(&mut reader, 1, &tags(), &Default::default(), None) {
+        Err(MapforgeError::MalformedTag(tag, actual)) => {
+            assert_eq!(tag, "highway");
+            assert_eq!(actual, offset);
+        }
+        other => panic!("unexpected result {other:?}"),
+    }
+}
+
+#[test]
+fn test_invalid_utf8() {
+    let data = [0x03, 0x41, 0xff, 0xfe];
+
+    let mut reader = Cursor::new(&data[..]);
+    assert!(matches!(read_string(&mut reader), Err(MapforgeError::InvalidUtf8(1))));
+
+    let mut reader = Cursor::new(&data[..]);
+    assert!(matches!(read_string(&mut reader), Err(MapforgeError::InvalidUtf8(1))));
+}
+
+#[test]
+fn test_vbe_number_too_large() {
+    // ten bytes of seven data bits each don't fit into 64 bits
+    let mut data = vec![0xff; 9];
+    data.push(0x7f);
+    assert!(matches!(read_vbe_u_int(&mut BufReader::new(&data[..])), Err(MapforgeError::VbeNumberTooLarge)));
+    assert!(matches!(read_vbe_s_int(&mut BufReader::new(&data[..])), Err(MapforgeError::VbeNumberTooLarge)));
+
+    // a corrupt number never ending in a last byte
+    let data = [0x80; 100];
+    assert!(matches!(read_vbe_u_int(&mut BufReader::new(&data[..])), Err(MapforgeError::VbeNumberTooLarge)));
+
+    // the largest numbers still fit
+    let data = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+    assert_eq!(read_vbe_u_int(&mut BufReader::new(&data[..])).unwrap(), usize::MAX);
+    let data = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f];
+    assert_eq!(read_vbe_s_int(&mut BufReader::new(&data[..])).unwrap(), -(i64::MAX as isize >> 1));
+}
+
+#[test]
+fn test_string_longer_than_data() {
+    // a string claiming to be a terabyte long is not allocated up front
+    let mut data = vbe_u(1 << 40);
+    data.extend(b"short");
+
+    let mut reader = Cursor::new(&data[..]);
+    match read_string(&mut reader) {
+        Err(MapforgeError::Io(err)) => assert_eq!(err.kind(), ErrorKind::UnexpectedEof),
+        other => panic!("unexpected result {other:?}"),
+    }
+}
+
+#[test]
+fn test_invalid_tag_id_offset_with_padded_encoding() {
+    // tag id 200 padded with a redundant byte is still reported where it starts
+    let mut data = vbe_s(-1_000);
+    data.extend(vbe_s(1_000));
+    data.push(0x01);
+    data.extend([0xc8, 0x81, 0x00]);
+
+    let mut reader = Cursor::new(&data[..]);
+    match process_pois(&mut reader, 1, &tags(), &Default::default()) {
+        Err(MapforgeError::InvalidTagId(200, actual)) => assert_eq!(actual, data.len() as u64 - 3),
+        other => panic!("unexpected result {other:?}"),
+    }
+}
+
+#[test]
+fn test_invalid_utf8_in_tile() {
+    let mut poi = PoiFixture {
+        name: Some("ab".to_string()),
+        ..Default::default()
+    }
+    .build();
+    let length = poi.len();
+    poi[length - 2..].copy_from_slice(&[0xc3, 0x28]);
+
+    let fixture = MapFixture {
+        blocks: vec![
+            tile_block(None, &[(1, 0), (0, 0), (0, 0), (0, 0), (0, 0)], &[poi], &[]),
+            vec![],
+        ],
+        ..Default::default()
+    };
+    let bytes = fixture.build();
+    let file = fixture.write();
+    let mut map_file = MapFile::open(file.path()).unwrap();
+
+    // the offset is absolute within the file
+    match map_file.read_tile(10, 548, 336) {
+        Err(MapforgeError::InvalidUtf8(offset)) => {
+            assert_eq!(&bytes[offset as usize..offset as usize + 2], [0xc3, 0x28])
+        }
+        other => panic!("unexpected result {other:?}"),
+    }
+}
+
+#[test]
+fn test_feature_count_larger_than_data() {
+    // the counts come from the zoom table and aren't allocated up front
+    let data = PoiFixture::default().build();
+    let mut reader = Cursor::new(&data[..]);
+    assert!(matches!(process_pois(&mut reader, 1 << 40, &tags(), &Default::default()), Err(MapforgeError::Io(_))));
+
+    let data = WayFixture {
+        data_blocks: vec![vec![vec![(0, 0), (1, 1)]]],
+        ..Default::default()
+    }
+    .build();
+    let mut reader = Cursor::new(&data[..]);
+    assert!(matches!(process_ways(&mut reader, 1 << 40, &tags(), &Default::default(), None), Err(MapforgeError::Io(_))));
+}
+
+#[test]
+fn test_invalid_zoom_intervals() {
+    // base, min and max zoom level
+    for (base, min, max) in [(70, 8, 80), (70, 8, 12), (10, 0, 255), (10, 20, 0), (5, 8, 12), (14, 8, 12), (33, 33, 33)] {
+        let data = MapFixture {
+            base_zoom_level: base,
+            min_zoom_level: min,
+            max_zoom_level: max,
+            blocks: vec![vec![], vec![]],
+            ..Default::default()
+        }
+        .build();
+
+        match MapFile::from_reader(Cursor::new(&data)) {
+            Err(MapforgeError::InvalidZoomInterval(b, mi, ma)) => assert_eq!((b, mi, ma), (base, min, max)),
+            other => panic!("unexpected result {other:?} for interval {base}/{min}/{max}"),
+        }
+        assert!(matches!(MapSlice::new(&data), Err(MapforgeError::InvalidZoomInterval(..))));
+    }
+}
+
+#[test]
+fn test_too_many_tiles() {
+    let world = BoundingBox {
+        min_lat: -85.0,
+        min_lon: -180.0,
+        max_lat: 85.0,
+        max_lon: 180.0,
+    };
+    assert!(matches!(MapFile::calculate_total_tiles(&world, 32), Err(MapforgeError::TooManyTiles(32))));
+    assert!(matches!(MapFile::calculate_total_tiles(&world, 70), Err(MapforgeError::InvalidZoomLevel(70))));
+    assert_eq!(MapFile::calculate_total_tiles(&world, 1).unwrap(), 4);
+
+    // a map that claims every tile of the world on zoom level 32
+    let data = MapFixture {
+        bounding_box: (-85.0, -180.0, 85.0, 180.0),
+        base_zoom_level: 32,
+        min_zoom_level: 32,
+        max_zoom_level: 32,
+        ..Default::default()
+    }
+    .build();
+    assert!(matches!(MapFile::from_reader(Cursor::new(&data)), Err(MapforgeError::TooManyTiles(32))));
+    assert!(matches!(MapSlice::new(&data), Err(MapforgeError::TooManyTiles(32))));
+}
+
+#[test]
+fn test_tile_index_larger_than_data() {
+    // half of the world on zoom level 16, the index alone would take 10 GB
+    let data = MapFixture {
+        bounding_box: (0.0, -180.0, 85.0, 180.0),
+        base_zoom_level: 16,
+        min_zoom_level: 16,
+        max_zoom_level: 16,
+        ..Default::default()
+    }
+    .build();
+
+    match MapFile::from_reader(Cursor::new(&data)) {
+        Err(MapforgeError::Io(err)) => assert_eq!(err.kind(), ErrorKind::UnexpectedEof),
+        other => panic!("unexpected result {other:?}"),
+    }
+}
@@ -0,0 +1,48 @@
+#![cfg(feature = "mmap")]
+
+mod common;
+
+use std::io::Write;
+
+use common::features_fixture;
+use mapsforge_rs::{
+    mmap::MmapMapFile,
+    types::{MapFile, MapSlice},
+    MapforgeError, Result,
+};
+use tempfile::NamedTempFile;
+
+#[test]
+fn test_read_tile_through_mmap() -> Result<()> {
+    let file = features_fixture(false).write();
+
+    let mut map_file = MmapMapFile::open_mmap(file.path())?;
+    let tile = map_file.read_tile(10, 548, 336)?;
+    assert_eq!(tile.pois[0].name.as_deref(), Some("west"));
+    assert_eq!(tile.ways[0].name.as_deref(), Some("way"));
+
+    let expected = MapFile::open(file.path())?.read_tile(10, 548, 336)?;
+    assert_eq!(format!("{tile:?}"), format!("{expected:?}"));
+
+    // the mapped bytes can be decoded in place as well
+    let map = MapSlice::new(map_file.reader.get_ref())?;
+    assert_eq!(format!("{:?}", map.read_tile(10, 548, 336)?), format!("{expected:?}"));
+
+    Ok(())
+}
+
+#[test]
+fn test_truncated_map_through_mmap() -> Result<()> {
+    // the mapping ends with the file, reading past it is a short read
+    let mut data = features_fixture(true).build();
+    data.truncate(data.len() - 10);
+    let mut file = NamedTempFile::new()?;
+    file.write_all(&data)?;
+
+    let mut map_file = MmapMapFile::open_mmap(file.path())?;
+    assert_eq!(map_file.reader.get_ref().len(), data.len());
+    assert_eq!(map_file.read_tile(10, 548, 336)?.pois[0].debug_signature.as_deref(), Some("***POIStart1"));
+    assert!(matches!(map_file.read_tile(10, 549, 336), Err(MapforgeError::Io(_))));
+
+    Ok(())
+}
@@ -0,0 +1,272 @@
+#![cfg(feature = "mvt")]
+
+use std::io::Cursor;
+
+use mapsforge_rs::{
+    types::{BoundingBox, LatLong, MapFile, MapWriter, MvtEncoder, MvtLayerRule, Tag, Tile, Way, WayCoordinateBlock, ZoomInterval, POI},
+    Result,
+};
+
+// a decoded vector tile feature: geometry type, command integers and
+// properties with values as strings
+#[derive(Debug)]
+struct Feature {
+    geometry_type: u64,
+    geometry: Vec<u32>,
+    properties: Vec<(String, String)>,
+}
+
+#[derive(Debug)]
+struct Layer {
+    name: String,
+    extent: u64,
+    features: Vec<Feature>,
+}
+
+fn read_varint(bytes: &mut &[u8]) -> u64 {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[0];
+        *bytes = &bytes[1..];
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+// fields of a message, varints as numbers and length delimited fields as bytes
+fn fields(mut bytes: &[u8]) -> Vec<(u64, u64, Vec<u8>)> {
+    let mut fields = vec![];
+    while !bytes.is_empty() {
+        let key = read_varint(&mut bytes);
+        if key & 7 == 2 {
+            let length = read_varint(&mut bytes) as usize;
+            fields.push((key >> 3, 0, bytes[..length].to_vec()));
+            bytes = &bytes[length..];
+        } else {
+            fields.push((key >> 3, read_varint(&mut bytes), vec![]));
+        }
+    }
+    fields
+}
+
+fn packed(mut bytes: &[u8]) -> Vec<u32> {
+    let mut values = vec![];
+    while !bytes.is_empty() {
+        values.push(read_varint(&mut bytes) as u32);
+    }
+    values
+}
+
+fn decode(tile: &[u8]) -> Vec<Layer> {
+    fields(tile)
+        .into_iter()
+        .filter(|(field, _, _)| *field == 3)
+        .map(|(_, _, layer)| {
+            let layer = fields(&layer);
+            let strings = |number: u64| -> Vec<String> {
+                layer.iter().filter(|f| f.0 == number).map(|f| String::from_utf8(f.2.clone()).unwrap()).collect()
+            };
+            let keys = strings(3);
+            let values: Vec<String> = layer
+                .iter()
+                .filter(|f| f.0 == 4)
+                .map(|f| match &fields(&f.2)[0] {
+                    (1, _, string) => String::from_utf8(string.clone()).unwrap(),
+                    (6, value, _) => ((value >> 1) as i64 ^ -((value & 1) as i64)).to_string(),
+                    other => panic!("unexpected value {other:?}"),
+                })
+                .collect();
+
+            let features = layer
+                .iter()
+                .filter(|f| f.0 == 2)
+                .map(|f| {
+                    let feature = fields(&f.2);
+                    let tags = packed(&feature.iter().find(|f| f.0 == 2).unwrap().2);
+                    Feature {
+                        geometry_type: feature.iter().find(|f| f.0 == 3).unwrap().1,
+                        geometry: packed(&feature.iter().find(|f| f.0 == 4).unwrap().2),
+                        properties: tags
+                            .chunks(2)
+                            .map(|pair| (keys[pair[0] as usize].clone(), values[pair[1] as usize].clone()))
+                            .collect(),
+                    }
+                })
+                .collect();
+
+            Layer {
+                name: strings(1).remove(0),
+                extent: layer.iter().find(|f| f.0 == 5).unwrap().1,
+                features,
+            }
+        })
+        .collect()
+}
+
+// absolute positions of the geometry, one list per move to
+fn parts(geometry: &[u32]) -> Vec<Vec<(i32, i32)>> {
+    let zigzag = |value: u32| ((value >> 1) as i32) ^ -((value & 1) as i32);
+    let mut parts: Vec<Vec<(i32, i32)>> = vec![];
+    let mut cursor = (0, 0);
+    let mut index = 0;
+    while index < geometry.len() {
+        let (id, count) = (geometry[index] & 7, geometry[index] >> 3);
+        index += 1;
+        if id == 7 {
+            continue;
+        }
+        for _ in 0..count {
+            cursor = (cursor.0 + zigzag(geometry[index]), cursor.1 + zigzag(geometry[index + 1]));
+            index += 2;
+            if id == 1 {
+                parts.push(vec![]);
+            }
+            parts.last_mut().unwrap().push(cursor);
+        }
+    }
+    parts
+}
+
+// twice the area by the surveyor's formula
+fn area(ring: &[(i32, i32)]) -> i64 {
+    (0..ring.len())
+        .map(|i| {
+            let (a, b) = (ring[i], ring[(i + 1) % ring.len()]);
+            a.0 as i64 * b.1 as i64 - b.0 as i64 * a.1 as i64
+        })
+        .sum()
+}
+
+// the position at pixel (x, y) of tile 548/336 on zoom level 10 with an extent
+// of 4096, x and y are multiples of 2048 so they are corners of tiles
+fn position(x: u32, y: u32) -> LatLong {
+    LatLong {
+        latitude: MapFile::tiley2lat(336 * 2 + y / 2048, 11),
+        longitude: MapFile::tilex2long(548 * 2 + x / 2048, 11),
+    }
+}
+
+fn way(key: &str, value: &str, blocks: Vec<Vec<LatLong>>) -> Way {
+    Way {
+        layer: 5,
+        tags: Some(vec![Tag::new(key, value)]),
+        coordinate_blocks: vec![blocks
+            .into_iter()
+            .map(WayCoordinateBlock::new)
+            .collect()],
+        ..Default::default()
+    }
+}
+
+fn poi(key: &str, value: &str, position: LatLong) -> POI {
+    POI {
+        position,
+        layer: 5,
+        tags: Some(vec![Tag::new(key, value)]),
+        name: Some("Ecke".to_string()),
+        ..Default::default()
+    }
+}
+
+fn encoder() -> MvtEncoder {
+    MvtEncoder::new(vec![
+        MvtLayerRule::new("amenity", None, "pois"),
+        MvtLayerRule::new("highway", None, "roads"),
+        MvtLayerRule::new("landuse", Some("forest"), "landuse"),
+    ])
+}
+
+fn encode(pois: Vec<POI>, ways: Vec<Way>) -> Vec<Layer> {
+    let tile = Tile {
+        pois,
+        ways,
+        ..Default::default()
+    };
+    decode(&encoder().encode_tile(&tile, 10, 548, 336))
+}
+
+#[test]
+fn test_points_are_projected_into_the_extent() {
+    let layers = encode(vec![poi("amenity", "cafe", position(2048, 2048)), poi("amenity", "bench", position(0, 0))], vec![]);
+
+    assert_eq!(layers.len(), 1);
+    assert_eq!(layers[0].name, "pois");
+    assert_eq!(layers[0].extent, 4096);
+    let features = &layers[0].features;
+    assert_eq!(features[0].geometry_type, 1);
+    assert_eq!(parts(&features[0].geometry), [[(2048, 2048)]]);
+    assert_eq!(parts(&features[1].geometry), [[(0, 0)]]);
+    assert_eq!(
+        features[0].properties,
+        [("amenity".to_string(), "cafe".to_string()), ("layer".to_string(), "5".to_string()), ("name".to_string(), "Ecke".to_string())]
+    );
+}
+
+#[test]
+fn test_lines_are_clipped_to_the_buffer() {
+    // from the middle of the tile to the middle of the next tile to the east
+    let road = way("highway", "primary", vec![vec![position(2048, 2048), position(6144, 2048)]]);
+    let layers = encode(vec![poi("amenity", "cafe", position(6144, 2048))], vec![road]);
+
+    assert_eq!(layers.iter().map(|layer| layer.name.as_str()).collect::<Vec<_>>(), ["roads"]);
+    let feature = &layers[0].features[0];
+    assert_eq!(feature.geometry_type, 2);
+    assert_eq!(parts(&feature.geometry), [[(2048, 2048), (4096 + 64, 2048)]]);
+}
+
+#[test]
+fn test_polygon_winding_order() {
+    let outer = vec![position(0, 0), position(0, 4096), position(4096, 4096), position(4096, 0), position(0, 0)];
+    let hole = vec![position(2048, 2048), position(4096, 2048), position(4096, 4096), position(2048, 4096), position(2048, 2048)];
+    let layers = encode(vec![], vec![way("landuse", "forest", vec![outer, hole]), way("landuse", "meadow", vec![])]);
+
+    let feature = &layers[0].features[0];
+    assert_eq!(feature.geometry_type, 3);
+    let rings = parts(&feature.geometry);
+    assert_eq!(rings.len(), 2);
+    assert!(area(&rings[0]) > 0);
+    assert!(area(&rings[1]) < 0);
+    // the meadow matches no rule
+    assert_eq!(layers[0].features.len(), 1);
+}
+
+#[test]
+fn test_default_layer() {
+    let mut encoder = encoder();
+    encoder.default_layer = Some("other".to_string());
+    let tile = Tile {
+        pois: vec![poi("shop", "bakery", position(1024, 1024))],
+        ..Default::default()
+    };
+
+    let layers = decode(&encoder.encode_tile(&tile, 10, 548, 336));
+    assert_eq!(layers[0].name, "other");
+}
+
+#[test]
+fn test_read_mvt_from_map() -> Result<()> {
+    let bbox = BoundingBox {
+        min_lat: 52.3,
+        min_lon: 12.8,
+        max_lat: 52.4,
+        max_lon: 13.2,
+    };
+    let mut writer = MapWriter::new(bbox, vec![ZoomInterval::new(10, 8, 14)]);
+    writer.add_poi(poi("amenity", "cafe", LatLong { latitude: 52.35, longitude: 12.85 }), 10);
+    writer.add_way(way("highway", "primary", vec![vec![LatLong { latitude: 52.32, longitude: 12.9 }, LatLong { latitude: 52.34, longitude: 13.1 }]]), 10);
+    let mut bytes = vec![];
+    writer.write_to(&mut bytes)?;
+    let mut map_file = MapFile::from_reader(Cursor::new(bytes))?;
+
+    let layers = decode(&map_file.read_mvt(&encoder(), 10, 548, 336)?);
+    assert_eq!(layers.iter().map(|layer| layer.name.as_str()).collect::<Vec<_>>(), ["pois", "roads"]);
+    // the road leaves the tile to the east, it is clipped at the buffer
+    let road = parts(&layers[1].features[0].geometry);
+    assert_eq!(road[0].last().unwrap().0, 4096 + 64);
+
+    Ok(())
+}
@@ -0,0 +1,460 @@
+#![cfg(feature = "osm")]
+
+use std::{collections::HashMap, io::{Cursor, Write}};
+
+use flate2::{write::ZlibEncoder, Compression};
+use mapsforge_rs::{
+    types::{BoundingBox, MapFile, OsmConverter, Tile, Way, ZoomInterval},
+    MapforgeError, Result,
+};
+
+// a minimal protobuf encoder for the messages of the PBF format
+#[derive(Default)]
+struct Message(Vec<u8>);
+
+impl Message {
+    fn varint(mut self, field: u64, value: u64) -> Self {
+        encode_varint(&mut self.0, field << 3);
+        encode_varint(&mut self.0, value);
+        self
+    }
+
+    fn bytes(mut self, field: u64, bytes: &[u8]) -> Self {
+        encode_varint(&mut self.0, field << 3 | 2);
+        encode_varint(&mut self.0, bytes.len() as u64);
+        self.0.extend(bytes);
+        self
+    }
+
+    fn packed(self, field: u64, values: &[u64]) -> Self {
+        let mut bytes = vec![];
+        for &value in values {
+            encode_varint(&mut bytes, value);
+        }
+        self.bytes(field, &bytes)
+    }
+
+    fn message(self, field: u64, message: Message) -> Self {
+        self.bytes(field, &message.0)
+    }
+}
+
+fn encode_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn deltas(values: &[i64]) -> Vec<u64> {
+    let mut previous = 0;
+    values
+        .iter()
+        .map(|&value| {
+            let delta = value - previous;
+            previous = value;
+            zigzag(delta)
+        })
+        .collect()
+}
+
+// a blob with its header, data blobs are zlib compressed, the header blob raw
+fn blob(out: &mut Vec<u8>, blob_type: &str, block: Message) {
+    let blob = if blob_type == "OSMData" {
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(&block.0).unwrap();
+        Message::default().varint(2, block.0.len() as u64).bytes(3, &encoder.finish().unwrap())
+    } else {
+        Message::default().bytes(1, &block.0)
+    };
+
+    let header = Message::default().bytes(1, blob_type.as_bytes()).varint(3, blob.0.len() as u64);
+    out.extend((header.0.len() as u32).to_be_bytes());
+    out.extend(header.0);
+    out.extend(blob.0);
+}
+
+type Tags<'a> = &'a [(&'a str, &'a str)];
+// way members with their role
+type Members<'a> = Vec<(i64, &'a str)>;
+
+// builds a PBF extract with the default granularity of 100 nanodegrees
+#[derive(Default)]
+struct Extract<'a> {
+    strings: Vec<String>,
+    nodes: Vec<(i64, f64, f64, Tags<'a>)>,
+    ways: Vec<(i64, Vec<i64>, Tags<'a>)>,
+    relations: Vec<(i64, Members<'a>, Tags<'a>)>,
+}
+
+impl<'a> Extract<'a> {
+    fn string(&mut self, string: &str) -> u64 {
+        if let Some(index) = self.strings.iter().position(|s| s == string) {
+            return index as u64;
+        }
+        self.strings.push(string.to_string());
+        self.strings.len() as u64 - 1
+    }
+
+    fn tags(&mut self, tags: Tags) -> (Vec<u64>, Vec<u64>) {
+        tags.iter().map(|(key, value)| (self.string(key), self.string(value))).unzip()
+    }
+
+    fn encode(mut self, bbox: Option<BoundingBox>) -> Vec<u8> {
+        // the empty string comes first, 0 ends the tags of a dense node
+        self.string("");
+
+        let nodes = std::mem::take(&mut self.nodes);
+        let mut keys_values = vec![];
+        for &(_, _, _, tags) in &nodes {
+            for (key, value) in tags.iter() {
+                keys_values.push(self.string(key));
+                keys_values.push(self.string(value));
+            }
+            keys_values.push(0);
+        }
+        let dense = Message::default()
+            .packed(1, &deltas(&nodes.iter().map(|node| node.0).collect::<Vec<_>>()))
+            .packed(8, &deltas(&nodes.iter().map(|node| (node.1 * 1e7).round() as i64).collect::<Vec<_>>()))
+            .packed(9, &deltas(&nodes.iter().map(|node| (node.2 * 1e7).round() as i64).collect::<Vec<_>>()))
+            .packed(10, &keys_values);
+        let mut group = Message::default().message(2, dense);
+
+        for (id, refs, tags) in std::mem::take(&mut self.ways) {
+            let (keys, values) = self.tags(tags);
+            let way = Message::default().varint(1, id as u64).packed(2, &keys).packed(3, &values).packed(8, &deltas(&refs));
+            group = group.message(3, way);
+        }
+
+        for (id, members, tags) in std::mem::take(&mut self.relations) {
+            let (keys, values) = self.tags(tags);
+            let roles: Vec<u64> = members.iter().map(|(_, role)| self.string(role)).collect();
+            let ids: Vec<i64> = members.iter().map(|(id, _)| *id).collect();
+            let relation = Message::default()
+                .varint(1, id as u64)
+                .packed(2, &keys)
+                .packed(3, &values)
+                .packed(8, &roles)
+                .packed(9, &deltas(&ids))
+                .packed(10, &vec![1; members.len()]);
+            group = group.message(4, relation);
+        }
+
+        let mut string_table = Message::default();
+        for string in &self.strings {
+            string_table = string_table.bytes(1, string.as_bytes());
+        }
+
+        let mut header = Message::default().bytes(4, b"OsmSchema-V0.6").bytes(4, b"DenseNodes");
+        if let Some(bbox) = bbox {
+            let nano = |degrees: f64| zigzag((degrees * 1e9).round() as i64);
+            let header_bbox = Message::default()
+                .varint(1, nano(bbox.min_lon))
+                .varint(2, nano(bbox.max_lon))
+                .varint(3, nano(bbox.max_lat))
+                .varint(4, nano(bbox.min_lat));
+            header = header.message(1, header_bbox);
+        }
+
+        let mut out = vec![];
+        blob(&mut out, "OSMHeader", header);
+        blob(&mut out, "OSMData", Message::default().message(1, string_table).message(2, group));
+        out
+    }
+}
+
+// around the border of the tiles 548 and 549 on zoom level 10 at longitude 13.0078125
+fn extract() -> Vec<u8> {
+    let extract = Extract {
+        nodes: vec![
+            (1, 52.35, 12.85, &[("amenity", "cafe"), ("name", "Ecke"), ("wheelchair", "yes")]),
+            (2, 52.36, 12.9, &[("amenity", "bench")]),
+            (3, 52.38, 12.95, &[("shop", "bakery"), ("ele", "34.6"), ("layer", "1"), ("addr:housenumber", "7")]),
+            // the road
+            (11, 52.32, 12.9, &[]),
+            (12, 52.33, 12.98, &[]),
+            (13, 52.34, 13.1, &[]),
+            // the building
+            (21, 52.385, 12.9, &[]),
+            (22, 52.385, 12.91, &[]),
+            (23, 52.39, 12.91, &[]),
+            (24, 52.39, 12.9, &[]),
+            // the forest and its clearing
+            (31, 52.31, 12.82, &[]),
+            (32, 52.31, 12.88, &[]),
+            (33, 52.37, 12.88, &[]),
+            (34, 52.37, 12.82, &[]),
+            (35, 52.33, 12.84, &[]),
+            (36, 52.33, 12.86, &[]),
+            (37, 52.35, 12.86, &[]),
+            (38, 52.35, 12.84, &[]),
+        ],
+        ways: vec![
+            (10, vec![11, 12, 13], &[("highway", "primary"), ("ref", "B 1"), ("surface", "asphalt")]),
+            (20, vec![21, 22, 23, 24, 21], &[("building", "yes")]),
+            (30, vec![31, 32, 33], &[]),
+            (31, vec![31, 34, 33], &[]),
+            (32, vec![35, 36, 37, 38, 35], &[]),
+        ],
+        relations: vec![(40, vec![(30, "outer"), (32, "inner"), (31, "outer")], &[("type", "multipolygon"), ("landuse", "forest"), ("name", "Wald")])],
+        ..Default::default()
+    };
+
+    extract.encode(Some(BoundingBox {
+        min_lat: 52.3,
+        min_lon: 12.8,
+        max_lat: 52.4,
+        max_lon: 13.2,
+    }))
+}
+
+fn converter() -> OsmConverter {
+    let poi_tags = HashMap::from([("amenity=cafe".to_string(), 12), ("shop=*".to_string(), 14)]);
+    let way_tags = HashMap::from([
+        ("highway=*".to_string(), 10),
+        ("building=yes".to_string(), 14),
+        ("landuse=forest".to_string(), 11),
+    ]);
+
+    let mut converter = OsmConverter::new(poi_tags, way_tags);
+    converter.zoom_intervals = vec![ZoomInterval::new(10, 8, 14)];
+    converter
+}
+
+fn convert(converter: &OsmConverter, extract: &[u8]) -> Result<MapFile<Cursor<Vec<u8>>>> {
+    let mut bytes = vec![];
+    converter.convert(extract)?.write_to(&mut bytes)?;
+    MapFile::from_reader(Cursor::new(bytes))
+}
+
+#[test]
+fn test_converted_header() -> Result<()> {
+    let map_file = convert(&converter(), &extract())?;
+    let header = &map_file.header;
+
+    assert!((header.bounding_box.min_lat - 52.3).abs() < 1e-6);
+    assert!((header.bounding_box.max_lon - 13.2).abs() < 1e-6);
+    // only whitelisted tags end up in the tag tables
+    assert_eq!(header.poi_tags, ["amenity=cafe", "shop=bakery"]);
+    assert_eq!(header.way_tags, ["building=yes", "highway=primary", "landuse=forest"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_converted_pois() -> Result<()> {
+    let mut map_file = convert(&converter(), &extract())?;
+
+    let tile = map_file.read_tile(12, 548 << 2, 336 << 2)?;
+    assert_eq!(tile.pois.len(), 1);
+    let cafe = &tile.pois[0];
+    assert_eq!(cafe.name.as_deref(), Some("Ecke"));
+    assert_eq!(cafe.layer, 5);
+    assert!((cafe.position.latitude - 52.35).abs() < 1e-6);
+
+    let tile = map_file.read_tile(14, 548 << 4, 336 << 4)?;
+    let bakery = tile.pois.iter().find(|poi| poi.house_number.is_some()).unwrap();
+    assert_eq!(bakery.house_number.as_deref(), Some("7"));
+    assert_eq!(bakery.elevation, Some(35));
+    assert_eq!(bakery.layer, 6);
+    assert_eq!(tile.pois.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_converted_ways_are_clipped() -> Result<()> {
+    let mut map_file = convert(&converter(), &extract())?;
+    let border = MapFile::tilex2long(549, 10);
+
+    let west = map_file.read_tile(10, 548, 336)?;
+    assert_eq!(west.ways.len(), 1);
+    let road = &west.ways[0];
+    assert_eq!(road.reference.as_deref(), Some("B 1"));
+    let nodes = &road.coordinate_blocks[0][0].coordinates;
+    assert_eq!(nodes.len(), 3);
+    assert!(nodes[2].longitude > border && nodes[2].longitude < border + 0.02);
+
+    let east = map_file.read_tile(10, 549, 336)?;
+    let nodes = &east.ways[0].coordinate_blocks[0][0].coordinates;
+    assert_eq!(nodes.len(), 2);
+    assert!(nodes[0].longitude < border && nodes[0].longitude > border - 0.02);
+    assert!((nodes[1].longitude - 13.1).abs() < 1e-6);
+
+    Ok(())
+}
+
+#[test]
+fn test_converted_polygons() -> Result<()> {
+    let mut map_file = convert(&converter(), &extract())?;
+
+    let (x, y) = MapFile::get_tiles(52.34, 12.85, 11)?;
+    let tile = map_file.read_tile(11, x, y)?;
+    let forest = tile.ways.iter().find(|way| way.name.as_deref() == Some("Wald")).unwrap();
+    // the outer ring joined from two ways and the clearing
+    assert_eq!(forest.coordinate_blocks[0].len(), 2);
+    assert_eq!(forest.coordinate_blocks[0][0].coordinates.len(), 5);
+    assert_eq!(forest.coordinate_blocks[0][1].coordinates.len(), 5);
+    assert_eq!(forest.tags.as_ref().unwrap().iter().map(|tag| tag.key.as_str()).collect::<Vec<_>>(), ["landuse", "name"]);
+    assert!(tile.ways.iter().all(|way| way.tags.as_ref().unwrap()[0].key != "building"));
+
+    let (x, y) = MapFile::get_tiles(52.387, 12.905, 14)?;
+    let tile = map_file.read_tile(14, x, y)?;
+    let building = tile.ways.iter().find(|way| way.tags.as_ref().unwrap()[0].key == "building").unwrap();
+    let ring = &building.coordinate_blocks[0][0].coordinates;
+    assert_eq!(ring.len(), 5);
+    assert_eq!(ring[0].latitude, ring[4].latitude);
+
+    Ok(())
+}
+
+#[test]
+fn test_bounding_box_from_features() -> Result<()> {
+    let extract = Extract {
+        nodes: vec![(1, 52.35, 12.85, &[("amenity", "cafe")]), (2, 52.36, 12.9, &[("shop", "kiosk")])],
+        ..Default::default()
+    };
+
+    let map_file = convert(&converter(), &extract.encode(None))?;
+    let bbox = map_file.header.bounding_box;
+    assert!((bbox.min_lat - 52.35).abs() < 1e-6 && (bbox.max_lat - 52.36).abs() < 1e-6);
+    assert!((bbox.min_lon - 12.85).abs() < 1e-6 && (bbox.max_lon - 12.9).abs() < 1e-6);
+
+    Ok(())
+}
+
+#[test]
+fn test_invalid_pbf() {
+    let mut truncated = extract();
+    truncated.truncate(truncated.len() - 10);
+    assert!(matches!(converter().convert(truncated.as_slice()), Err(MapforgeError::Io(_))));
+
+    let mut unsupported = vec![];
+    blob(&mut unsupported, "OSMHeader", Message::default().bytes(4, b"HistoricalInformation"));
+    assert!(matches!(converter().convert(unsupported.as_slice()), Err(MapforgeError::InvalidPbf(_))));
+
+    // only the first two bytes of the next blob size
+    let mut cut_off = extract();
+    cut_off.extend([0, 0]);
+    assert!(matches!(converter().convert(cut_off.as_slice()), Err(MapforgeError::InvalidPbf(_))));
+}
+
+#[test]
+fn test_pbf_values_out_of_range() {
+    let convert_group = |group: Message| {
+        let mut pbf = vec![];
+        blob(&mut pbf, "OSMHeader", Message::default().bytes(4, b"OsmSchema-V0.6"));
+        blob(&mut pbf, "OSMData", Message::default().message(2, group));
+        converter().convert(pbf.as_slice())
+    };
+
+    // the second node ref overflows
+    let way = Message::default().varint(1, 1).packed(8, &[zigzag(i64::MAX), zigzag(1)]);
+    assert!(matches!(convert_group(Message::default().message(3, way)), Err(MapforgeError::InvalidPbf(_))));
+
+    // the node id is fine but the latitude times the granularity overflows
+    let dense = Message::default().packed(1, &[zigzag(1)]).packed(8, &[zigzag(i64::MAX / 10)]).packed(9, &[zigzag(0)]);
+    assert!(matches!(convert_group(Message::default().message(2, dense)), Err(MapforgeError::InvalidPbf(_))));
+}
+
+// a small extract laid out like the ones OSM tools write: dense nodes with
+// metadata, one block per entity type and a relation that isn't a
+// multipolygon. it is written by fixtures/village_pbf.py, not by an OSM tool
+const VILLAGE: &[u8] = include_bytes!("fixtures/village.osm.pbf");
+
+fn village_converter() -> OsmConverter {
+    let poi_tags = HashMap::from([
+        ("amenity=cafe".to_string(), 12),
+        ("shop=*".to_string(), 14),
+        ("natural=peak".to_string(), 13),
+    ]);
+    let way_tags = HashMap::from([
+        ("highway=*".to_string(), 10),
+        ("railway=rail".to_string(), 10),
+        ("building=*".to_string(), 14),
+        ("natural=water".to_string(), 10),
+        ("bridge=yes".to_string(), 14),
+        ("tunnel=yes".to_string(), 14),
+    ]);
+
+    OsmConverter::new(poi_tags, way_tags)
+}
+
+fn tag<'a>(way: &'a Way, key: &str) -> Option<&'a str> {
+    way.tags.as_ref()?.iter().find(|tag| tag.key == key).map(|tag| tag.value.as_str())
+}
+
+#[test]
+fn test_converted_village_extract() -> Result<()> {
+    let mut map_file = convert(&village_converter(), VILLAGE)?;
+
+    let bbox = map_file.header.bounding_box;
+    assert!((bbox.min_lat - 52.31).abs() < 1e-6 && (bbox.max_lon - 13.05).abs() < 1e-6);
+    assert_eq!(map_file.header.zoom_interval_configuration.len(), 3);
+
+    let result = map_file.query(bbox, 14)?;
+    let mut names: Vec<_> = result.pois.iter().map(|poi| poi.name.as_deref()).collect();
+    names.sort();
+    assert_eq!(names, [None, Some("Café am See"), Some("Kleiner Berg")]);
+    let peak = result.pois.iter().find(|poi| poi.name.as_deref() == Some("Kleiner Berg")).unwrap();
+    assert_eq!(peak.elevation, Some(68));
+
+    // the bus route isn't converted
+    assert!(result.ways.iter().all(|way| way.reference.as_deref() != Some("614")));
+
+    // the footway without its missing middle node
+    let footway = result.ways.iter().find(|way| tag(way, "highway") == Some("footway")).unwrap();
+    assert_eq!(footway.coordinate_blocks[0][0].coordinates.len(), 2);
+
+    let school = result.ways.iter().find(|way| way.name.as_deref() == Some("Grundschule")).unwrap();
+    assert_eq!(school.house_number.as_deref(), Some("12"));
+    assert_eq!(tag(school, "building"), Some("school"));
+
+    Ok(())
+}
+
+#[test]
+fn test_converted_village_layers() -> Result<()> {
+    let mut map_file = convert(&village_converter(), VILLAGE)?;
+    let tile = map_file.read_tile(10, 548, 336)?;
+
+    let layer = |key: &str, value: &str| tile.ways.iter().find(|way| tag(way, key) == Some(value)).unwrap().layer;
+    assert_eq!(layer("bridge", "yes"), 6);
+    assert_eq!(layer("tunnel", "yes"), 4);
+    // the highest layer mapsforge can store
+    assert_eq!(layer("highway", "service"), 15);
+    // layer 12 can't be stored and falls back to the default layer
+    assert_eq!(layer("railway", "rail"), 5);
+
+    let (x, y) = MapFile::get_tiles(52.3618, 12.9702, 14)?;
+    let tile = map_file.read_tile(14, x, y)?;
+    let bakery = tile.pois.iter().find(|poi| poi.house_number.as_deref() == Some("3")).unwrap();
+    assert_eq!(bakery.layer, 4);
+
+    Ok(())
+}
+
+#[test]
+fn test_converted_village_lakes() -> Result<()> {
+    let mut map_file = convert(&village_converter(), VILLAGE)?;
+    let tile = map_file.read_tile(10, 548, 336)?;
+
+    // both lakes of the relation in one way, the first one with its island
+    let lakes = tile.ways.iter().find(|way| way.name.as_deref() == Some("Seen")).unwrap();
+    assert_eq!(lakes.coordinate_blocks.len(), 2);
+    assert_eq!(lakes.coordinate_blocks[0].len(), 2);
+    assert_eq!(lakes.coordinate_blocks[1].len(), 1);
+    assert_eq!(lakes.coordinate_blocks[0][0].coordinates.len(), 5);
+
+    // the street crosses into the next tile
+    let street = |tile: &Tile| tile.ways.iter().any(|way| way.name.as_deref() == Some("Dorfstraße"));
+    assert!(street(&tile));
+    assert!(street(&map_file.read_tile(10, 549, 336)?));
+
+    Ok(())
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use std::io::Cursor;
+
+use common::{tile_block, vbe_string, MapFixture, PoiFixture};
+use mapsforge_rs::{poi::process_pois, types::{LatLong, MapFile, TileContext}, Result};
+
+fn poi_tags() -> Vec<String> {
+    vec![
+        "amenity=restaurant".to_string(),
+        "natural=peak".to_string(),
+        "cuisine=%s".to_string(),
+        "capacity=%i".to_string(),
+    ]
+}
+
+#[test]
+fn test_process_pois_reads_optional_fields() -> Result<()> {
+    let mut data = PoiFixture {
+        lat_diff: -1_000,
+        lon_diff: 2_000,
+        layer: 5,
+        tag_ids: vec![0],
+        name: Some("Zur Post".to_string()),
+        house_number: Some("12a".to_string()),
+        ..Default::default()
+    }
+    .build();
+    data.extend(
+        PoiFixture {
+            lat_diff: -3_000,
+            lon_diff: 4_000,
+            layer: 7,
+            tag_ids: vec![1],
+            name: Some("Brocken".to_string()),
+            elevation: Some(1141),
+            ..Default::default()
+        }
+        .build(),
+    );
+    data.extend(
+        PoiFixture {
+            lat_diff: 5,
+            lon_diff: -6,
+            ..Default::default()
+        }
+        .build(),
+    );
+
+    let tile = TileContext {
+        origin: LatLong { latitude: 52.0, longitude: 13.0 },
+        ..Default::default()
+    };
+    let mut reader = Cursor::new(&data[..]);
+    let pois = process_pois(&mut reader, 3, &poi_tags(), &tile)?;
+
+    assert_eq!(pois.len(), 3);
+
+    assert_eq!(pois[0].layer, 5);
+    assert_eq!(pois[0].tag_ids, vec![0]);
+    let tags = pois[0].tags.as_ref().unwrap();
+    assert_eq!(tags[0].key, "amenity");
+    assert_eq!(tags[0].value, "restaurant");
+    // name and house number are among the tags as well, like those of ways
+    assert_eq!((tags[1].key.as_str(), tags[1].value.as_str()), ("name", "Zur Post"));
+    assert_eq!((tags[2].key.as_str(), tags[2].value.as_str()), ("addr:housenumber", "12a"));
+    assert_eq!(pois[0].name.as_deref(), Some("Zur Post"));
+    assert_eq!(pois[0].house_number.as_deref(), Some("12a"));
+    assert_eq!(pois[0].elevation, None);
+    assert!((pois[0].position_offset.latitude + 0.001).abs() < 1e-12);
+    assert!((pois[0].position.latitude - 51.999).abs() < 1e-9);
+    assert!((pois[0].position.longitude - 13.002).abs() < 1e-9);
+
+    assert_eq!(pois[1].layer, 7);
+    assert_eq!(pois[1].tags.as_ref().unwrap()[0].value, "peak");
+    assert_eq!(pois[1].name.as_deref(), Some("Brocken"));
+    assert_eq!(pois[1].house_number, None);
+    assert_eq!(pois[1].elevation, Some(1141));
+
+    assert_eq!(pois[2].layer, 0);
+    assert!(pois[2].tag_ids.is_empty());
+    assert_eq!(pois[2].name, None);
+    assert!((pois[2].position_offset.longitude + 0.000006).abs() < 1e-12);
+
+    // everything has been consumed
+    assert_eq!(reader.position(), data.len() as u64);
+
+    Ok(())
+}
+
+#[test]
+fn test_process_pois_reads_variable_tag_values() -> Result<()> {
+    let mut data = PoiFixture {
+        tag_ids: vec![0, 2, 3],
+        ..Default::default()
+    }
+    .build();
+    // the variable values follow the tag ids, before the flags byte
+    let flags = data.pop().unwrap();
+    data.extend(vbe_string("italian"));
+    data.extend(120i32.to_be_bytes());
+    data.push(flags);
+
+    let mut reader = Cursor::new(&data[..]);
+    let pois = process_pois(&mut reader, 1, &poi_tags(), &Default::default())?;
+
+    let tags = pois[0].tags.as_ref().unwrap();
+    assert_eq!(pois[0].tag_ids, vec![0, 2, 3]);
+    assert_eq!((tags[1].key.as_str(), tags[1].value.as_str()), ("cuisine", "italian"));
+    assert_eq!((tags[2].key.as_str(), tags[2].value.as_str()), ("capacity", "120"));
+
+    Ok(())
+}
+
+#[test]
+fn test_read_tile_with_several_pois() -> Result<()> {
+    let pois: Vec<Vec<u8>> = (0..3)
+        .map(|i| {
+            PoiFixture {
+                lat_diff: -10_000 * i,
+                lon_diff: 10_000 * i,
+                tag_ids: vec![(i % 2) as u64],
+                name: Some(format!("poi {i}")),
+                ..Default::default()
+            }
+            .build()
+        })
+        .collect();
+
+    let file = MapFixture {
+        poi_tags: poi_tags(),
+        blocks: vec![
+            tile_block(None, &[(1, 0), (0, 0), (2, 0), (0, 0), (0, 0)], &pois, &[]),
+            vec![],
+        ],
+        ..Default::default()
+    }
+    .write();
+    let mut map_file = MapFile::open(file.path())?;
+
+    let tile = map_file.read_tile(10, 548, 336)?;
+    let names: Vec<_> = tile.pois.iter().map(|poi| poi.name.clone().unwrap()).collect();
+    assert_eq!(names, ["poi 0", "poi 1", "poi 2"]);
+    assert_eq!(tile.pois[1].tags.as_ref().unwrap()[0].key, "natural");
+    assert_eq!(tile.pois[2].tags.as_ref().unwrap()[0].key, "amenity");
+
+    Ok(())
+}
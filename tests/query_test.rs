@@ -0,0 +1,124 @@
+mod common;
+
+use std::io::Cursor;
+
+use common::{tile_block, MapFixture, PoiFixture};
+use mapsforge_rs::{
+    types::{LatLong, MapFile, MapWriter, Tag, Way, WayCoordinateBlock, ZoomInterval},
+    BoundingBox, MapforgeError, Result,
+};
+
+const NO_FEATURES: [(u64, u64); 5] = [(0, 0); 5];
+
+// one POI in each of the tiles (548, 336) and (549, 336) on zoom level 10
+fn fixture() -> MapFixture {
+    let poi = |lat_diff, lon_diff| PoiFixture {
+        lat_diff,
+        lon_diff,
+        ..Default::default()
+    };
+
+    MapFixture {
+        blocks: vec![
+            tile_block(None, &[(0, 0), (0, 0), (1, 0), (0, 0), (0, 0)], &[poi(-130_000, 300_000).build()], &[]),
+            tile_block(None, &[(0, 0), (0, 0), (1, 0), (0, 0), (0, 0)], &[poi(-130_000, 100_000).build()], &[]),
+        ],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_query_filters_pois_by_bounding_box() -> Result<()> {
+    let file = fixture().write();
+    let mut map_file = MapFile::open(file.path())?;
+
+    let bbox = BoundingBox {
+        min_lat: 52.3,
+        min_lon: 12.9,
+        max_lat: 52.4,
+        max_lon: 13.05,
+    };
+    let result = map_file.query(bbox, 10)?;
+
+    assert_eq!(result.pois.len(), 1);
+    let poi = &result.pois[0];
+    let expected_lat = MapFile::tiley2lat(336, 10) - 0.13;
+    let expected_lon = MapFile::tilex2long(548, 10) + 0.3;
+    assert!((poi.position.latitude - expected_lat).abs() < 1e-9);
+    assert!((poi.position.longitude - expected_lon).abs() < 1e-9);
+
+    // the whole map returns the POIs of both tiles
+    let result = map_file.query(map_file.header.bounding_box, 10)?;
+    assert_eq!(result.pois.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_query_outside_of_map() -> Result<()> {
+    let file = fixture().write();
+    let mut map_file = MapFile::open(file.path())?;
+
+    let bbox = BoundingBox {
+        min_lat: -10.0,
+        min_lon: -10.0,
+        max_lat: 10.0,
+        max_lon: 10.0,
+    };
+    let result = map_file.query(bbox, 10)?;
+
+    assert!(result.pois.is_empty());
+    assert!(result.ways.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_query_invalid_zoom_level() {
+    let file = MapFixture {
+        blocks: vec![tile_block(None, &NO_FEATURES, &[], &[]), vec![]],
+        ..Default::default()
+    }
+    .write();
+    let mut map_file = MapFile::open(file.path()).unwrap();
+
+    let bbox = map_file.header.bounding_box;
+    assert!(matches!(
+        map_file.query(bbox, 20),
+        Err(MapforgeError::InvalidZoomLevel(20))
+    ));
+}
+
+#[test]
+fn test_query_returns_ways_once() -> Result<()> {
+    let bbox = BoundingBox {
+        min_lat: 52.3,
+        min_lon: 12.8,
+        max_lat: 52.4,
+        max_lon: 13.5,
+    };
+    let nodes = [(52.35, 12.9), (52.351, 13.2), (52.36, 13.45)];
+    let coordinates: Vec<LatLong> = nodes.iter().map(|&(latitude, longitude)| LatLong { latitude, longitude }).collect();
+    let highway = Way {
+        layer: 5,
+        tags: Some(vec![Tag::new("highway", "primary")]),
+        coordinate_blocks: vec![vec![WayCoordinateBlock::new(coordinates)]],
+        ..Default::default()
+    };
+
+    let mut writer = MapWriter::new(bbox, vec![ZoomInterval::new(10, 8, 14)]);
+    writer.add_way(highway, 8);
+    let mut bytes = vec![];
+    writer.write_to(&mut bytes)?;
+    let mut map_file = MapFile::from_reader(Cursor::new(bytes))?;
+
+    // the highway is stored in each of the tiles 548 to 550 on zoom level 10
+    let stored: usize = (548..=550).map(|x| map_file.read_tile(10, x, 336).unwrap().ways.len()).sum();
+    assert_eq!(stored, 3);
+
+    assert_eq!(map_file.query(bbox, 10)?.ways.len(), 1);
+    // tile 137 on zoom level 8 spans the base tiles 548 to 551
+    assert_eq!(map_file.read_tile(8, 137, 84)?.ways.len(), 1);
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use std::{
+    io::{ErrorKind, Write},
+    sync::Arc,
+    thread,
+};
+
+use common::features_fixture;
+use mapsforge_rs::{
+    types::{BoundingBox, MapFile, SharedMapFile},
+    MapforgeError, Result,
+};
+use tempfile::NamedTempFile;
+
+#[test]
+fn test_shared_map_file_is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<SharedMapFile>();
+}
+
+#[test]
+fn test_concurrent_tile_reads() -> Result<()> {
+    let file = features_fixture(true).write();
+    let shared = Arc::new(SharedMapFile::open(file.path())?);
+    let mut map_file = MapFile::open(file.path())?;
+
+    let tiles = [(8, 137, 84), (10, 548, 336), (10, 549, 336), (12, 548 * 4, 336 * 4), (12, 548 * 4 + 3, 336 * 4)];
+    let expected: Vec<String> = tiles
+        .iter()
+        .map(|&(zoom, x, y)| map_file.read_tile(zoom, x, y).map(|tile| format!("{tile:?}")))
+        .collect::<Result<_>>()?;
+
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || -> Result<Vec<String>> {
+                let mut tiles_read = vec![];
+                for _ in 0..20 {
+                    tiles_read = tiles
+                        .iter()
+                        .map(|&(zoom, x, y)| shared.read_tile(zoom, x, y).map(|tile| format!("{tile:?}")))
+                        .collect::<Result<_>>()?;
+                }
+                Ok(tiles_read)
+            })
+        })
+        .collect();
+
+    for thread in threads {
+        assert_eq!(thread.join().unwrap()?, expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_shared_query() -> Result<()> {
+    let file = features_fixture(true).write();
+    let shared = SharedMapFile::open(file.path())?;
+
+    let bbox = BoundingBox {
+        min_lat: 52.0,
+        min_lon: 12.0,
+        max_lat: 53.0,
+        max_lon: 14.0,
+    };
+    let result = shared.query(bbox, 10)?;
+    let expected = MapFile::open(file.path())?.query(bbox, 10)?;
+    assert_eq!(format!("{result:?}"), format!("{expected:?}"));
+    assert_eq!(result.pois.len(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_shared_read_with_corrupt_sub_file_size() -> Result<()> {
+    let mut bytes = features_fixture(true).build();
+
+    // the size of the only sub-file ends the header, the last block is read
+    // up to it
+    let header_size = u32::from_be_bytes(bytes[20..24].try_into().unwrap()) as usize;
+    bytes[24 + header_size - 8..24 + header_size].copy_from_slice(&(u64::MAX / 2).to_be_bytes());
+    let mut file = NamedTempFile::new()?;
+    file.write_all(&bytes)?;
+
+    let shared = SharedMapFile::open(file.path())?;
+    assert_eq!(shared.read_tile(10, 548, 336)?.pois.len(), 2);
+    match shared.read_tile(10, 549, 336) {
+        Err(MapforgeError::Io(err)) => assert_eq!(err.kind(), ErrorKind::UnexpectedEof),
+        result => panic!("expected an unexpected end of file, got {result:?}"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,174 @@
+mod common;
+
+use common::{signature, tile_block, MapFixture, PoiFixture, WayFixture};
+use std::borrow::Cow;
+
+use mapsforge_rs::{
+    types::{BoundingBox, MapFile, MapSlice},
+    MapforgeError, Result,
+};
+
+fn fixture(debug: bool) -> MapFixture {
+    let poi = PoiFixture {
+        signature: debug.then(|| "***POIStart1".to_string()),
+        lat_diff: -1_000,
+        lon_diff: 1_000,
+        name: Some("Café".to_string()),
+        ..Default::default()
+    }
+    .build();
+    let way = WayFixture {
+        signature: debug.then(|| "---WayStart1".to_string()),
+        sub_tile_bitmap: 0xffff,
+        name: Some("Hauptstraße".to_string()),
+        data_blocks: vec![vec![vec![(-130_000, 300_000), (-130_100, 300_100)]]],
+        ..Default::default()
+    }
+    .build();
+
+    MapFixture {
+        debug,
+        blocks: vec![
+            tile_block(
+                debug.then_some("###TileStart548,336"),
+                &[(1, 1), (0, 0), (0, 0), (0, 0), (0, 0)],
+                &[poi],
+                &[way],
+            ),
+            vec![],
+        ],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_read_tile_from_slice() -> Result<()> {
+    for debug in [false, true] {
+        let data = fixture(debug).build();
+        let map = MapSlice::new(&data)?;
+
+        let tile = map.read_tile(10, 548, 336)?;
+        assert_eq!(tile.pois[0].name.as_deref(), Some("Café"));
+        assert_eq!(tile.ways[0].name.as_deref(), Some("Hauptstraße"));
+        assert_eq!(tile.ways[0].coordinate_blocks[0][0].coordinates.len(), 2);
+
+        // the block without data
+        let tile = map.read_tile(10, 549, 336)?;
+        assert_eq!(tile.zoom_table, vec![(0, 0); 5]);
+        assert!(tile.pois.is_empty());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_slice_matches_map_file() -> Result<()> {
+    let fixture = fixture(true);
+    let data = fixture.build();
+    let file = fixture.write();
+
+    let map = MapSlice::new(&data)?;
+    let mut map_file = MapFile::open(file.path())?;
+
+    for index in 0..2 {
+        let entry = map.tile_index_entry(0, 548 + index as u32, 336)?;
+        assert_eq!(entry.offset_abs, map_file.tile_indices[0][index].offset_abs);
+    }
+
+    let from_slice = map.read_tile(8, 137, 84)?;
+    let from_file = map_file.read_tile(8, 137, 84)?;
+    assert_eq!(format!("{from_slice:?}"), format!("{from_file:?}"));
+
+    let bbox = BoundingBox {
+        min_lat: 52.0,
+        min_lon: 12.0,
+        max_lat: 53.0,
+        max_lon: 14.0,
+    };
+    let from_slice = map.query(bbox, 10)?;
+    let from_file = map_file.query(bbox, 10)?;
+    assert_eq!(format!("{from_slice:?}"), format!("{from_file:?}"));
+    assert_eq!(from_slice.pois.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_block_borrows_data() -> Result<()> {
+    let data = fixture(true).build();
+    let map = MapSlice::new(&data)?;
+
+    let block = map.block(0, 548, 336)?;
+    assert!(block.starts_with(&signature("###TileStart548,336")));
+    assert_eq!(block.as_ptr_range().end, data.as_ptr_range().end);
+    assert!(map.block(0, 549, 336)?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_tile_ref_borrows_strings() -> Result<()> {
+    let data = fixture(true).build();
+    let map = MapSlice::new(&data)?;
+    let borrowed = |value: &Cow<str>| match value {
+        Cow::Borrowed(value) => data.as_ptr_range().contains(&value.as_ptr()),
+        Cow::Owned(_) => false,
+    };
+
+    let tile = map.read_tile_ref(10, 548, 336)?;
+    assert!(borrowed(tile.pois[0].name.as_ref().unwrap()));
+    assert!(borrowed(tile.ways[0].name.as_ref().unwrap()));
+    assert_eq!(tile.ways[0].tags.last().unwrap().value, "Hauptstraße");
+
+    let mut map_file = MapFile::open(fixture(true).write().path())?;
+    let from_file = map_file.read_tile(10, 548, 336)?;
+    assert_eq!(format!("{:?}", tile.into_owned()), format!("{from_file:?}"));
+
+    Ok(())
+}
+
+#[test]
+fn test_decoded_tile_outlives_data() -> Result<()> {
+    let tile = {
+        let data = fixture(false).build();
+        MapSlice::new(&data)?.read_tile(10, 548, 336)?
+    };
+    assert_eq!(tile.pois[0].name.as_deref(), Some("Café"));
+
+    Ok(())
+}
+
+#[test]
+fn test_tile_index_entry_outside_of_the_map() -> Result<()> {
+    let data = fixture(false).build();
+    let map = MapSlice::new(&data)?;
+
+    assert!(matches!(map.tile_index_entry(0, 547, 336), Err(MapforgeError::TileOutOfBounds(10, 547, 336))));
+    assert!(matches!(map.block(0, 548, 337), Err(MapforgeError::TileOutOfBounds(10, 548, 337))));
+    assert!(matches!(map.tile_index_entry(1, 548, 336), Err(MapforgeError::InvalidZoomIntervalIndex(1))));
+
+    Ok(())
+}
+
+#[test]
+fn test_truncated_slice() {
+    let data = fixture(false).build();
+
+    // the tile index is cut off
+    let header_size = u32::from_be_bytes(data[20..24].try_into().unwrap()) as usize;
+    let result = MapSlice::new(&data[..24 + header_size + 3]);
+    assert!(matches!(result, Err(MapforgeError::Io(_))));
+
+    // the tile data is cut off
+    let map = MapSlice::new(&data[..data.len() - 4]).unwrap();
+    assert!(matches!(map.read_tile(10, 548, 336), Err(MapforgeError::Io(_))));
+}
+
+#[test]
+fn test_invalid_index_signature_in_slice() {
+    let mut data = fixture(true).build();
+    let header_size = u32::from_be_bytes(data[20..24].try_into().unwrap()) as usize;
+    data[24 + header_size] = b'-';
+
+    assert!(matches!(MapSlice::new(&data), Err(MapforgeError::InvalidIndexSignature)));
+}
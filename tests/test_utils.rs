@@ -1,24 +1,24 @@
-use std::io::BufReader;
-use mapsforge_rs::utils::{read_vbe_u, read_vbe_u_int, read_vbe_s_int};
+use std::io::{BufReader, Cursor};
+use mapsforge_rs::utils::{read_string, read_vbe_u_int, read_vbe_s_int};
 
 #[test]
-fn test_read_vbe_u() {
+fn test_read_string() {
     // Test simple string
     let data = [0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f]; // "Hello" with length 5
-    let mut reader = BufReader::new(&data[..]);
-    assert_eq!(read_vbe_u(&mut reader).unwrap(), "Hello");
+    let mut reader = Cursor::new(&data[..]);
+    assert_eq!(read_string(&mut reader).unwrap(), "Hello");
 
     // Test empty string
     let data = [0x00];
-    let mut reader = BufReader::new(&data[..]);
-    assert_eq!(read_vbe_u(&mut reader).unwrap(), "");
+    let mut reader = Cursor::new(&data[..]);
+    assert_eq!(read_string(&mut reader).unwrap(), "");
 
     // Test string with multi-byte length
     let long_string = "A".repeat(130);
     let mut data = vec![0x82, 0x01]; // Length 130 in VBE format
     data.extend(long_string.as_bytes());
-    let mut reader = BufReader::new(&data[..]);
-    assert_eq!(read_vbe_u(&mut reader).unwrap(), long_string);
+    let mut reader = Cursor::new(&data[..]);
+    assert_eq!(read_string(&mut reader).unwrap(), long_string);
 }
 
 #[test]
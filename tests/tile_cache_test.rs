@@ -0,0 +1,169 @@
+mod common;
+
+use std::io::Cursor;
+
+use common::{tile_block, MapFixture, PoiFixture, WayFixture};
+use mapsforge_rs::{
+    types::{MapFile, MapFileOptions, Tile, TileCache, POI},
+    Result,
+};
+
+fn fixture() -> MapFixture {
+    let poi = |zoom: u8| {
+        PoiFixture {
+            name: Some(format!("poi {zoom}")),
+            ..Default::default()
+        }
+        .build()
+    };
+    let way = |zoom: u8, sub_tile_bitmap: u16| {
+        WayFixture {
+            name: Some(format!("way {zoom}")),
+            sub_tile_bitmap,
+            data_blocks: vec![vec![vec![(-130_000, 300_000), (-130_100, 300_100)]]],
+            ..Default::default()
+        }
+        .build()
+    };
+
+    MapFixture {
+        blocks: vec![
+            tile_block(
+                None,
+                &[(1, 1), (0, 0), (1, 1), (1, 1), (1, 0)],
+                &[poi(8), poi(10), poi(11), poi(12)],
+                &[way(8, 0xffff), way(10, 0x8000), way(11, 0x0001)],
+            ),
+            tile_block(None, &[(1, 0), (0, 0), (0, 0), (0, 0), (0, 0)], &[poi(8)], &[]),
+        ],
+        ..Default::default()
+    }
+}
+
+fn cached(data: Vec<u8>) -> Result<MapFile<Cursor<Vec<u8>>>> {
+    let options = MapFileOptions {
+        tile_cache_size: 1 << 20,
+        ..Default::default()
+    };
+    MapFile::from_reader_with(Cursor::new(data), options)
+}
+
+#[test]
+fn test_cached_tiles_match_decoded_tiles() -> Result<()> {
+    let data = fixture().build();
+    let mut cached = cached(data.clone())?;
+    let mut uncached = MapFile::from_reader(Cursor::new(data))?;
+
+    let tiles = [
+        (8, 137, 84),
+        (9, 274, 168),
+        (10, 548, 336),
+        (10, 549, 336),
+        (11, 1096, 672),
+        (11, 1097, 673),
+        (12, 548 * 4, 336 * 4),
+        (12, 548 * 4 + 3, 336 * 4 + 3),
+        (12, 548 * 4 + 1, 336 * 4 + 2),
+    ];
+    // every tile twice, the second time from the cache
+    for (zoom, x, y) in tiles.iter().chain(tiles.iter()) {
+        let expected = uncached.read_tile(*zoom, *x, *y)?;
+        let tile = cached.read_tile(*zoom, *x, *y)?;
+        assert_eq!(format!("{tile:?}"), format!("{expected:?}"), "tile {zoom}/{x}/{y}");
+    }
+
+    let cache = cached.tile_cache.as_ref().unwrap();
+    assert_eq!(cache.tiles.len(), 2);
+    assert!(cache.size > 0 && cache.size <= cache.budget);
+
+    Ok(())
+}
+
+#[test]
+fn test_cached_tiles_are_not_read_again() -> Result<()> {
+    let mut map_file = cached(fixture().build())?;
+
+    let tile = map_file.read_tile(12, 548 * 4, 336 * 4)?;
+    assert_eq!(tile.ways.len(), 2);
+
+    // decoding the block again would fail now
+    let offset = map_file.tile_indices[0][0].offset_abs as usize;
+    map_file.reader.get_mut()[offset..].fill(0xff);
+
+    let tile = map_file.read_tile(10, 548, 336)?;
+    assert_eq!(tile.pois.len(), 2);
+    assert_eq!(tile.ways.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_tile_cache_budget() {
+    let tile = |name: &str| Tile {
+        pois: vec![POI {
+            name: Some(name.to_string()),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let size = tile("a").approximate_size();
+
+    let mut cache = TileCache::new(2 * size);
+    cache.insert(0, 1, 1, tile("a"));
+    cache.insert(0, 1, 2, tile("b"));
+    assert_eq!(cache.size, 2 * size);
+
+    // (0, 1, 1) was used last, so (0, 1, 2) is dropped
+    assert!(cache.get(0, 1, 1).is_some());
+    cache.insert(0, 1, 3, tile("c"));
+    assert!(cache.get(0, 1, 2).is_none());
+    assert_eq!(cache.get(0, 1, 3).unwrap().pois[0].name.as_deref(), Some("c"));
+    assert_eq!(cache.size, 2 * size);
+
+    // replacing a block doesn't count it twice
+    cache.insert(0, 1, 3, tile("d"));
+    assert_eq!(cache.size, 2 * size);
+
+    // a block larger than the budget isn't kept
+    let mut large = tile("e");
+    large.pois.push(Default::default());
+    large.pois.push(Default::default());
+    cache.insert(0, 2, 2, large);
+    assert!(cache.get(0, 2, 2).is_none());
+    assert_eq!(cache.tiles.len(), 2);
+
+    cache.clear();
+    assert_eq!(cache.size, 0);
+}
+
+#[test]
+fn test_cache_drops_blocks_while_reading() -> Result<()> {
+    let data = fixture().build();
+
+    // the budget of a cache that holds just the larger of the two blocks
+    let mut full = cached(data.clone())?;
+    full.read_tile(10, 548, 336)?;
+    full.read_tile(10, 549, 336)?;
+    let budget = full.tile_cache.as_ref().unwrap().tiles.iter().map(|(_, block)| block.approximate_size()).max().unwrap();
+
+    let options = MapFileOptions {
+        tile_cache_size: budget,
+        ..Default::default()
+    };
+    let mut cached = MapFile::from_reader_with(Cursor::new(data.clone()), options)?;
+    let mut uncached = MapFile::from_reader(Cursor::new(data))?;
+
+    // going back and forth between the blocks drops the other one every time
+    for (zoom, x, y) in [(10, 548, 336), (11, 1098, 672), (12, 548 * 4 + 3, 336 * 4 + 3), (10, 549, 336), (10, 548, 336)] {
+        let expected = uncached.read_tile(zoom, x, y)?;
+        let tile = cached.read_tile(zoom, x, y)?;
+        assert_eq!(format!("{tile:?}"), format!("{expected:?}"), "tile {zoom}/{x}/{y}");
+
+        let cache = cached.tile_cache.as_ref().unwrap();
+        let block_x = x >> (zoom - 10);
+        assert_eq!(cache.tiles.iter().map(|(&(_, x, _), _)| x).collect::<Vec<_>>(), vec![block_x]);
+        assert!(cache.size <= cache.budget);
+    }
+
+    Ok(())
+}
@@ -17,7 +17,7 @@ fn test_tile_index_reading() -> Result<()> {
         let expected_tiles = MapFile::calculate_total_tiles(
             &map_file.header.bounding_box,
             map_file.header.zoom_interval_configuration[0].base_zoom_level
-        );
+        )?;
         assert_eq!(first_interval.len(), expected_tiles as usize);
 
         
@@ -0,0 +1,275 @@
+mod common;
+
+use std::io::Cursor;
+
+use common::{tile_block, MapFixture, PoiFixture, WayFixture};
+use mapsforge_rs::{
+    types::{MapFile, MapSlice, SharedMapFile},
+    MapforgeError, Result,
+};
+
+fn fixture() -> MapFixture {
+    MapFixture {
+        blocks: vec![
+            tile_block(None, &[(0, 0), (0, 0), (0, 0), (0, 0), (0, 0)], &[], &[]),
+            vec![],
+        ],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_tile_range() {
+    let fixture = fixture();
+    let file = fixture.write();
+    let map_file = MapFile::open(file.path()).unwrap();
+
+    assert_eq!(
+        MapFile::tile_range(&map_file.header.bounding_box, 10),
+        (548, 336, 549, 336)
+    );
+    assert_eq!(MapFile::calculate_total_tiles(&map_file.header.bounding_box, 10).unwrap(), 2);
+}
+
+#[test]
+fn test_read_tile_on_base_zoom_level() -> Result<()> {
+    let file = fixture().write();
+    let mut map_file = MapFile::open(file.path())?;
+
+    let tile = map_file.read_tile(10, 548, 336)?;
+    assert_eq!(tile.zoom_table, vec![(0, 0); 5]);
+    assert!(tile.pois.is_empty());
+    assert!(tile.ways.is_empty());
+
+    // the second block has no data
+    let tile = map_file.read_tile(10, 549, 336)?;
+    assert_eq!(tile.zoom_table, vec![(0, 0); 5]);
+    assert!(tile.pois.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_read_tile_above_and_below_base_zoom_level() -> Result<()> {
+    let file = fixture().write();
+    let mut map_file = MapFile::open(file.path())?;
+
+    // served from the base tile (548, 336)
+    let tile = map_file.read_tile(12, 548 * 4 + 3, 336 * 4 + 1)?;
+    assert_eq!(tile.zoom_table.len(), 5);
+
+    // spans both base tiles
+    let tile = map_file.read_tile(8, 137, 84)?;
+    assert_eq!(tile.zoom_table.len(), 5);
+
+    Ok(())
+}
+
+#[test]
+fn test_read_tile_errors() {
+    let file = fixture().write();
+    let mut map_file = MapFile::open(file.path()).unwrap();
+
+    assert!(matches!(
+        map_file.read_tile(13, 0, 0),
+        Err(MapforgeError::InvalidZoomLevel(13))
+    ));
+    assert!(matches!(
+        map_file.read_tile(10, 550, 336),
+        Err(MapforgeError::TileOutOfBounds(10, 550, 336))
+    ));
+    assert!(matches!(
+        map_file.read_tile(10, 2048, 0),
+        Err(MapforgeError::TileOutOfBounds(10, 2048, 0))
+    ));
+}
+
+// POIs and ways starting on zoom levels 8, 10 and 12, named after their level
+fn zoom_levels_fixture() -> MapFixture {
+    let poi = |zoom: u8| {
+        PoiFixture {
+            name: Some(format!("poi {zoom}")),
+            ..Default::default()
+        }
+        .build()
+    };
+    let way = |zoom: u8| {
+        WayFixture {
+            name: Some(format!("way {zoom}")),
+            sub_tile_bitmap: 0xffff,
+            data_blocks: vec![vec![vec![(-130_000, 300_000), (-130_100, 300_100)]]],
+            ..Default::default()
+        }
+        .build()
+    };
+
+    MapFixture {
+        blocks: vec![
+            tile_block(
+                None,
+                &[(1, 1), (0, 0), (1, 1), (0, 0), (1, 0)],
+                &[poi(8), poi(10), poi(12)],
+                &[way(8), way(10)],
+            ),
+            tile_block(None, &[(1, 0), (0, 0), (0, 0), (0, 0), (0, 0)], &[poi(8)], &[]),
+        ],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_read_tile_decodes_features_of_zoom_level() -> Result<()> {
+    let file = zoom_levels_fixture().write();
+    let mut map_file = MapFile::open(file.path())?;
+
+    let names = |tile: &mapsforge_rs::types::Tile| {
+        let pois: Vec<_> = tile.pois.iter().map(|poi| poi.name.clone().unwrap()).collect();
+        let ways: Vec<_> = tile.ways.iter().map(|way| way.name.clone().unwrap()).collect();
+        (pois, ways)
+    };
+
+    let tile = map_file.read_tile(10, 548, 336)?;
+    assert_eq!(names(&tile), (vec!["poi 8".into(), "poi 10".into()], vec!["way 8".into(), "way 10".into()]));
+    // the zoom table is always complete
+    assert_eq!(tile.zoom_table, vec![(1, 1), (0, 0), (1, 1), (0, 0), (1, 0)]);
+
+    let tile = map_file.read_tile(12, 548 * 4, 336 * 4)?;
+    assert_eq!(names(&tile).0, ["poi 8", "poi 10", "poi 12"]);
+    assert_eq!(names(&tile).1, ["way 8", "way 10"]);
+
+    // the ways are found through the first way offset after skipping POIs
+    let tile = map_file.read_tile(9, 274, 168)?;
+    assert_eq!(names(&tile).0, ["poi 8", "poi 8"]);
+    assert_eq!(names(&tile).1, ["way 8"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_query_decodes_features_of_zoom_level() -> Result<()> {
+    let file = zoom_levels_fixture().write();
+    let mut map_file = MapFile::open(file.path())?;
+    let bbox = map_file.header.bounding_box;
+
+    let result = map_file.query(bbox, 8)?;
+    assert_eq!(result.ways.len(), 1);
+
+    let result = map_file.query(bbox, 11)?;
+    assert_eq!(result.ways.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_tile_bitmask() -> Result<()> {
+    assert_eq!(MapFile::tile_bitmask(10, 10, 548, 336)?, None);
+    assert_eq!(MapFile::tile_bitmask(10, 9, 274, 168)?, None);
+
+    assert_eq!(MapFile::tile_bitmask(10, 11, 1096, 672)?, Some(0xcc00));
+    assert_eq!(MapFile::tile_bitmask(10, 11, 1097, 672)?, Some(0x3300));
+    assert_eq!(MapFile::tile_bitmask(10, 11, 1096, 673)?, Some(0x00cc));
+    assert_eq!(MapFile::tile_bitmask(10, 11, 1097, 673)?, Some(0x0033));
+
+    assert_eq!(MapFile::tile_bitmask(10, 12, 548 * 4, 336 * 4)?, Some(0x8000));
+    assert_eq!(MapFile::tile_bitmask(10, 12, 548 * 4 + 2, 336 * 4 + 1)?, Some(0x0200));
+    assert_eq!(MapFile::tile_bitmask(10, 12, 548 * 4 + 3, 336 * 4 + 3)?, Some(0x0001));
+    // deeper zoom levels use the sub-tile containing the tile
+    assert_eq!(MapFile::tile_bitmask(10, 14, (548 * 4 + 1) * 4 + 3, 336 * 16)?, Some(0x4000));
+
+    // the tile numbers would be shifted by 32 bits or more
+    assert!(matches!(MapFile::tile_bitmask(0, 34, 0, 0), Err(MapforgeError::InvalidZoomLevel(34))));
+    assert!(matches!(MapFile::tile_bitmask(10, 255, 0, 0), Err(MapforgeError::InvalidZoomLevel(255))));
+
+    Ok(())
+}
+
+#[test]
+fn test_get_tiles() -> Result<()> {
+    assert_eq!(MapFile::get_tiles(52.35, 12.85, 10)?, (548, 336));
+    assert_eq!(MapFile::get_tiles(52.35, 12.85, 0)?, (0, 0));
+    assert_eq!(MapFile::get_tiles(-85.0, 179.999_999_99, 32)?.0, u32::MAX);
+
+    assert!(matches!(MapFile::get_tiles(52.35, 12.85, 40), Err(MapforgeError::InvalidZoomLevel(40))));
+    assert!(matches!(MapFile::get_tiles(52.35, -190.0, 10), Err(MapforgeError::TileOutOfBounds(10, 0, 336))));
+    assert!(matches!(MapFile::get_tiles(89.0, 12.85, 10), Err(MapforgeError::TileOutOfBounds(10, 548, 0))));
+    assert!(matches!(MapFile::get_tiles(f64::NAN, 12.85, 10), Err(MapforgeError::TileOutOfBounds(..))));
+
+    Ok(())
+}
+
+#[test]
+fn test_read_tile_beyond_tile_numbers() {
+    // a zoom interval reaching far deeper than any tile number
+    let fixture = |max_zoom_level: u8| MapFixture {
+        max_zoom_level,
+        blocks: vec![tile_block(None, &vec![(0, 0); usize::from(max_zoom_level) - 8 + 1], &[], &[]), vec![]],
+        ..Default::default()
+    };
+    let data = fixture(60).build();
+    assert!(matches!(MapFile::from_reader(Cursor::new(data.clone())), Err(MapforgeError::InvalidZoomInterval(10, 8, 60))));
+    assert!(matches!(MapSlice::new(&data), Err(MapforgeError::InvalidZoomInterval(10, 8, 60))));
+    let file = fixture(60).write();
+    assert!(matches!(SharedMapFile::open(file.path()), Err(MapforgeError::InvalidZoomInterval(10, 8, 60))));
+
+    // the deepest zoom level with tile numbers
+    let mut map_file = MapFile::from_reader(Cursor::new(fixture(32).build())).unwrap();
+    assert!(matches!(map_file.read_tile(33, 0, 0), Err(MapforgeError::InvalidZoomLevel(33))));
+    assert!(map_file.read_tile(32, 548 << 22, 336 << 22).is_ok());
+}
+
+#[test]
+fn test_read_tile_skips_ways_outside_of_sub_tile() -> Result<()> {
+    let way = |name: &str, sub_tile_bitmap| {
+        WayFixture {
+            name: Some(name.to_string()),
+            sub_tile_bitmap,
+            tag_ids: vec![],
+            data_blocks: vec![vec![vec![(-100, 100), (-200, 200)]]],
+            ..Default::default()
+        }
+        .build()
+    };
+
+    let file = MapFixture {
+        blocks: vec![
+            tile_block(
+                None,
+                &[(0, 3), (0, 0), (0, 0), (0, 0), (0, 0)],
+                &[],
+                &[way("top left", 0x8000), way("bottom right", 0x0001), way("everywhere", 0xffff)],
+            ),
+            vec![],
+        ],
+        ..Default::default()
+    }
+    .write();
+    let mut map_file = MapFile::open(file.path())?;
+
+    let mut names = |zoom, x, y| -> Result<Vec<String>> {
+        let tile = map_file.read_tile(zoom, x, y)?;
+        Ok(tile.ways.into_iter().map(|way| way.name.unwrap()).collect())
+    };
+
+    assert_eq!(names(10, 548, 336)?, ["top left", "bottom right", "everywhere"]);
+    assert_eq!(names(11, 1096, 672)?, ["top left", "everywhere"]);
+    assert_eq!(names(11, 1097, 673)?, ["bottom right", "everywhere"]);
+    assert_eq!(names(11, 1097, 672)?, ["everywhere"]);
+    assert_eq!(names(12, 548 * 4 + 1, 336 * 4)?, ["everywhere"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_read_tile_from_memory() -> Result<()> {
+    let mut map_file = MapFile::from_reader(Cursor::new(zoom_levels_fixture().build()))?;
+    assert_eq!(map_file.tile_indices[0].len(), 2);
+
+    let tile = map_file.read_tile(10, 548, 336)?;
+    assert_eq!(tile.pois.len(), 2);
+    assert_eq!(tile.ways[1].name.as_deref(), Some("way 10"));
+
+    // the source can be handed back
+    assert!(map_file.reader.into_inner().starts_with(b"mapsforge binary OSM"));
+
+    Ok(())
+}
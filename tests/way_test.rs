@@ -0,0 +1,211 @@
+mod common;
+
+use std::io::Cursor;
+
+use common::{tile_block, MapFixture, WayFixture};
+use mapsforge_rs::{
+    types::{LatLong, MapFile, TileContext},
+    way::process_ways,
+    BoundingBox, Result,
+};
+
+fn way_tags() -> Vec<String> {
+    vec!["highway=residential".to_string(), "building=yes".to_string()]
+}
+
+fn assert_position(position: &LatLong, latitude: f64, longitude: f64) {
+    assert!((position.latitude - latitude).abs() < 1e-9, "{position:?}");
+    assert!((position.longitude - longitude).abs() < 1e-9, "{position:?}");
+}
+
+#[test]
+fn test_process_ways_keeps_coordinate_blocks() -> Result<()> {
+    let mut data = WayFixture {
+        sub_tile_bitmap: 0xffff,
+        layer: 5,
+        tag_ids: vec![0],
+        name: Some("Hauptstraße".to_string()),
+        reference: Some("B 1".to_string()),
+        label_position: Some((-50, 100)),
+        data_blocks: vec![vec![vec![(-1_000, 2_000), (-1_500, 2_500)]]],
+        ..Default::default()
+    }
+    .build();
+    // a multipolygon: two outer rings, the first one with a hole
+    data.extend(
+        WayFixture {
+            tag_ids: vec![1],
+            data_blocks: vec![
+                vec![
+                    vec![(-100, 100), (-100, 900), (-900, 900), (-100, 100)],
+                    vec![(-300, 300), (-300, 600), (-600, 600), (-300, 300)],
+                ],
+                vec![vec![(-2_000, 2_000), (-2_000, 3_000), (-3_000, 3_000), (-2_000, 2_000)]],
+            ],
+            ..Default::default()
+        }
+        .build(),
+    );
+
+    let tile = TileContext {
+        origin: LatLong { latitude: 52.0, longitude: 13.0 },
+        ..Default::default()
+    };
+    let mut reader = Cursor::new(&data[..]);
+    let ways = process_ways(&mut reader, 2, &way_tags(), &tile, None)?;
+
+    assert_eq!(ways.len(), 2);
+
+    let way = &ways[0];
+    assert_eq!(way.sub_tile_bitmap, 0xffff);
+    assert_eq!(way.layer, 5);
+    assert_eq!(way.name.as_deref(), Some("Hauptstraße"));
+    assert_eq!(way.reference.as_deref(), Some("B 1"));
+    assert_eq!(way.house_number, None);
+    assert!(!way.double_delta_encoding);
+    assert_eq!(way.coordinate_blocks.len(), 1);
+    assert_eq!(way.coordinate_blocks[0].len(), 1);
+    assert_eq!(way.coordinate_blocks[0][0].coordinates.len(), 2);
+    assert_position(&way.coordinate_blocks[0][0].initial_position, 51.999, 13.002);
+    assert_position(&way.coordinate_blocks[0][0].coordinates[0], 51.999, 13.002);
+    assert_position(way.label_position.as_ref().unwrap(), 51.99895, 13.0021);
+
+    let way = &ways[1];
+    assert_eq!(way.tags.as_ref().unwrap()[0].key, "building");
+    // the data blocks stay apart, the first one is the polygon with the hole
+    let node_counts: Vec<Vec<_>> = way
+        .coordinate_blocks
+        .iter()
+        .map(|blocks| blocks.iter().map(|block| block.coordinates.len()).collect())
+        .collect();
+    assert_eq!(node_counts, [vec![4, 4], vec![4]]);
+    assert_position(&way.coordinate_blocks[0][1].initial_position, 51.9997, 13.0003);
+    assert_position(&way.coordinate_blocks[1][0].initial_position, 51.998, 13.002);
+
+    assert_eq!(reader.position(), data.len() as u64);
+
+    Ok(())
+}
+
+#[test]
+fn test_query_returns_ways_inside_bounding_box() -> Result<()> {
+    let way = |lat_diff, lon_diff| {
+        WayFixture {
+            tag_ids: vec![0],
+            data_blocks: vec![vec![vec![(lat_diff, lon_diff), (lat_diff, lon_diff)]]],
+            ..Default::default()
+        }
+        .build()
+    };
+
+    let file = MapFixture {
+        way_tags: way_tags(),
+        blocks: vec![
+            tile_block(None, &[(0, 0), (0, 0), (0, 2), (0, 0), (0, 0)], &[], &[way(-130_000, 300_000), way(-10_000, 10_000)]),
+            vec![],
+        ],
+        ..Default::default()
+    }
+    .write();
+    let mut map_file = MapFile::open(file.path())?;
+
+    let tile = map_file.read_tile(10, 548, 336)?;
+    assert_eq!(tile.ways.len(), 2);
+    let origin = LatLong {
+        latitude: MapFile::tiley2lat(336, 10),
+        longitude: MapFile::tilex2long(548, 10),
+    };
+    assert_position(
+        &tile.ways[0].coordinate_blocks[0][0].initial_position,
+        origin.latitude - 0.13,
+        origin.longitude + 0.3,
+    );
+
+    let bbox = BoundingBox {
+        min_lat: 52.3,
+        min_lon: 12.9,
+        max_lat: 52.4,
+        max_lon: 13.05,
+    };
+    let result = map_file.query(bbox, 10)?;
+    assert_eq!(result.ways.len(), 1);
+
+    Ok(())
+}
+
+// small deterministic generator so failures can be reproduced
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn range(&mut self, min: i64, max: i64) -> i64 {
+        min + (self.next() % (max - min + 1) as u64) as i64
+    }
+}
+
+fn random_nodes(rng: &mut XorShift) -> Vec<(i64, i64)> {
+    let count = rng.range(2, 60) as usize;
+    let mut node = (rng.range(-200_000, 0), rng.range(0, 300_000));
+    (0..count)
+        .map(|_| {
+            node = (node.0 + rng.range(-5_000, 5_000), node.1 + rng.range(-5_000, 5_000));
+            node
+        })
+        .collect()
+}
+
+fn assert_round_trip(double_delta: bool) -> Result<()> {
+    let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+    let origin = LatLong { latitude: 52.48, longitude: 12.65 };
+    let tile = TileContext {
+        origin,
+        ..Default::default()
+    };
+
+    for _ in 0..100 {
+        let data_blocks = vec![vec![random_nodes(&mut rng), random_nodes(&mut rng)]];
+        let data = WayFixture {
+            double_delta,
+            data_blocks: data_blocks.clone(),
+            ..Default::default()
+        }
+        .build();
+
+        let mut reader = Cursor::new(&data[..]);
+        let ways = process_ways(&mut reader, 1, &way_tags(), &tile, None)?;
+        let way = &ways[0];
+
+        assert_eq!(way.double_delta_encoding, double_delta);
+        assert_eq!(way.coordinate_blocks.len(), 1);
+        assert_eq!(way.coordinate_blocks[0].len(), 2);
+        for (block, nodes) in way.coordinate_blocks[0].iter().zip(&data_blocks[0]) {
+            assert_eq!(block.coordinates.len(), nodes.len());
+            for (position, &(lat, lon)) in block.coordinates.iter().zip(nodes) {
+                assert_position(
+                    position,
+                    origin.latitude + lat as f64 / 1_000_000.0,
+                    origin.longitude + lon as f64 / 1_000_000.0,
+                );
+            }
+            assert_eq!(block.initial_position.latitude, block.coordinates[0].latitude);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_single_delta_round_trip() -> Result<()> {
+    assert_round_trip(false)
+}
+
+#[test]
+fn test_double_delta_round_trip() -> Result<()> {
+    assert_round_trip(true)
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use common::{position, way};
+use mapsforge_rs::types::POI;
+
+const LINE: &[(f64, f64)] = &[(52.5, 13.25), (52.75, 13.5)];
+const OUTER: &[(f64, f64)] = &[(52.0, 13.0), (52.0, 14.0), (53.0, 14.0), (52.0, 13.0)];
+const HOLE: &[(f64, f64)] = &[(52.1, 13.5), (52.1, 13.6), (52.2, 13.6), (52.1, 13.5)];
+const ISLAND: &[(f64, f64)] = &[(54.0, 15.0), (54.0, 16.0), (55.0, 16.0), (54.0, 15.0)];
+
+#[test]
+fn test_wkt() {
+    let poi = POI {
+        position: position(52.5, 13.25),
+        ..Default::default()
+    };
+    assert_eq!(poi.to_wkt(), "POINT (13.25 52.5)");
+
+    assert_eq!(way(&[&[LINE]]).to_wkt(), "LINESTRING (13.25 52.5, 13.5 52.75)");
+    assert_eq!(way(&[&[LINE], &[LINE]]).to_wkt(), "MULTILINESTRING ((13.25 52.5, 13.5 52.75), (13.25 52.5, 13.5 52.75))");
+    assert_eq!(way(&[]).to_wkt(), "LINESTRING EMPTY");
+    assert_eq!(
+        way(&[&[OUTER, HOLE]]).to_wkt(),
+        "POLYGON ((13 52, 14 52, 14 53, 13 52), (13.5 52.1, 13.6 52.1, 13.6 52.2, 13.5 52.1))"
+    );
+    assert_eq!(
+        way(&[&[OUTER], &[ISLAND]]).to_wkt(),
+        "MULTIPOLYGON (((13 52, 14 52, 14 53, 13 52)), ((15 54, 16 54, 16 55, 15 54)))"
+    );
+}
+
+#[test]
+fn test_wkb_point() {
+    let poi = POI {
+        position: position(52.5, 13.25),
+        ..Default::default()
+    };
+
+    let mut expected = vec![1, 1, 0, 0, 0];
+    expected.extend(13.25f64.to_le_bytes());
+    expected.extend(52.5f64.to_le_bytes());
+    assert_eq!(poi.to_wkb(), expected);
+}
+
+#[test]
+fn test_wkb_ways() {
+    let line = way(&[&[LINE]]).to_wkb();
+    assert_eq!(line[..9], [1, 2, 0, 0, 0, 2, 0, 0, 0]);
+    assert_eq!(line.len(), 9 + 2 * 16);
+
+    // ring count followed by the point count of the first ring
+    let polygon = way(&[&[OUTER, HOLE]]).to_wkb();
+    assert_eq!(polygon[..13], [1, 3, 0, 0, 0, 2, 0, 0, 0, 4, 0, 0, 0]);
+    assert_eq!(polygon.len(), 9 + 2 * (4 + 4 * 16));
+
+    // every polygon of a multipolygon has its own byte order and type
+    let multi_polygon = way(&[&[OUTER], &[ISLAND]]).to_wkb();
+    assert_eq!(multi_polygon[..14], [1, 6, 0, 0, 0, 2, 0, 0, 0, 1, 3, 0, 0, 0]);
+    assert_eq!(multi_polygon.len(), 9 + 2 * (9 + 4 + 4 * 16));
+
+    let multi_line = way(&[&[LINE], &[LINE]]).to_wkb();
+    assert_eq!(multi_line[..14], [1, 5, 0, 0, 0, 2, 0, 0, 0, 1, 2, 0, 0, 0]);
+}
@@ -0,0 +1,257 @@
+mod common;
+
+use std::io::Cursor;
+
+use common::position;
+use mapsforge_rs::{
+    types::{BoundingBox, LatLong, MapFile, MapWriter, Tag, Way, WayCoordinateBlock, ZoomInterval, POI},
+    header::DEBUG_INFO_MASK,
+    MapforgeError, Result,
+};
+
+fn poi(latitude: f64, longitude: f64, name: &str, tags: &[(&str, &str)]) -> POI {
+    POI {
+        position: position(latitude, longitude),
+        layer: 5,
+        tags: Some(tags.iter().map(|(key, value)| Tag::new(key, value)).collect()),
+        name: Some(name.to_string()),
+        ..Default::default()
+    }
+}
+
+fn way(nodes: &[(f64, f64)], name: &str, double_delta: bool) -> Way {
+    let coordinates: Vec<LatLong> = nodes.iter().map(|&(lat, lon)| position(lat, lon)).collect();
+    Way {
+        layer: 7,
+        tags: Some(vec![Tag::new("highway", "primary"), Tag::new("name", name)]),
+        name: Some(name.to_string()),
+        reference: Some("B 96".to_string()),
+        label_position: Some(coordinates[1]),
+        coordinate_blocks: vec![vec![WayCoordinateBlock::new(coordinates)]],
+        double_delta_encoding: double_delta,
+        ..Default::default()
+    }
+}
+
+// covers the tiles (548, 336) and (549, 336) on zoom level 10
+fn writer() -> MapWriter {
+    let bbox = BoundingBox {
+        min_lat: 52.3,
+        min_lon: 12.8,
+        max_lat: 52.4,
+        max_lon: 13.2,
+    };
+    let mut writer = MapWriter::new(bbox, vec![ZoomInterval::new(7, 5, 9), ZoomInterval::new(10, 10, 14)]);
+    writer.header.created_by = Some("mapsforge-rs".to_string());
+
+    writer.add_poi(poi(52.35, 12.85, "west", &[("amenity", "cafe")]), 8);
+    writer.add_poi(poi(52.36, 13.15, "east", &[("amenity", "cafe"), ("wheelchair", "yes")]), 12);
+    writer.add_poi(POI { elevation: Some(-12), house_number: Some("3a".to_string()), ..poi(52.38, 13.1, "deep", &[]) }, 14);
+
+    // crosses from tile 548 into tile 549
+    writer.add_way(way(&[(52.31, 12.9), (52.33, 12.95), (52.34, 13.1), (52.35, 13.12)], "Hauptstraße", false), 10);
+    writer.add_way(way(&[(52.39, 13.16), (52.391, 13.161), (52.393, 13.165)], "Nebenstraße", true), 6);
+
+    writer
+}
+
+fn read(writer: &MapWriter) -> Result<MapFile<Cursor<Vec<u8>>>> {
+    let mut bytes = vec![];
+    writer.write_to(&mut bytes)?;
+    MapFile::from_reader(Cursor::new(bytes))
+}
+
+fn assert_close(actual: LatLong, expected: LatLong) {
+    assert!((actual.latitude - expected.latitude).abs() < 2e-6, "{actual:?} != {expected:?}");
+    assert!((actual.longitude - expected.longitude).abs() < 2e-6, "{actual:?} != {expected:?}");
+}
+
+#[test]
+fn test_written_header() -> Result<()> {
+    let map_file = read(&writer())?;
+    let header = &map_file.header;
+
+    assert_eq!(header.created_by.as_deref(), Some("mapsforge-rs"));
+    assert_eq!(header.file_size, map_file.reader.get_ref().len() as u64);
+    // the most frequent tag gets the first id
+    assert_eq!(header.poi_tags, ["amenity=cafe", "wheelchair=yes"]);
+    assert_eq!(header.way_tags, ["highway=primary"]);
+    assert_eq!(header.zoom_interval_configuration[1].base_zoom_level, 10);
+    assert_eq!(map_file.tile_indices[1].len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_written_features_round_trip() -> Result<()> {
+    let writer = writer();
+    let mut map_file = read(&writer)?;
+
+    let west = map_file.read_tile(14, 548 << 4, 336 << 4)?;
+    assert_eq!(west.zoom_table, [(1, 1), (0, 0), (0, 0), (0, 0), (0, 0)]);
+    assert_eq!(west.pois.len(), 1);
+    let poi = &west.pois[0];
+    assert_eq!(poi.name.as_deref(), Some("west"));
+    assert_eq!(poi.layer, 5);
+    assert_eq!(poi.tags.as_ref().unwrap()[0].value, "cafe");
+    assert_close(poi.position, position(52.35, 12.85));
+
+    let way = &map_file.read_tile(10, 548, 336)?.ways[0];
+    assert_eq!(way.name.as_deref(), Some("Hauptstraße"));
+    assert_eq!(way.reference.as_deref(), Some("B 96"));
+    assert_eq!(way.layer, 7);
+    assert_eq!(way.tag_ids, [0]);
+    let expected = &writer.ways[0].0.coordinate_blocks[0][0].coordinates;
+    for (node, expected) in way.coordinate_blocks[0][0].coordinates.iter().zip(expected) {
+        assert_close(*node, *expected);
+    }
+    assert_close(way.label_position.unwrap(), position(52.33, 12.95));
+
+    // visible from zoom level 12 and 14 on
+    assert!(map_file.read_tile(10, 549, 336)?.pois.is_empty());
+    let east = map_file.read_tile(12, 549 << 2, 336 << 2)?;
+    assert_eq!(east.pois.iter().map(|poi| poi.name.as_deref().unwrap()).collect::<Vec<_>>(), ["east"]);
+    let east = map_file.read_tile(14, (549 << 4) + 15, 336 << 4)?;
+    let deep = east.pois.iter().find(|poi| poi.name.as_deref() == Some("deep")).unwrap();
+    assert_eq!(deep.elevation, Some(-12));
+    assert_eq!(deep.house_number.as_deref(), Some("3a"));
+
+    // the double delta encoded way is in the interval of the lower zoom levels
+    let overview = map_file.read_tile(6, 34, 21)?;
+    assert_eq!(overview.ways.len(), 1);
+    assert!(overview.ways[0].double_delta_encoding);
+    let expected = &writer.ways[1].0.coordinate_blocks[0][0].coordinates;
+    for (node, expected) in overview.ways[0].coordinate_blocks[0][0].coordinates.iter().zip(expected) {
+        assert_close(*node, *expected);
+    }
+    assert!(overview.pois.is_empty());
+    let overview = map_file.read_tile(9, 274, 168)?;
+    assert_eq!(overview.pois.iter().map(|poi| poi.name.as_deref().unwrap()).collect::<Vec<_>>(), ["west"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_written_sub_tile_bitmaps() -> Result<()> {
+    let mut map_file = read(&writer())?;
+
+    // the way crossing both tiles is stored in each of them
+    let west = map_file.read_tile(10, 548, 336)?;
+    let east = map_file.read_tile(10, 549, 336)?;
+    assert_eq!(west.ways.len(), 1);
+    assert_eq!(east.ways.iter().filter(|way| way.name.as_deref() == Some("Hauptstraße")).count(), 1);
+    assert_ne!(west.ways[0].sub_tile_bitmap, 0xffff);
+
+    // sub-tiles the way doesn't touch skip it
+    let tiles_with_way = (0..4)
+        .flat_map(|y| (0..4).map(move |x| (x, y)))
+        .filter(|&(x, y)| {
+            let tile = map_file.read_tile(12, 548 * 4 + x, 336 * 4 + y).unwrap();
+            !tile.ways.is_empty()
+        })
+        .count();
+    assert_eq!(tiles_with_way as u32, west.ways[0].sub_tile_bitmap.count_ones());
+
+    Ok(())
+}
+
+#[test]
+fn test_written_debug_signatures() -> Result<()> {
+    let mut writer = writer();
+    writer.header.flags |= DEBUG_INFO_MASK;
+
+    let mut map_file = read(&writer)?;
+    let tile = map_file.read_tile(10, 548, 336)?;
+    assert_eq!(tile.debug_signature.as_deref(), Some("###TileStart548,336"));
+    assert_eq!(tile.pois[0].debug_signature.as_deref(), Some("***POIStart0"));
+    assert_eq!(tile.ways[0].debug_signature.as_deref(), Some("---WayStart0"));
+
+    Ok(())
+}
+
+#[test]
+fn test_feature_with_too_many_tags() {
+    let mut writer = writer();
+    let tags: Vec<(String, String)> = (0..16).map(|i| (format!("key{i}"), "value".to_string())).collect();
+    let tags: Vec<(&str, &str)> = tags.iter().map(|(key, value)| (key.as_str(), value.as_str())).collect();
+    writer.add_poi(poi(52.35, 12.85, "crowded", &tags), 10);
+
+    assert!(matches!(writer.write_to(&mut vec![]), Err(MapforgeError::TooManyFeatureTags(16))));
+}
+
+#[test]
+fn test_feature_with_invalid_layer() {
+    let mut high = writer();
+    high.add_poi(POI { layer: 16, ..poi(52.35, 12.85, "high", &[]) }, 10);
+    assert!(matches!(high.write_to(&mut vec![]), Err(MapforgeError::InvalidLayer(16))));
+
+    let mut low = writer();
+    low.add_way(Way { layer: -1, ..way(&[(52.31, 12.9), (52.33, 12.95)], "low", false) }, 10);
+    assert!(matches!(low.write_to(&mut vec![]), Err(MapforgeError::InvalidLayer(-1))));
+}
+
+#[test]
+fn test_written_clipped_ways() -> Result<()> {
+    let mut writer = writer();
+    writer.clip_ways = true;
+    let mut map_file = read(&writer)?;
+    let border = MapFile::tilex2long(549, 10);
+
+    // each tile holds the part of the way up to a little beyond its border
+    let west = &map_file.read_tile(10, 548, 336)?.ways[0];
+    let nodes = &west.coordinate_blocks[0][0].coordinates;
+    assert_eq!(nodes.len(), 3);
+    assert_close(nodes[0], position(52.31, 12.9));
+    assert!(nodes[2].longitude > border && nodes[2].longitude < 13.1);
+
+    let east = map_file.read_tile(10, 549, 336)?;
+    let east = east.ways.iter().find(|way| way.name.as_deref() == Some("Hauptstraße")).unwrap();
+    let nodes = &east.coordinate_blocks[0][0].coordinates;
+    assert!(nodes[0].longitude < border && nodes[0].longitude > 12.95);
+    assert_close(nodes[nodes.len() - 1], position(52.35, 13.12));
+
+    Ok(())
+}
+
+#[test]
+fn test_written_way_data_blocks() -> Result<()> {
+    let ring = |lat: f64, lon: f64, size: f64| {
+        let coordinates = vec![
+            position(lat, lon),
+            position(lat, lon + size),
+            position(lat + size, lon + size),
+            position(lat + size, lon),
+            position(lat, lon),
+        ];
+        WayCoordinateBlock::new(coordinates)
+    };
+
+    // a lake with an island and a second lake, all within tile 548
+    let lakes = Way {
+        layer: 5,
+        tags: Some(vec![Tag::new("natural", "water")]),
+        coordinate_blocks: vec![vec![ring(52.32, 12.85, 0.04), ring(52.33, 12.86, 0.01)], vec![ring(52.37, 12.9, 0.02)]],
+        ..Default::default()
+    };
+    let mut writer = writer();
+    writer.clip_ways = true;
+    writer.add_way(lakes.clone(), 10);
+
+    let mut map_file = read(&writer)?;
+    let tile = map_file.read_tile(10, 548, 336)?;
+    let way = tile.ways.iter().find(|way| way.tag_ids == [1]).unwrap();
+
+    let node_counts: Vec<Vec<_>> = way
+        .coordinate_blocks
+        .iter()
+        .map(|blocks| blocks.iter().map(|block| block.coordinates.len()).collect())
+        .collect();
+    assert_eq!(node_counts, [vec![5, 5], vec![5]]);
+    for (blocks, expected) in way.coordinate_blocks.iter().flatten().zip(lakes.coordinate_blocks.iter().flatten()) {
+        for (node, expected) in blocks.coordinates.iter().zip(&expected.coordinates) {
+            assert_close(*node, *expected);
+        }
+    }
+
+    Ok(())
+}